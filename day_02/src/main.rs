@@ -3,16 +3,16 @@ use intcode_computer::*;
 fn main() {
     // PART 1
     // INPUT
-    let mut intcode = [
+    let mut intcode = vec![
         1, 0, 0, 3, 1, 1, 2, 3, 1, 3, 4, 3, 1, 5, 0, 3, 2, 1, 10, 19, 1, 6, 19, 23, 2, 23, 6, 27,
         2, 6, 27, 31, 2, 13, 31, 35, 1, 10, 35, 39, 2, 39, 13, 43, 1, 43, 13, 47, 1, 6, 47, 51, 1,
         10, 51, 55, 2, 55, 6, 59, 1, 5, 59, 63, 2, 9, 63, 67, 1, 6, 67, 71, 2, 9, 71, 75, 1, 6, 75,
         79, 2, 79, 13, 83, 1, 83, 10, 87, 1, 13, 87, 91, 1, 91, 10, 95, 2, 9, 95, 99, 1, 5, 99,
         103, 2, 10, 103, 107, 1, 107, 2, 111, 1, 111, 5, 0, 99, 2, 14, 0, 0,
     ];
-    //Initialize the thing
-    intcode[1] = 12;
-    intcode[2] = 2;
+    //Initialize the thing: the "1202 program alarm" state
+    let alarm: Patch = "1=12, 2=2".parse().unwrap();
+    alarm.apply(&mut intcode).unwrap();
     //Execute the thing
     execute(&mut intcode, &vec![]);
     //Now read the thing >:3
@@ -20,29 +20,36 @@ fn main() {
 
     //PART 2
     //Is there any point in NOT trying every combination here?
-    'outer: for noun in 0..100 {
-        for verb in 0..100 {
-            let mut intcode = [
-                1, 0, 0, 3, 1, 1, 2, 3, 1, 3, 4, 3, 1, 5, 0, 3, 2, 1, 10, 19, 1, 6, 19, 23, 2, 23,
-                6, 27, 2, 6, 27, 31, 2, 13, 31, 35, 1, 10, 35, 39, 2, 39, 13, 43, 1, 43, 13, 47, 1,
-                6, 47, 51, 1, 10, 51, 55, 2, 55, 6, 59, 1, 5, 59, 63, 2, 9, 63, 67, 1, 6, 67, 71,
-                2, 9, 71, 75, 1, 6, 75, 79, 2, 79, 13, 83, 1, 83, 10, 87, 1, 13, 87, 91, 1, 91, 10,
-                95, 2, 9, 95, 99, 1, 5, 99, 103, 2, 10, 103, 107, 1, 107, 2, 111, 1, 111, 5, 0, 99,
-                2, 14, 0, 0,
-            ];
-            intcode[1] = noun;
-            intcode[2] = verb;
+    //(There isn't, but at least every core can get its share of combinations)
+    let program = [
+        1, 0, 0, 3, 1, 1, 2, 3, 1, 3, 4, 3, 1, 5, 0, 3, 2, 1, 10, 19, 1, 6, 19, 23, 2, 23, 6, 27,
+        2, 6, 27, 31, 2, 13, 31, 35, 1, 10, 35, 39, 2, 39, 13, 43, 1, 43, 13, 47, 1, 6, 47, 51, 1,
+        10, 51, 55, 2, 55, 6, 59, 1, 5, 59, 63, 2, 9, 63, 67, 1, 6, 67, 71, 2, 9, 71, 75, 1, 6, 75,
+        79, 2, 79, 13, 83, 1, 83, 10, 87, 1, 13, 87, 91, 1, 91, 10, 95, 2, 9, 95, 99, 1, 5, 99,
+        103, 2, 10, 103, 107, 1, 107, 2, 111, 1, 111, 5, 0, 99, 2, 14, 0, 0,
+    ];
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let pool = VmPool::new(&program, threads);
+    let combinations: Vec<(i64, i64)> = (0..100)
+        .flat_map(|noun| (0..100).map(move |verb| (noun, verb)))
+        .collect();
+    //Let's not be entirely horrible and stop when we reach the solution, ok
+    let solution = pool.find(
+        &combinations,
+        |&(noun, verb), mut intcode| {
+            intcode.write(1, noun);
+            intcode.write(2, verb);
             execute(&mut intcode, &vec![]);
-            if intcode[0] == 19690720 {
-                println!(
-                    "verb: {}, noun:{}, result:{}",
-                    verb,
-                    noun,
-                    100 * noun + verb
-                );
-                //Let's not be entirely horrible and stop when we reach the solution, ok
-                break 'outer;
-            }
-        }
+            intcode.read(0)
+        },
+        |&result| result == 19690720,
+    );
+    if let Some(((noun, verb), _)) = solution {
+        println!(
+            "verb: {}, noun:{}, result:{}",
+            verb,
+            noun,
+            100 * noun + verb
+        );
     }
 }
@@ -47,7 +47,7 @@ impl std::ops::AddAssign for Point {
 // https://en.wikipedia.org/wiki/Taxicab_geometry
 impl Point {
     fn manhattan(&self) -> i32 {
-        return self.x.abs() + self.y.abs();
+        self.x.abs() + self.y.abs()
     }
 }
 
@@ -69,7 +69,7 @@ fn main() {
             String::from(wire).split(",").for_each(|segment| {
                 //Parse the direction from the first character.
                 //This lets us create unit increments in the four directions.
-                let direction = Dir::from_char(segment.chars().nth(0).unwrap());
+                let direction = Dir::from_char(segment.chars().next().unwrap());
                 //The rest of the segment data is its length: parse it, and for every step,
                 for _ in 0..segment[1..].parse().unwrap() {
                     //Move to the next point on that segment
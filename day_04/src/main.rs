@@ -22,11 +22,11 @@ fn satisfies_conditions(password: Vec<u32>) -> bool {
     //For each pair of consecutive digits in the password,
     for window in password.windows(2) {
         //First digit bigger than second digit: abort
-        if window.iter().nth(0).unwrap() > window.iter().nth(1).unwrap() {
+        if window.first().unwrap() > window.get(1).unwrap() {
             return false;
         }
         //We need this to be true at least once (two identical adjacent digits)
-        has_double = has_double || (window.iter().nth(0).unwrap() == window.iter().nth(1).unwrap());
+        has_double = has_double || (window.first().unwrap() == window.get(1).unwrap());
     }
     has_double
 }
@@ -43,16 +43,16 @@ fn satisfies_harder_conditions(password: Vec<char>) -> bool {
     padded_password.extend(vec!['-']);
     for window in padded_password.windows(4) {
         //First middle digit (y) bigger than the second middle digit: abort
-        if window.iter().nth(1).unwrap().to_digit(10).unwrap()
-            > window.iter().nth(2).unwrap().to_digit(10).unwrap()
+        if window.get(1).unwrap().to_digit(10).unwrap()
+            > window.get(2).unwrap().to_digit(10).unwrap()
         {
             return false;
         }
         //We need this to be true at least once (exactly two identical adjacent digits)
         has_xyyz = has_xyyz
-            || ((window.iter().nth(0).unwrap() != window.iter().nth(1).unwrap())
-                && (window.iter().nth(1).unwrap() == window.iter().nth(2).unwrap())
-                && (window.iter().nth(2).unwrap() != window.iter().nth(3).unwrap()));
+            || ((window.first().unwrap() != window.get(1).unwrap())
+                && (window.get(1).unwrap() == window.get(2).unwrap())
+                && (window.get(2).unwrap() != window.get(3).unwrap()));
     }
     has_xyyz
 }
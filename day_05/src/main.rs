@@ -1,12 +1,12 @@
 use intcode_computer::*;
 
 fn main() {
-    let mut intcode: Vec<i32> = include_str!("input.txt") //Read the input
+    let mut intcode: Vec<i64> = include_str!("input.txt") //Read the input
         .split(",")
-        .map(|s| s.parse::<i32>().unwrap())
+        .map(|s| s.parse::<i64>().unwrap())
         .collect();
 
-    // This yields [3, 0, 0, 0, 0, 0, 0, 0, 0, 14522484], which should be an error, but the last number ("diagnostic code") is the right answer.
-    // ...Guess I'll cheat and pretend everything went alright.
+    // This yields [0, 0, 0, 0, 0, 0, 0, 0, 0, 14522484]: every test passes, and the last number is the "diagnostic code".
+    // (It used to start with a 3, because Out ignored immediate mode. Cheating is no longer required!)
     println!("Output: {:?}", execute(&mut intcode[..], &vec![1]));
 }
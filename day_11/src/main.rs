@@ -0,0 +1,146 @@
+mod robot;
+use intcode_computer::*;
+use robot::*;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Color {
+    Black,
+    White,
+}
+
+/* The brain (an intcode program) gets the color of the panel under the robot (0 black, 1 white),
+ * and answers with two outputs: the color to paint that panel, then where to turn (0 left, 1 right).
+ * The robot then moves forward one panel, and it all starts over until the brain halts.
+ * Nothing says both outputs come before the brain stops for input again, so a lone color waits for its turn.
+ */
+fn paint_hull(program: &[i64], robot: &mut GridRobot<Color>) {
+    let mut brain = Vm::new(program);
+    let mut outputs = vec![];
+    loop {
+        let state = brain.run();
+        outputs.extend(brain.take_output());
+        let instructions = outputs.chunks_exact(2);
+        let leftover = instructions.remainder().to_vec();
+        for instruction in instructions {
+            robot.paint(match instruction[0] {
+                0 => Color::Black,
+                1 => Color::White,
+                color => panic!("Unknown color {}", color),
+            });
+            match instruction[1] {
+                0 => robot.turn_left(),
+                1 => robot.turn_right(),
+                turn => panic!("Unknown turn {}", turn),
+            }
+            robot.forward();
+        }
+        outputs = leftover;
+        if state == State::Halted {
+            if let [color] = outputs[..] {
+                panic!(
+                    "The brain halted after painting {} without saying where to turn",
+                    color
+                );
+            }
+            break;
+        }
+        //Panels that were never painted are black
+        brain.push_input(match robot.current() {
+            Some(Color::White) => 1,
+            _ => 0,
+        });
+    }
+}
+
+//The registration identifier is painted in white on black
+fn render(robot: &GridRobot<Color>) -> String {
+    robot.render(|panel| match panel {
+        Some(Color::White) => '#',
+        _ => ' ',
+    })
+}
+
+fn main() {
+    //No input.txt in the repo for this one: pass the puzzle input's path
+    let path = std::env::args()
+        .nth(1)
+        .expect("Usage: day_11 <path to puzzle input>");
+    let program = load_program(path, &[]).unwrap_or_else(|error| panic!("{}", error));
+
+    // PART 1
+    //Start on a black panel, and count the panels that got painted at least once
+    let mut robot = GridRobot::new();
+    paint_hull(&program, &mut robot);
+    println!("{}", robot.panels().len());
+
+    // PART 2
+    //Same thing starting on a white panel, and the result is a picture
+    let mut robot = GridRobot::new();
+    robot.paint(Color::White);
+    paint_hull(&program, &mut robot);
+    println!("{}", render(&robot));
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    //A brain that ignores what it sees and answers with the given (color, turn) pairs
+    fn scripted_brain(answers: &[(i64, i64)]) -> Vec<i64> {
+        let mut program = vec![];
+        for (color, turn) in answers {
+            program.extend(&[3, 1000, 104, *color, 104, *turn]);
+        }
+        program.push(99);
+        program
+    }
+
+    #[test]
+    fn robot() {
+        let mut robot = GridRobot::new();
+        robot.turn_right();
+        robot.forward();
+        robot.paint(1);
+        robot.turn_left();
+        robot.turn_left();
+        robot.forward();
+        robot.forward();
+        robot.paint(2);
+        assert_eq!(robot.heading, Dir::West);
+        assert_eq!(robot.position, Point { x: -1, y: 0 });
+        assert_eq!(robot.current(), Some(2));
+        assert_eq!(robot.panels().len(), 2);
+        assert_eq!(
+            robot.render(|panel| match panel {
+                Some(n) => (b'0' + n) as char,
+                None => '.',
+            }),
+            "2.1\n"
+        );
+    }
+
+    #[test]
+    fn example() {
+        let brain = scripted_brain(&[(1, 0), (0, 0), (1, 0), (1, 0), (0, 1), (1, 0), (1, 0)]);
+        let mut robot = GridRobot::new();
+        paint_hull(&brain, &mut robot);
+        assert_eq!(robot.panels().len(), 6);
+        assert_eq!(robot.position, Point { x: 0, y: 1 });
+        assert_eq!(robot.heading, Dir::West);
+        assert_eq!(render(&robot), "  #\n  #\n## \n");
+    }
+
+    #[test]
+    fn split_instruction() {
+        //Paints white, reads the panel again, and only then says to turn right
+        let brain = [104, 1, 3, 1000, 104, 1, 99];
+        let mut robot = GridRobot::new();
+        paint_hull(&brain, &mut robot);
+        assert_eq!(robot.panels().len(), 1);
+        assert_eq!(robot.heading, Dir::East);
+
+        let halted = std::panic::catch_unwind(|| paint_hull(&[104, 1, 99], &mut GridRobot::new()));
+        assert!(halted.is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+//Fancier Up Down Left Right (same as day 3, plus turning)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Dir {
+    North,
+    East,
+    South,
+    West,
+}
+impl Dir {
+    pub fn turn_left(self) -> Dir {
+        match self {
+            Dir::North => Dir::West,
+            Dir::West => Dir::South,
+            Dir::South => Dir::East,
+            Dir::East => Dir::North,
+        }
+    }
+
+    pub fn turn_right(self) -> Dir {
+        //Three lefts make a right ^u^
+        self.turn_left().turn_left().turn_left()
+    }
+
+    //Returns unit vectors we can add to points to follow that direction
+    pub fn to_point(self) -> Point {
+        match self {
+            Dir::South => Point { x: 0, y: -1 },
+            Dir::North => Point { x: 0, y: 1 },
+            Dir::West => Point { x: -1, y: 0 },
+            Dir::East => Point { x: 1, y: 0 },
+        }
+    }
+}
+
+//Eq, PartialEq, and Hash are needed for HashSet/HashMap
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+//Trait required to add points together
+impl std::ops::AddAssign for Point {
+    fn add_assign(&mut self, other: Self) {
+        *self = Self {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        };
+    }
+}
+
+/* A robot moving around an infinite grid, one panel at a time.
+ * Only the panels it actually painted are stored, with whatever it painted them with.
+ */
+pub struct GridRobot<T> {
+    pub position: Point,
+    pub heading: Dir,
+    panels: HashMap<Point, T>,
+}
+impl<T: Copy> GridRobot<T> {
+    //Starts at (0, 0), facing up, on a grid that was never painted
+    pub fn new() -> Self {
+        GridRobot {
+            position: Point { x: 0, y: 0 },
+            heading: Dir::North,
+            panels: HashMap::new(),
+        }
+    }
+
+    pub fn turn_left(&mut self) {
+        self.heading = self.heading.turn_left();
+    }
+
+    pub fn turn_right(&mut self) {
+        self.heading = self.heading.turn_right();
+    }
+
+    pub fn forward(&mut self) {
+        self.position += self.heading.to_point();
+    }
+
+    //Whatever was painted under the robot, if anything
+    pub fn current(&self) -> Option<T> {
+        self.panels.get(&self.position).copied()
+    }
+
+    pub fn paint(&mut self, value: T) {
+        self.panels.insert(self.position, value);
+    }
+
+    //Every panel that was painted at least once
+    pub fn panels(&self) -> &HashMap<Point, T> {
+        &self.panels
+    }
+
+    /* One character per panel, with up (North) at the top.
+     * Only covers the painted area, so nothing is drawn if nothing was painted.
+     */
+    pub fn render<F: Fn(Option<T>) -> char>(&self, draw: F) -> String {
+        let mut picture = String::new();
+        if self.panels.is_empty() {
+            return picture;
+        }
+        let min_x = self.panels.keys().map(|p| p.x).min().unwrap();
+        let max_x = self.panels.keys().map(|p| p.x).max().unwrap();
+        let min_y = self.panels.keys().map(|p| p.y).min().unwrap();
+        let max_y = self.panels.keys().map(|p| p.y).max().unwrap();
+        for y in (min_y..=max_y).rev() {
+            for x in min_x..=max_x {
+                picture.push(draw(self.panels.get(&Point { x, y }).copied()));
+            }
+            picture.push('\n');
+        }
+        picture
+    }
+}
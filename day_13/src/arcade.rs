@@ -0,0 +1,250 @@
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::{cursor, execute, terminal};
+use intcode_computer::*;
+use std::collections::HashMap;
+use std::io::{stdout, Write};
+use std::panic;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Tile {
+    Empty,
+    Wall,
+    Block,
+    Paddle,
+    Ball,
+}
+impl Tile {
+    fn from_id(id: i64) -> Tile {
+        match id {
+            0 => Tile::Empty,
+            1 => Tile::Wall,
+            2 => Tile::Block,
+            3 => Tile::Paddle,
+            4 => Tile::Ball,
+            _ => panic!("Unknown tile id {}", id),
+        }
+    }
+
+    fn to_char(self) -> char {
+        match self {
+            Tile::Empty => ' ',
+            Tile::Wall => '#',
+            Tile::Block => '=',
+            Tile::Paddle => '_',
+            Tile::Ball => 'o',
+        }
+    }
+}
+
+//What the game reads as input
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Joystick {
+    Left = -1,
+    Neutral = 0,
+    Right = 1,
+}
+
+/* Everything the game drew so far.
+ * The game only outputs (x, y, tile) triples for what changed, except (-1, 0, n) which sets the score to n.
+ */
+#[derive(Default)]
+pub struct Screen {
+    tiles: HashMap<(i64, i64), Tile>,
+    pub score: i64,
+}
+impl Screen {
+    pub fn update(&mut self, output: &[i64]) {
+        if !output.len().is_multiple_of(3) {
+            panic!("The game output isn't made of triples: {:?}", output);
+        }
+        for triple in output.chunks(3) {
+            match (triple[0], triple[1]) {
+                (-1, 0) => self.score = triple[2],
+                (x, y) => {
+                    self.tiles.insert((x, y), Tile::from_id(triple[2]));
+                }
+            }
+        }
+    }
+
+    pub fn count(&self, tile: Tile) -> usize {
+        self.tiles.values().filter(|&&t| t == tile).count()
+    }
+
+    //Where the first tile of that kind is (there should only be one ball and one paddle)
+    pub fn find(&self, tile: Tile) -> Option<(i64, i64)> {
+        self.tiles
+            .iter()
+            .find(|(_, &t)| t == tile)
+            .map(|(&position, _)| position)
+    }
+
+    //The score, then one line per row, y going down like the game expects
+    pub fn render(&self) -> String {
+        let mut frame = format!("Score: {}\n", self.score);
+        if self.tiles.is_empty() {
+            return frame;
+        }
+        let max_x = self.tiles.keys().map(|p| p.0).max().unwrap();
+        let max_y = self.tiles.keys().map(|p| p.1).max().unwrap();
+        for y in 0..=max_y {
+            for x in 0..=max_x {
+                frame.push(self.tiles.get(&(x, y)).unwrap_or(&Tile::Empty).to_char());
+            }
+            frame.push('\n');
+        }
+        frame
+    }
+}
+
+//The cabinet: the game running in a VM, and the screen it draws on
+pub struct Arcade {
+    vm: Vm,
+    screen: Screen,
+}
+impl Arcade {
+    pub fn new(program: &[i64]) -> Self {
+        Arcade {
+            vm: Vm::new(program),
+            screen: Screen::default(),
+        }
+    }
+
+    //Memory address 0 is the number of quarters inserted: 2 plays for free
+    pub fn free_play(program: &[i64]) -> Self {
+        let mut program = program.to_vec();
+        program[0] = 2;
+        Arcade::new(&program)
+    }
+
+    pub fn screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    //Runs the game until it wants the joystick (or the game is over), and draws whatever it output
+    pub fn run(&mut self) -> State {
+        let state = self.vm.run();
+        self.screen.update(&self.vm.take_output());
+        state
+    }
+
+    pub fn push_joystick(&mut self, joystick: Joystick) {
+        self.vm.push_input(joystick as i64);
+    }
+}
+
+//Follows the ball with the paddle: no lookahead needed, the paddle moves as fast as the ball does
+pub fn autopilot(screen: &Screen) -> Joystick {
+    match (screen.find(Tile::Ball), screen.find(Tile::Paddle)) {
+        (Some(ball), Some(paddle)) if ball.0 < paddle.0 => Joystick::Left,
+        (Some(ball), Some(paddle)) if ball.0 > paddle.0 => Joystick::Right,
+        _ => Joystick::Neutral,
+    }
+}
+
+//Plays a whole free game with the autopilot, without drawing anything, and gives the final score
+pub fn play_headless(program: &[i64]) -> i64 {
+    let mut arcade = Arcade::free_play(program);
+    while arcade.run() != State::Halted {
+        arcade.push_joystick(autopilot(arcade.screen()));
+    }
+    arcade.screen().score
+}
+
+//The panic hook that was there before the game started
+type Hook = Arc<dyn Fn(&panic::PanicHookInfo) + Send + Sync>;
+
+/* Raw mode and the alternate screen, left when dropped (even by a panic).
+ * A panic gives the terminal back before its message gets printed, or it would be lost with the alternate screen.
+ */
+struct Terminal {
+    hook: Hook,
+}
+impl Terminal {
+    fn enter() -> std::io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        //From here on the terminal gets restored, even if the next part fails
+        let guard = Terminal {
+            hook: Arc::from(panic::take_hook()),
+        };
+        let hook = Arc::clone(&guard.hook);
+        panic::set_hook(Box::new(move |info| {
+            Terminal::restore();
+            hook(info);
+        }));
+        execute!(stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(guard)
+    }
+
+    fn restore() {
+        execute!(stdout(), cursor::Show, terminal::LeaveAlternateScreen).ok();
+        terminal::disable_raw_mode().ok();
+    }
+}
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        Terminal::restore();
+        //The hook can't be changed while panicking (and the program is going down anyway)
+        if !std::thread::panicking() {
+            let hook = Arc::clone(&self.hook);
+            panic::set_hook(Box::new(move |info| hook(info)));
+        }
+    }
+}
+
+/* Plays a free game in the terminal.
+ * With the autopilot on, frames are just drawn one after the other; otherwise the arrow keys move the paddle,
+ * the game goes on by itself when no key is pressed in time, and q (or Esc) quits.
+ * Gives the final score back.
+ */
+pub fn play_interactive(program: &[i64], with_autopilot: bool) -> std::io::Result<i64> {
+    let mut arcade = Arcade::free_play(program);
+    let mut out = stdout();
+    let _terminal = Terminal::enter()?;
+    let result = (|| {
+        while arcade.run() != State::Halted {
+            draw(&mut out, arcade.screen())?;
+            let joystick = if with_autopilot {
+                std::thread::sleep(Duration::from_millis(20));
+                autopilot(arcade.screen())
+            } else {
+                match read_joystick()? {
+                    Some(joystick) => joystick,
+                    None => break,
+                }
+            };
+            arcade.push_joystick(joystick);
+        }
+        draw(&mut out, arcade.screen())
+    })();
+    result.map(|_| arcade.screen().score)
+}
+
+fn draw(out: &mut impl Write, screen: &Screen) -> std::io::Result<()> {
+    execute!(
+        out,
+        cursor::MoveTo(0, 0),
+        terminal::Clear(terminal::ClearType::All)
+    )?;
+    //Raw mode doesn't go back to the start of the line by itself
+    write!(out, "{}", screen.render().replace('\n', "\r\n"))?;
+    out.flush()
+}
+
+//None when the player wants to quit
+fn read_joystick() -> std::io::Result<Option<Joystick>> {
+    if !event::poll(Duration::from_millis(150))? {
+        return Ok(Some(Joystick::Neutral));
+    }
+    match event::read()? {
+        Event::Key(key) if key.kind != KeyEventKind::Release => Ok(match key.code {
+            KeyCode::Left => Some(Joystick::Left),
+            KeyCode::Right => Some(Joystick::Right),
+            KeyCode::Char('q') | KeyCode::Esc => None,
+            _ => Some(Joystick::Neutral),
+        }),
+        _ => Ok(Some(Joystick::Neutral)),
+    }
+}
@@ -0,0 +1,67 @@
+mod arcade;
+use arcade::*;
+use intcode_computer::*;
+
+fn main() {
+    //No input.txt in the repo for this one: pass the puzzle input's path
+    //Add --play to play part 2 yourself with the arrow keys, or --watch to watch the autopilot play it
+    let mut args = std::env::args().skip(1);
+    let path = args
+        .next()
+        .expect("Usage: day_13 <path to puzzle input> [--play|--watch]");
+    let program = load_program(path, &[]).unwrap_or_else(|error| panic!("{}", error));
+
+    // PART 1
+    //Without quarters, the game only draws the starting screen
+    let mut arcade = Arcade::new(&program);
+    arcade.run();
+    println!("{}", arcade.screen().count(Tile::Block));
+
+    // PART 2
+    //Break every block, the score at the end is the answer
+    let score = match args.next().as_deref() {
+        Some("--play") => play_interactive(&program, false).expect("Terminal error"),
+        Some("--watch") => play_interactive(&program, true).expect("Terminal error"),
+        Some(flag) => panic!("Unknown flag {}", flag),
+        None => play_headless(&program),
+    };
+    println!("{}", score);
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn screen() {
+        let mut screen = Screen::default();
+        screen.update(&[1, 2, 3, 6, 5, 4]);
+        assert_eq!(screen.find(Tile::Paddle), Some((1, 2)));
+        assert_eq!(screen.find(Tile::Ball), Some((6, 5)));
+        assert_eq!(autopilot(&screen), Joystick::Right);
+        //Tiles get redrawn, and the score isn't a tile
+        screen.update(&[6, 5, 0, 0, 5, 4, -1, 0, 12345]);
+        assert_eq!(screen.find(Tile::Ball), Some((0, 5)));
+        assert_eq!(screen.count(Tile::Empty), 1);
+        assert_eq!(screen.score, 12345);
+        assert_eq!(autopilot(&screen), Joystick::Left);
+        assert_eq!(
+            screen.render(),
+            "Score: 12345\n       \n       \n _     \n       \n       \no      \n"
+        );
+    }
+
+    #[test]
+    fn headless() {
+        //Draws the ball right of the paddle, reads the joystick, and scores 10 + whatever it read
+        let game = [
+            1101, 0, 0, 1000, //Turned into a harmless multiplication by free play
+            104, 3, 104, 1, 104, 4, //Ball at (3, 1)
+            104, 1, 104, 1, 104, 3, //Paddle at (1, 1)
+            3, 100, 1001, 100, 10, 100, //
+            104, -1, 104, 0, 4, 100, 99,
+        ];
+        assert_eq!(play_headless(&game), 11);
+    }
+}
@@ -0,0 +1,264 @@
+use intcode_computer::*;
+use std::collections::{HashMap, VecDeque};
+
+//Fancier Up Down Left Right (same as day 3, the droid numbers them its own way)
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Dir {
+    North,
+    South,
+    West,
+    East,
+}
+impl Dir {
+    const ALL: [Dir; 4] = [Dir::North, Dir::South, Dir::West, Dir::East];
+
+    //What the droid reads to move that way
+    fn command(self) -> i64 {
+        match self {
+            Dir::North => 1,
+            Dir::South => 2,
+            Dir::West => 3,
+            Dir::East => 4,
+        }
+    }
+
+    //Returns unit vectors we can add to points to follow that direction
+    fn to_point(self) -> Point {
+        match self {
+            Dir::South => Point { x: 0, y: -1 },
+            Dir::North => Point { x: 0, y: 1 },
+            Dir::West => Point { x: -1, y: 0 },
+            Dir::East => Point { x: 1, y: 0 },
+        }
+    }
+}
+
+//Eq, PartialEq, and Hash are needed for HashSet/HashMap
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+//Adding points together gives a new one this time, we never move anything in place
+impl std::ops::Add for Point {
+    type Output = Point;
+    fn add(self, other: Self) -> Point {
+        Point {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        }
+    }
+}
+
+//What the droid answers after trying to move
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Tile {
+    Wall,
+    Open,
+    Oxygen,
+}
+
+/* Maps the whole maze, starting from the droid's position at (0, 0).
+ * Instead of walking the droid back and forth, every open tile keeps a snapshot of the droid standing on it:
+ * trying a direction from there is just restoring the snapshot and sending one command.
+ * Tiles are discovered breadth-first, so the map comes out in order of distance from the start.
+ */
+fn explore(mut droid: Vm) -> HashMap<Point, Tile> {
+    let origin = Point { x: 0, y: 0 };
+    let mut map = HashMap::new();
+    map.insert(origin, Tile::Open);
+    if droid.run() != State::WaitingForInput {
+        panic!("The droid isn't waiting for a command");
+    }
+    let mut queue = VecDeque::new();
+    queue.push_back((origin, droid.snapshot()));
+    while let Some((position, snapshot)) = queue.pop_front() {
+        for dir in Dir::ALL.iter() {
+            let next = position + dir.to_point();
+            if map.contains_key(&next) {
+                continue;
+            }
+            droid.restore(&snapshot);
+            droid.push_input(dir.command());
+            droid.run();
+            let tile = match droid.take_output()[..] {
+                [0] => Tile::Wall,
+                [1] => Tile::Open,
+                [2] => Tile::Oxygen,
+                ref answer => panic!("The droid answered {:?}", answer),
+            };
+            map.insert(next, tile);
+            if tile != Tile::Wall {
+                queue.push_back((next, droid.snapshot()));
+            }
+        }
+    }
+    map
+}
+
+//How many moves it takes to reach every tile that can be reached from start
+fn distances(map: &HashMap<Point, Tile>, start: Point) -> HashMap<Point, usize> {
+    let mut distances = HashMap::new();
+    distances.insert(start, 0);
+    let mut queue = VecDeque::new();
+    queue.push_back(start);
+    while let Some(position) = queue.pop_front() {
+        let distance = distances[&position];
+        for dir in Dir::ALL.iter() {
+            let next = position + dir.to_point();
+            let open = matches!(map.get(&next), Some(Tile::Open) | Some(Tile::Oxygen));
+            if open && !distances.contains_key(&next) {
+                distances.insert(next, distance + 1);
+                queue.push_back(next);
+            }
+        }
+    }
+    distances
+}
+
+fn oxygen_system(map: &HashMap<Point, Tile>) -> Point {
+    *map.iter()
+        .find(|(_, &tile)| tile == Tile::Oxygen)
+        .expect("The droid never found the oxygen system")
+        .0
+}
+
+//Oxygen spreads one tile per minute, so the last tile to fill up is the one farthest from the system
+fn flood_time(map: &HashMap<Point, Tile>) -> usize {
+    *distances(map, oxygen_system(map)).values().max().unwrap()
+}
+
+fn main() {
+    //No input.txt in the repo for this one: pass the puzzle input's path
+    let path = std::env::args()
+        .nth(1)
+        .expect("Usage: day_15 <path to puzzle input>");
+    let program = load_program(path, &[]).unwrap_or_else(|error| panic!("{}", error));
+    let map = explore(Vm::new(&program));
+
+    // PART 1
+    //Fewest moves from the start to the oxygen system
+    let origin = Point { x: 0, y: 0 };
+    println!("{}", distances(&map, origin)[&oxygen_system(&map)]);
+
+    // PART 2
+    //Minutes until the whole maze is full of oxygen
+    println!("{}", flood_time(&map));
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    //Text maps have y going down, points have North going up: D is the droid, O the oxygen system
+    fn parse_map(text: &str) -> (Point, HashMap<Point, Tile>) {
+        let mut droid = Point { x: 0, y: 0 };
+        let mut map = HashMap::new();
+        for (row, line) in text.lines().enumerate() {
+            for (column, c) in line.chars().enumerate() {
+                let point = Point {
+                    x: column as i32,
+                    y: -(row as i32),
+                };
+                let tile = match c {
+                    '#' => Tile::Wall,
+                    '.' => Tile::Open,
+                    'D' => {
+                        droid = point;
+                        Tile::Open
+                    }
+                    'O' => Tile::Oxygen,
+                    _ => continue,
+                };
+                map.insert(point, tile);
+            }
+        }
+        (droid, map)
+    }
+
+    /* A fake droid, as a custom opcode: x and y are stored in the VM's memory (relative to the start),
+     * so they come back along with snapshots like they would in a real droid program.
+     */
+    struct FakeDroid {
+        start: Point,
+        maze: HashMap<Point, Tile>,
+    }
+    impl OpcodeHandler for FakeDroid {
+        fn opcode(&self) -> i64 {
+            60
+        }
+        fn arity(&self) -> usize {
+            3
+        }
+        fn param_rule(&self, index: usize) -> ParamRule {
+            match index {
+                0 => ParamRule::Read,
+                _ => ParamRule::Write,
+            }
+        }
+        //60 command x y
+        fn execute(&mut self, args: &[i64], context: &mut HandlerContext) {
+            let dir = Dir::ALL[args[0] as usize - 1];
+            let (x, y) = (args[1] as usize, args[2] as usize);
+            let next = Point {
+                x: context.memory.read(x) as i32,
+                y: context.memory.read(y) as i32,
+            } + dir.to_point();
+            let answer = match self.maze.get(&(self.start + next)) {
+                Some(Tile::Open) => 1,
+                Some(Tile::Oxygen) => 2,
+                _ => 0,
+            };
+            if answer != 0 {
+                context.memory.write(x, next.x as i64);
+                context.memory.write(y, next.y as i64);
+            }
+            context.output.push(answer);
+        }
+    }
+
+    const MAZE: &str = "\
+#######
+#D..#.#
+#.#.#.#
+#.#...#
+#.###O#
+#######";
+
+    #[test]
+    fn exploration() {
+        let (start, maze) = parse_map(MAZE);
+        let mut registry = OpcodeRegistry::new();
+        registry.register(FakeDroid {
+            start,
+            maze: maze.clone(),
+        });
+        //Read a command into [20], move with it, and start over
+        let droid = VmBuilder::new(&[3, 20, 60, 20, 21, 22, 1105, 1, 0])
+            .registry(registry)
+            .build();
+        let map = explore(droid);
+        //Every open tile was found (walls only when there was an open tile next to them)
+        let open = |map: &HashMap<Point, Tile>| map.values().filter(|&&t| t != Tile::Wall).count();
+        assert_eq!(open(&map), open(&maze));
+        assert_eq!(oxygen_system(&map), Point { x: 4, y: -3 });
+        assert_eq!(
+            distances(&map, Point { x: 0, y: 0 })[&Point { x: 4, y: -3 }],
+            7
+        );
+        assert_eq!(flood_time(&map), 10);
+    }
+
+    #[test]
+    fn flood() {
+        let (_, map) = parse_map(
+            " ##
+#..##
+#.#..#
+#.O.#
+ ###",
+        );
+        assert_eq!(flood_time(&map), 4);
+    }
+}
@@ -0,0 +1,107 @@
+mod scaffold;
+use intcode_computer::*;
+use scaffold::*;
+
+fn main() {
+    //No input.txt in the repo for this one: pass the puzzle input's path
+    let path = std::env::args()
+        .nth(1)
+        .expect("Usage: day_17 <path to puzzle input>");
+    let mut program = load_program(path, &[]).unwrap_or_else(|error| panic!("{}", error));
+
+    // PART 1
+    //The program starts by printing the camera image
+    let mut vm = Vm::new(&program);
+    vm.run();
+    let camera = Camera::parse(&vm.take_ascii().text);
+    println!("{}", camera.alignment_parameters());
+
+    // PART 2
+    //Wake the robot up (address 0 set to 2), and walk it over every scaffold with a compressed routine
+    let path = camera.path();
+    let routine = compress(&path)
+        .unwrap_or_else(|| panic!("The path doesn't fit in three functions: {}", join(&path)));
+    program[0] = 2;
+    let mut vm = Vm::new(&program);
+    for line in routine.lines() {
+        vm.push_line(&line);
+    }
+    //No continuous video feed, we only want the answer
+    vm.push_line("n");
+    if vm.run() != State::Halted {
+        panic!("The robot wants more input:\n{}", vm.take_ascii().text);
+    }
+    let output = vm.take_ascii();
+    match output.values.last() {
+        Some(dust) => println!("{}", dust),
+        None => panic!("The robot didn't collect any dust:\n{}", output.text),
+    }
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    //The moves the robot ends up making
+    fn expand(routine: &Routine) -> Vec<Move> {
+        routine
+            .main
+            .iter()
+            .flat_map(|&name| routine.functions[(name as u8 - b'A') as usize].clone())
+            .collect()
+    }
+
+    #[test]
+    fn alignment() {
+        let camera = Camera::parse(
+            "..#..........
+..#..........
+#######...###
+#.#...#...#.#
+#############
+..#...#...#..
+..#####...^..
+",
+        );
+        assert_eq!(
+            camera.intersections(),
+            vec![(2, 2), (2, 4), (6, 4), (10, 4)]
+        );
+        assert_eq!(camera.alignment_parameters(), 76);
+    }
+
+    #[test]
+    fn movement_routine() {
+        let camera = Camera::parse(
+            "#######...#####
+#.....#...#...#
+#.....#...#...#
+......#...#...#
+......#...###.#
+......#.....#.#
+^########...#.#
+......#.#...#.#
+......#########
+........#...#..
+....#########..
+....#...#......
+....#...#......
+....#...#......
+....#####......
+",
+        );
+        let path = camera.path();
+        assert_eq!(
+            join(&path),
+            "R,8,R,8,R,4,R,4,R,8,L,6,L,2,R,4,R,4,R,8,R,8,R,8,L,6,L,2"
+        );
+        let routine = compress(&path).unwrap();
+        assert_eq!(expand(&routine), path);
+        assert!(routine.lines().iter().all(|line| line.len() <= 20));
+
+        //Too many different moves for three functions
+        let moves: Vec<Move> = (10..=31).map(Move::Forward).collect();
+        assert_eq!(compress(&moves), None);
+    }
+}
@@ -0,0 +1,241 @@
+use std::fmt;
+
+//Up Down Left Right, with y going down like in the camera image
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Dir {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+impl Dir {
+    fn from_char(c: char) -> Option<Dir> {
+        match c {
+            '^' => Some(Dir::Up),
+            '>' => Some(Dir::Right),
+            'v' => Some(Dir::Down),
+            '<' => Some(Dir::Left),
+            _ => None,
+        }
+    }
+
+    fn turn_left(self) -> Dir {
+        match self {
+            Dir::Up => Dir::Left,
+            Dir::Left => Dir::Down,
+            Dir::Down => Dir::Right,
+            Dir::Right => Dir::Up,
+        }
+    }
+
+    fn turn_right(self) -> Dir {
+        //Three lefts make a right ^u^
+        self.turn_left().turn_left().turn_left()
+    }
+
+    fn to_offset(self) -> (i32, i32) {
+        match self {
+            Dir::Up => (0, -1),
+            Dir::Right => (1, 0),
+            Dir::Down => (0, 1),
+            Dir::Left => (-1, 0),
+        }
+    }
+}
+
+//What the camera sees: scaffolds (#), open space (.), and the robot standing on a scaffold (^>v<)
+pub struct Camera {
+    rows: Vec<Vec<char>>,
+    pub robot: (i32, i32),
+    pub heading: Dir,
+}
+impl Camera {
+    pub fn parse(image: &str) -> Camera {
+        let rows: Vec<Vec<char>> = image
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| line.chars().collect())
+            .collect();
+        let (robot, heading) = rows
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| {
+                row.iter().enumerate().filter_map(move |(x, &c)| {
+                    Dir::from_char(c).map(|dir| ((x as i32, y as i32), dir))
+                })
+            })
+            .next()
+            .expect("No robot in the camera image");
+        Camera {
+            rows,
+            robot,
+            heading,
+        }
+    }
+
+    //Outside of the image is open space
+    pub fn is_scaffold(&self, (x, y): (i32, i32)) -> bool {
+        if x < 0 || y < 0 {
+            return false;
+        }
+        match self
+            .rows
+            .get(y as usize)
+            .and_then(|row| row.get(x as usize))
+        {
+            Some(&c) => c == '#' || Dir::from_char(c).is_some(),
+            None => false,
+        }
+    }
+
+    //Scaffolds with scaffolds on all four sides
+    pub fn intersections(&self) -> Vec<(i32, i32)> {
+        let mut intersections = vec![];
+        for (y, row) in self.rows.iter().enumerate() {
+            for x in 0..row.len() {
+                let (x, y) = (x as i32, y as i32);
+                let neighbours = [(x, y), (x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)];
+                if neighbours.iter().all(|&p| self.is_scaffold(p)) {
+                    intersections.push((x, y));
+                }
+            }
+        }
+        intersections
+    }
+
+    pub fn alignment_parameters(&self) -> i32 {
+        self.intersections().iter().map(|(x, y)| x * y).sum()
+    }
+
+    /* The path that goes over every scaffold: go straight as far as possible (so intersections are crossed),
+     * then turn towards the only scaffold left, until there's nowhere left to go.
+     */
+    pub fn path(&self) -> Vec<Move> {
+        let step = |(x, y): (i32, i32), dir: Dir| {
+            let (dx, dy) = dir.to_offset();
+            (x + dx, y + dy)
+        };
+        let mut moves = vec![];
+        let (mut position, mut heading) = (self.robot, self.heading);
+        loop {
+            let mut forward = 0;
+            while self.is_scaffold(step(position, heading)) {
+                position = step(position, heading);
+                forward += 1;
+            }
+            if forward > 0 {
+                moves.push(Move::Forward(forward));
+            }
+            if self.is_scaffold(step(position, heading.turn_left())) {
+                heading = heading.turn_left();
+                moves.push(Move::Left);
+            } else if self.is_scaffold(step(position, heading.turn_right())) {
+                heading = heading.turn_right();
+                moves.push(Move::Right);
+            } else {
+                return moves;
+            }
+        }
+    }
+}
+
+//What the robot understands
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Move {
+    Left,
+    Right,
+    Forward(usize),
+}
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Move::Left => write!(f, "L"),
+            Move::Right => write!(f, "R"),
+            Move::Forward(steps) => write!(f, "{}", steps),
+        }
+    }
+}
+
+//The robot wants comma separated lists
+pub fn join<T: fmt::Display>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+//The robot's memory can't hold more than that per line (newline not included)
+const MAX_LENGTH: usize = 20;
+
+//A movement routine for the robot: the main routine calls functions A, B and C, which hold the actual moves
+#[derive(Debug, PartialEq)]
+pub struct Routine {
+    pub main: Vec<char>,
+    pub functions: Vec<Vec<Move>>,
+}
+impl Routine {
+    //What gets sent to the robot, line by line: main, A, B, C (empty functions are still sent)
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![join(&self.main)];
+        for index in 0..3 {
+            lines.push(join(self.functions.get(index).map_or(&[][..], |f| &f[..])));
+        }
+        lines
+    }
+}
+
+/* Splits the path into at most three functions that fit in memory, with a main routine that also fits.
+ * Depth first: at each point of the path, either call a function we already have if the path goes on with it,
+ * or make a new function out of the next few moves.
+ */
+pub fn compress(path: &[Move]) -> Option<Routine> {
+    let mut routine = Routine {
+        main: vec![],
+        functions: vec![],
+    };
+    if search(path, &mut routine) {
+        Some(routine)
+    } else {
+        None
+    }
+}
+
+fn search(path: &[Move], routine: &mut Routine) -> bool {
+    if path.is_empty() {
+        return true;
+    }
+    //Each call takes a letter and a comma
+    if routine.main.len() * 2 + 1 > MAX_LENGTH {
+        return false;
+    }
+    for index in 0..routine.functions.len() {
+        let function = &routine.functions[index];
+        if path.starts_with(function) {
+            let rest = &path[function.len()..];
+            routine.main.push((b'A' + index as u8) as char);
+            if search(rest, routine) {
+                return true;
+            }
+            routine.main.pop();
+        }
+    }
+    if routine.functions.len() < 3 {
+        let name = (b'A' + routine.functions.len() as u8) as char;
+        //Longest first: the fewer calls, the more room left in main
+        let fitting = (1..=path.len())
+            .take_while(|&length| join(&path[..length]).len() <= MAX_LENGTH)
+            .last()
+            .unwrap_or(0);
+        for length in (1..=fitting).rev() {
+            routine.functions.push(path[..length].to_vec());
+            routine.main.push(name);
+            if search(&path[length..], routine) {
+                return true;
+            }
+            routine.main.pop();
+            routine.functions.pop();
+        }
+    }
+    false
+}
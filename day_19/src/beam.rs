@@ -0,0 +1,148 @@
+use intcode_computer::*;
+use std::collections::HashMap;
+use std::ops::Range;
+
+//Rows with nothing known above them are scanned this many times their y (plus one) wide before giving up
+const SCAN_FACTOR: u64 = 10;
+
+/* Asks the drone program whether points are in the tractor beam.
+ * The program only answers once, so every question costs a fresh VM: answers are kept, and never asked twice.
+ */
+pub struct Beam {
+    program: Vec<i64>,
+    answers: HashMap<(u64, u64), bool>,
+}
+impl Beam {
+    pub fn new(program: &[i64]) -> Self {
+        Beam {
+            program: program.to_vec(),
+            answers: HashMap::new(),
+        }
+    }
+
+    pub fn query(&mut self, x: u64, y: u64) -> bool {
+        let program = &self.program;
+        *self.answers.entry((x, y)).or_insert_with(|| {
+            let mut drone = VmBuilder::new(program).input(&[x as i64, y as i64]).build();
+            drone.run();
+            match drone.take_output()[..] {
+                [0] => false,
+                [1] => true,
+                ref answer => panic!("The drone answered {:?} for ({}, {})", answer, x, y),
+            }
+        })
+    }
+
+    //How many times the program actually ran
+    pub fn queries(&self) -> usize {
+        self.answers.len()
+    }
+
+    /* The region that was asked about: # in the beam, . outside of it, and blank if nobody asked.
+     * Handy to see how little the edge tracking needs to look at.
+     */
+    pub fn render(&self, width: u64, height: u64) -> String {
+        let mut picture = String::new();
+        for y in 0..height {
+            for x in 0..width {
+                picture.push(match self.answers.get(&(x, y)) {
+                    Some(true) => '#',
+                    Some(false) => '.',
+                    None => ' ',
+                });
+            }
+            picture.push('\n');
+        }
+        picture
+    }
+}
+
+/* Follows the beam down, one row at a time.
+ * The beam is a cone coming from the origin: both of its edges only move right (or stay) from a row to the next,
+ * so each row only needs a few questions around where the previous row's edges were.
+ * Rows close to the origin can be empty (the beam is thinner than a cell there), those get scanned instead.
+ */
+pub struct EdgeTracker<'a> {
+    beam: &'a mut Beam,
+    //Beam cells of every row so far, empty if the beam missed that row
+    rows: Vec<Range<u64>>,
+}
+impl<'a> EdgeTracker<'a> {
+    pub fn new(beam: &'a mut Beam) -> Self {
+        EdgeTracker { beam, rows: vec![] }
+    }
+
+    //Finds the beam in the next row, and returns its y
+    pub fn next_row(&mut self) -> u64 {
+        let y = self.rows.len() as u64;
+        let previous = self.rows.iter().rev().find(|row| !row.is_empty()).cloned();
+        let row = match previous {
+            Some(previous) => {
+                //The start can't be left of the previous one, and can't go much further than the previous end
+                let start =
+                    (previous.start..previous.end + SCAN_FACTOR).find(|&x| self.beam.query(x, y));
+                match start {
+                    Some(start) => {
+                        let mut end = previous.end.max(start + 1);
+                        //Edges only move right, so everything up to the previous end should be in the beam (check anyway)
+                        if !self.beam.query(end - 1, y) {
+                            end = start + 1;
+                        }
+                        while self.beam.query(end, y) {
+                            end += 1;
+                        }
+                        start..end
+                    }
+                    None => 0..0,
+                }
+            }
+            None => match (0..SCAN_FACTOR * (y + 1)).find(|&x| self.beam.query(x, y)) {
+                Some(start) => {
+                    let mut end = start + 1;
+                    while self.beam.query(end, y) {
+                        end += 1;
+                    }
+                    start..end
+                }
+                None => 0..0,
+            },
+        };
+        self.rows.push(row);
+        y
+    }
+
+    //Beam cells in the top left width * height area (the drones are only allowed there)
+    pub fn count(&mut self, width: u64, height: u64) -> u64 {
+        while (self.rows.len() as u64) < height {
+            self.next_row();
+        }
+        self.rows[..height as usize]
+            .iter()
+            .map(|row| row.end.min(width).saturating_sub(row.start.min(width)))
+            .sum()
+    }
+
+    /* Top left corner of the closest size * size square that's entirely in the beam.
+     * The square's bottom left corner is the start of some row, so check whether the row size - 1 above
+     * still reaches the square's right side. Gives up after max_rows rows.
+     */
+    pub fn closest_square(&mut self, size: u64, max_rows: u64) -> Option<(u64, u64)> {
+        for y in 0..max_rows {
+            if y >= self.rows.len() as u64 {
+                self.next_row();
+            }
+            if y + 1 < size {
+                continue;
+            }
+            let bottom = &self.rows[y as usize];
+            let top = &self.rows[(y + 1 - size) as usize];
+            if bottom.is_empty() || top.is_empty() {
+                continue;
+            }
+            if top.end >= bottom.start + size {
+                return Some((bottom.start, y + 1 - size));
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,77 @@
+mod beam;
+use beam::*;
+use intcode_computer::*;
+
+fn main() {
+    //No input.txt in the repo for this one: pass the puzzle input's path
+    let path = std::env::args()
+        .nth(1)
+        .expect("Usage: day_19 <path to puzzle input>");
+    let program = load_program(path, &[]).unwrap_or_else(|error| panic!("{}", error));
+    let mut beam = Beam::new(&program);
+
+    // PART 1
+    //Points affected by the beam in the 50x50 area closest to the emitter
+    let mut tracker = EdgeTracker::new(&mut beam);
+    let affected = tracker.count(50, 50);
+    println!("{}", beam.render(50, 50));
+    println!("{}", affected);
+
+    // PART 2
+    //Closest 100x100 square that fits in the beam, as x * 10000 + y
+    let mut tracker = EdgeTracker::new(&mut beam);
+    let (x, y) = tracker
+        .closest_square(100, 100_000)
+        .expect("The square never fits in the beam");
+    println!("{}", x * 10000 + y);
+    println!("({} questions asked to the drones)", beam.queries());
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    //Answers 1 when x / 2 <= y <= 2 * x
+    const CONE: [i64; 31] = [
+        3, 100, 3, 101, //x, y
+        1002, 100, 2, 102, 7, 102, 101, 103, //y > 2x
+        1002, 101, 2, 104, 7, 104, 100, 105, //x > 2y
+        1, 103, 105, 106, 1008, 106, 0, 107, 4, 107, 99,
+    ];
+
+    //The slow way: ask about every single point
+    fn brute_force_square(beam: &mut Beam, size: u64) -> (u64, u64) {
+        for y in 0.. {
+            for x in 0..=2 * y {
+                if (0..size).all(|dy| (0..size).all(|dx| beam.query(x + dx, y + dy))) {
+                    return (x, y);
+                }
+            }
+        }
+        unreachable!()
+    }
+
+    #[test]
+    fn tracking() {
+        let mut beam = Beam::new(&CONE);
+        let mut tracker = EdgeTracker::new(&mut beam);
+        assert_eq!(tracker.count(10, 10), 50);
+        let mut square = vec![];
+        for size in 1..6 {
+            square.push(tracker.closest_square(size, 1000).unwrap());
+        }
+        //A lot fewer questions than scanning every point would take
+        assert!(beam.queries() < 100);
+        for (size, &corner) in (1..6).zip(&square) {
+            assert_eq!(corner, brute_force_square(&mut beam, size));
+        }
+    }
+
+    #[test]
+    fn render() {
+        let mut beam = Beam::new(&CONE);
+        EdgeTracker::new(&mut beam).count(5, 5);
+        assert_eq!(beam.render(5, 3), "#.   \n.##. \n ####\n");
+    }
+}
@@ -0,0 +1,178 @@
+mod springscript;
+use intcode_computer::*;
+use springscript::*;
+
+//How a springdroid run ended
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    //Made it across, and reported this much damage to the hull
+    HullDamage(i64),
+    //Fell into a hole: the droid prints an ASCII animation of the fall
+    Fell(String),
+}
+
+fn run_springdroid(program: &[i64], script: &Script) -> Outcome {
+    let mut droid = Vm::new(program);
+    for line in script.to_string().lines() {
+        droid.push_line(line);
+    }
+    if droid.run() != State::Halted {
+        panic!("The droid wants more input:\n{}", droid.take_ascii().text);
+    }
+    let output = droid.take_ascii();
+    match output.values.last() {
+        Some(&damage) => Outcome::HullDamage(damage),
+        None => Outcome::Fell(output.text),
+    }
+}
+
+/* Tries formulas (for J) one after the other until the droid makes it across.
+ * Formulas that don't compile or don't fit are skipped, failures are reported with the droid's last moments.
+ */
+fn search<'a, I: IntoIterator<Item = &'a str>>(
+    program: &[i64],
+    mode: Mode,
+    formulas: I,
+) -> Option<(&'a str, i64)> {
+    for formula in formulas {
+        let script = formula
+            .parse::<Expr>()
+            .and_then(|expr| compile(&expr, mode));
+        match script.map(|script| run_springdroid(program, &script)) {
+            Ok(Outcome::HullDamage(damage)) => return Some((formula, damage)),
+            Ok(Outcome::Fell(animation)) => {
+                //The last frame is enough to see which hole it fell in
+                let frames: Vec<&str> = animation.trim_end().split("\n\n").collect();
+                eprintln!("{} fell:\n{}\n", formula, frames.last().unwrap());
+            }
+            Err(error) => eprintln!("{} skipped: {}", formula, error),
+        }
+    }
+    None
+}
+
+//Jump if there's a hole in the next three tiles and ground where we land (D)
+const WALK_FORMULAS: [&str; 3] = ["!A", "!A | !C & D", "(!A | !B | !C) & D"];
+//Same, but don't jump if we'd be stuck on landing: we need to be able to walk (E) or jump again (H)
+const RUN_FORMULAS: [&str; 3] = [
+    "(!A | !B | !C) & D",
+    "(!A | !B | !C) & D & H",
+    "(!A | !B | !C) & D & (E | H)",
+];
+
+fn main() {
+    //No input.txt in the repo for this one: pass the puzzle input's path
+    //Any extra argument is a formula to try before the built-in ones, for both parts
+    let mut args = std::env::args().skip(1);
+    let path = args
+        .next()
+        .expect("Usage: day_21 <path to puzzle input> [formula...]");
+    let program = load_program(path, &[]).unwrap_or_else(|error| panic!("{}", error));
+    let extra: Vec<String> = args.collect();
+
+    for (mode, formulas) in [(Mode::Walk, WALK_FORMULAS), (Mode::Run, RUN_FORMULAS)] {
+        let candidates = extra.iter().map(|f| f.as_str()).chain(formulas);
+        match search(&program, mode, candidates) {
+            Some((formula, damage)) => println!("{} ({})", damage, formula),
+            None => println!("No formula made it across in {} mode", mode),
+        }
+    }
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    //What the droid does with a script: J is what it ends up with
+    fn interpret(script: &Script, sensors: u16) -> bool {
+        let (mut t, mut j) = (false, false);
+        for instruction in &script.instructions {
+            let source = match instruction.source {
+                Reg::Sensor(index) => sensors & (1 << index) != 0,
+                Reg::T => t,
+                Reg::J => j,
+            };
+            let destination = match instruction.destination {
+                Reg::T => &mut t,
+                Reg::J => &mut j,
+                Reg::Sensor(_) => unreachable!(),
+            };
+            *destination = match instruction.op {
+                Op::And => source && *destination,
+                Op::Or => source || *destination,
+                Op::Not => !source,
+            };
+        }
+        j
+    }
+
+    fn evaluate(expr: &Expr, sensors: u16) -> bool {
+        match expr {
+            Expr::Sensor(index) => sensors & (1 << index) != 0,
+            Expr::Not(inner) => !evaluate(inner, sensors),
+            Expr::And(left, right) => evaluate(left, sensors) && evaluate(right, sensors),
+            Expr::Or(left, right) => evaluate(left, sensors) || evaluate(right, sensors),
+        }
+    }
+
+    #[test]
+    fn parse_and_validate() {
+        let script: Script = "NOT A J # hole right ahead\n\nNOT C T\nAND D T\nOR T J\nWALK\n"
+            .parse()
+            .unwrap();
+        assert_eq!(script.instructions.len(), 4);
+        assert_eq!(script.instructions[3].to_string(), "OR T J");
+        assert_eq!(
+            script.to_string(),
+            "NOT A J\nNOT C T\nAND D T\nOR T J\nWALK\n"
+        );
+
+        assert!("NOT E J\nWALK".parse::<Script>().is_err());
+        assert!("NOT E J\nRUN".parse::<Script>().is_ok());
+        assert!("NOT J A\nWALK".parse::<Script>().is_err());
+        assert!("JMP A J\nWALK".parse::<Script>().is_err());
+        assert!("NOT A J".parse::<Script>().is_err());
+        assert!("WALK\nNOT A J".parse::<Script>().is_err());
+        let too_long = "NOT A J\n".repeat(MAX_INSTRUCTIONS + 1) + "WALK";
+        assert!(too_long.parse::<Script>().is_err());
+    }
+
+    #[test]
+    fn compiler() {
+        let formulas = RUN_FORMULAS.iter().chain(&WALK_FORMULAS).chain(&[
+            "A",
+            "!!A",
+            "!(A & B) | C",
+            "(A | B) & (C | D)",
+            "(A | B) & (C | D | !E & F)",
+            "(A | !B) & (C | D)",
+        ]);
+        for formula in formulas {
+            let expr: Expr = formula.parse().unwrap();
+            let script = compile(&expr, Mode::Run).unwrap();
+            for sensors in 0..1 << 9 {
+                assert_eq!(
+                    interpret(&script, sensors),
+                    evaluate(&expr, sensors),
+                    "{} with sensors {:09b}:\n{}",
+                    formula,
+                    sensors,
+                    script
+                );
+            }
+        }
+        assert_eq!(
+            compile(&"(!A | !B | !C) & D".parse().unwrap(), Mode::Walk)
+                .unwrap()
+                .instructions
+                .len(),
+            6
+        );
+        //Doesn't fit in two registers, or uses sensors WALK can't see
+        assert!(compile(&"(!A | B) & (C | !D) & (E | F)".parse().unwrap(), Mode::Run).is_err());
+        assert!(compile(&"!A & H".parse().unwrap(), Mode::Walk).is_err());
+        assert!("(A | B".parse::<Expr>().is_err());
+        assert!("A B".parse::<Expr>().is_err());
+    }
+}
@@ -0,0 +1,349 @@
+use std::fmt;
+use std::str::FromStr;
+
+//The springdroid's memory holds that many instructions, not one more
+pub const MAX_INSTRUCTIONS: usize = 15;
+
+/* Registers: A to I tell whether there's ground 1 to 9 tiles ahead (read-only),
+ * T is a scratch register, and the droid jumps if J is true once the script is over.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Reg {
+    Sensor(u8),
+    T,
+    J,
+}
+impl Reg {
+    fn parse(name: &str) -> Result<Reg, String> {
+        match name {
+            "T" => Ok(Reg::T),
+            "J" => Ok(Reg::J),
+            _ if name.len() == 1 && ("A"..="I").contains(&name) => {
+                Ok(Reg::Sensor(name.as_bytes()[0] - b'A'))
+            }
+            _ => Err(format!("Unknown register {}", name)),
+        }
+    }
+}
+impl fmt::Display for Reg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reg::Sensor(index) => write!(f, "{}", (b'A' + index) as char),
+            Reg::T => write!(f, "T"),
+            Reg::J => write!(f, "J"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Op {
+    And,
+    Or,
+    Not,
+}
+
+//op source destination, like "AND A J"
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Instruction {
+    pub op: Op,
+    pub source: Reg,
+    pub destination: Reg,
+}
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self.op {
+            Op::And => "AND",
+            Op::Or => "OR",
+            Op::Not => "NOT",
+        };
+        write!(f, "{} {} {}", op, self.source, self.destination)
+    }
+}
+
+/* What ends the script: WALK only sees 4 tiles ahead (A to D), RUN sees all 9.
+ * Also the word that tells the droid to start.
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Mode {
+    Walk,
+    Run,
+}
+impl Mode {
+    fn sensors(self) -> u8 {
+        match self {
+            Mode::Walk => 4,
+            Mode::Run => 9,
+        }
+    }
+}
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mode::Walk => write!(f, "WALK"),
+            Mode::Run => write!(f, "RUN"),
+        }
+    }
+}
+
+//A springscript program, as it gets sent to the droid
+#[derive(Clone, Debug, PartialEq)]
+pub struct Script {
+    pub instructions: Vec<Instruction>,
+    pub mode: Mode,
+}
+impl Script {
+    //Everything the droid would refuse
+    pub fn validate(&self) -> Result<(), String> {
+        if self.instructions.len() > MAX_INSTRUCTIONS {
+            return Err(format!(
+                "{} instructions, the droid only holds {}",
+                self.instructions.len(),
+                MAX_INSTRUCTIONS
+            ));
+        }
+        for instruction in &self.instructions {
+            if let Reg::Sensor(_) = instruction.destination {
+                return Err(format!("{}: sensors can't be written to", instruction));
+            }
+            if let Reg::Sensor(index) = instruction.source {
+                if index >= self.mode.sensors() {
+                    return Err(format!(
+                        "{}: {} can't be read in {} mode",
+                        instruction, instruction.source, self.mode
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+//One instruction per line, and the mode on the last one
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for instruction in &self.instructions {
+            writeln!(f, "{}", instruction)?;
+        }
+        writeln!(f, "{}", self.mode)
+    }
+}
+//Blank lines and # comments are allowed, the droid never sees them
+impl FromStr for Script {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut instructions = vec![];
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let error = |message: String| format!("Line {}: {}", number + 1, message);
+            let op = match words.first() {
+                None => continue,
+                Some(&"WALK") | Some(&"RUN") if words.len() > 1 => {
+                    return Err(error(format!("{} doesn't take arguments", words[0])))
+                }
+                Some(&"WALK") | Some(&"RUN") => {
+                    let mode = if words[0] == "WALK" {
+                        Mode::Walk
+                    } else {
+                        Mode::Run
+                    };
+                    //Nothing but comments after the mode
+                    if let Some(extra) = text
+                        .lines()
+                        .skip(number + 1)
+                        .find(|l| !l.split('#').next().unwrap().trim().is_empty())
+                    {
+                        return Err(error(format!("\"{}\" after {}", extra.trim(), mode)));
+                    }
+                    let script = Script { instructions, mode };
+                    script.validate()?;
+                    return Ok(script);
+                }
+                Some(&"AND") => Op::And,
+                Some(&"OR") => Op::Or,
+                Some(&"NOT") => Op::Not,
+                Some(word) => return Err(error(format!("Unknown instruction {}", word))),
+            };
+            if words.len() != 3 {
+                return Err(error(format!("\"{}\" should have two registers", line)));
+            }
+            instructions.push(Instruction {
+                op,
+                source: Reg::parse(words[1]).map_err(error)?,
+                destination: Reg::parse(words[2]).map_err(error)?,
+            });
+        }
+        Err("The script has to end with WALK or RUN".to_string())
+    }
+}
+
+//A boolean formula over the sensors, what we'd like J to be
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Sensor(u8),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+/* Formulas are written with ! (not), & (and), | (or) and parentheses, in that order of precedence:
+ *  (!A | !B | !C) & D
+ */
+impl FromStr for Expr {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let tokens: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+        let mut position = 0;
+        let expr = parse_or(&tokens, &mut position)?;
+        if position < tokens.len() {
+            return Err(format!("Unexpected '{}' in {}", tokens[position], text));
+        }
+        Ok(expr)
+    }
+}
+
+fn parse_or(tokens: &[char], position: &mut usize) -> Result<Expr, String> {
+    let mut expr = parse_and(tokens, position)?;
+    while tokens.get(*position) == Some(&'|') {
+        *position += 1;
+        expr = Expr::Or(Box::new(expr), Box::new(parse_and(tokens, position)?));
+    }
+    Ok(expr)
+}
+
+fn parse_and(tokens: &[char], position: &mut usize) -> Result<Expr, String> {
+    let mut expr = parse_not(tokens, position)?;
+    while tokens.get(*position) == Some(&'&') {
+        *position += 1;
+        expr = Expr::And(Box::new(expr), Box::new(parse_not(tokens, position)?));
+    }
+    Ok(expr)
+}
+
+fn parse_not(tokens: &[char], position: &mut usize) -> Result<Expr, String> {
+    let token = tokens.get(*position).copied();
+    *position += 1;
+    match token {
+        Some('!') => Ok(Expr::Not(Box::new(parse_not(tokens, position)?))),
+        Some('(') => {
+            let expr = parse_or(tokens, position)?;
+            if tokens.get(*position) != Some(&')') {
+                return Err("Missing )".to_string());
+            }
+            *position += 1;
+            Ok(expr)
+        }
+        Some(c @ 'A'..='I') => Ok(Expr::Sensor(c as u8 - b'A')),
+        Some(c) => Err(format!("Unexpected '{}'", c)),
+        None => Err("Unexpected end of formula".to_string()),
+    }
+}
+
+/* Turns a formula into a script that leaves its value in J.
+ * There are only two writable registers, so the right side of every & and | has to be computed
+ * without the scratch register: most formulas fit anyway, but (!A | B) & (C | !D) & (E | F) doesn't.
+ */
+pub fn compile(expr: &Expr, mode: Mode) -> Result<Script, String> {
+    let mut instructions = vec![];
+    compile_into(expr, Reg::J, Some(Reg::T), &mut instructions)?;
+    let script = Script { instructions, mode };
+    script.validate()?;
+    Ok(script)
+}
+
+fn instruction(op: Op, source: Reg, destination: Reg) -> Instruction {
+    Instruction {
+        op,
+        source,
+        destination,
+    }
+}
+
+//Computes expr into target, and only touches scratch if there is one
+fn compile_into(
+    expr: &Expr,
+    target: Reg,
+    scratch: Option<Reg>,
+    instructions: &mut Vec<Instruction>,
+) -> Result<(), String> {
+    match expr {
+        //Nothing copies a register, but two NOTs do
+        Expr::Sensor(index) => {
+            instructions.push(instruction(Op::Not, Reg::Sensor(*index), target));
+            instructions.push(instruction(Op::Not, target, target));
+        }
+        Expr::Not(inner) => match &**inner {
+            Expr::Sensor(index) => {
+                instructions.push(instruction(Op::Not, Reg::Sensor(*index), target))
+            }
+            Expr::Not(inner) => compile_into(inner, target, scratch, instructions)?,
+            _ => {
+                compile_into(inner, target, scratch, instructions)?;
+                instructions.push(instruction(Op::Not, target, target));
+            }
+        },
+        Expr::And(left, right) | Expr::Or(left, right) => {
+            let op = if let Expr::And(_, _) = expr {
+                Op::And
+            } else {
+                Op::Or
+            };
+            //Both sides can be swapped, keep the simplest one for last
+            let (left, right) = if operand_cost(left) < operand_cost(right) {
+                (right, left)
+            } else {
+                (left, right)
+            };
+            match &**right {
+                Expr::Sensor(index) => {
+                    compile_into(left, target, scratch, instructions)?;
+                    instructions.push(instruction(op, Reg::Sensor(*index), target));
+                }
+                //Negated sensors go through the scratch register
+                Expr::Not(inner) if matches!(**inner, Expr::Sensor(_)) => match (scratch, &**inner)
+                {
+                    (Some(scratch), _) => {
+                        compile_into(left, target, Some(scratch), instructions)?;
+                        compile_into(right, scratch, None, instructions)?;
+                        instructions.push(instruction(op, scratch, target));
+                    }
+                    //Or without it, thanks to De Morgan: left & !s is !(!left | s)
+                    (None, Expr::Sensor(index)) => {
+                        let dual = if op == Op::And { Op::Or } else { Op::And };
+                        compile_into(&negate(left), target, None, instructions)?;
+                        instructions.push(instruction(dual, Reg::Sensor(*index), target));
+                        instructions.push(instruction(Op::Not, target, target));
+                    }
+                    _ => unreachable!(),
+                },
+                //Anything bigger is computed in the scratch register first, while target is still free to use
+                _ => {
+                    let scratch = scratch
+                        .ok_or_else(|| format!("{:?} needs more than two registers", expr))?;
+                    compile_into(right, scratch, Some(target), instructions)?;
+                    compile_into(left, target, None, instructions)?;
+                    instructions.push(instruction(op, scratch, target));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+//Pushes the NOT all the way down to the sensors
+fn negate(expr: &Expr) -> Expr {
+    match expr {
+        Expr::Sensor(_) => Expr::Not(Box::new(expr.clone())),
+        Expr::Not(inner) => (**inner).clone(),
+        Expr::And(left, right) => Expr::Or(Box::new(negate(left)), Box::new(negate(right))),
+        Expr::Or(left, right) => Expr::And(Box::new(negate(left)), Box::new(negate(right))),
+    }
+}
+
+//0 for a sensor (used directly), 1 for a negated one (needs scratch), 2 for anything else
+fn operand_cost(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Sensor(_) => 0,
+        Expr::Not(inner) if matches!(**inner, Expr::Sensor(_)) => 1,
+        _ => 2,
+    }
+}
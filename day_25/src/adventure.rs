@@ -0,0 +1,272 @@
+use intcode_computer::*;
+use std::collections::HashMap;
+
+//Items that end the game (or the droid's ability to move) when taken, so the explorer never touches them
+pub const DEADLY: [&str; 5] = [
+    "escape pod",
+    "giant electromagnet",
+    "infinite loop",
+    "molten lava",
+    "photons",
+];
+
+//Where the droid has to go to get weighed
+const CHECKPOINT: &str = "Security Checkpoint";
+
+//A command that takes longer than that to answer is never going to answer
+const STEP_LIMIT: usize = 1_000_000;
+
+/* What the game prints when entering a room:
+ *  == Hull Breach ==
+ *  You got in through a hole in the floor here.
+ *
+ *  Doors here lead:
+ *  - north
+ *
+ *  Items here:
+ *  - mug
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Room {
+    pub name: String,
+    pub doors: Vec<String>,
+    pub items: Vec<String>,
+}
+
+//Every room described in some output, in order (being thrown out of a room describes two of them)
+pub fn parse_rooms(text: &str) -> Vec<Room> {
+    let mut rooms: Vec<Room> = vec![];
+    //Which list the "- " lines go to
+    let mut list = None;
+    for line in text.lines() {
+        if line.starts_with("== ") && line.ends_with(" ==") {
+            rooms.push(Room {
+                name: line[3..line.len() - 3].to_string(),
+                doors: vec![],
+                items: vec![],
+            });
+            list = None;
+        } else if line == "Doors here lead:" {
+            list = Some(true);
+        } else if line == "Items here:" {
+            list = Some(false);
+        } else if let (Some(room), Some(entry)) = (rooms.last_mut(), line.strip_prefix("- ")) {
+            match list {
+                Some(true) => room.doors.push(entry.to_string()),
+                Some(false) => room.items.push(entry.to_string()),
+                None => {}
+            }
+        } else {
+            list = None;
+        }
+    }
+    rooms
+}
+
+pub fn opposite(door: &str) -> &'static str {
+    match door {
+        "north" => "south",
+        "south" => "north",
+        "east" => "west",
+        "west" => "east",
+        _ => panic!("Unknown door {}", door),
+    }
+}
+
+//The airlock password, once the droid made it past the checkpoint
+pub fn password(text: &str) -> Option<String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let typing = words.iter().position(|&word| word == "typing")?;
+    words.get(typing + 1).map(|word| word.to_string())
+}
+
+//What the game did with a command
+#[derive(Debug, PartialEq)]
+pub enum Reply {
+    //Printed this, and asks for the next command
+    Prompt(String),
+    //Printed this, and the game is over
+    Halted(String),
+    //Never came back (hello infinite loop)
+    Stuck,
+}
+
+//The game, talked to in plain text
+pub struct Adventure {
+    vm: Vm,
+}
+impl Adventure {
+    pub fn new(vm: Vm) -> Self {
+        Adventure { vm }
+    }
+
+    //An empty command only runs the game, to get whatever it prints first
+    pub fn send(&mut self, command: &str) -> Reply {
+        if !command.is_empty() {
+            self.vm.push_line(command);
+        }
+        for _ in 0..STEP_LIMIT {
+            match self.vm.step() {
+                State::Running => {}
+                State::WaitingForInput => return Reply::Prompt(self.vm.take_ascii().text),
+                State::Halted => return Reply::Halted(self.vm.take_ascii().text),
+            }
+        }
+        Reply::Stuck
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        self.vm.snapshot()
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.vm.restore(snapshot);
+    }
+}
+
+/* Walks the droid through the whole ship, picks up everything that's safe to carry, then goes to the checkpoint.
+ * Items that aren't known to be deadly are tried from a snapshot: if the game dies or gets stuck, we go back in time.
+ */
+pub struct Explorer {
+    adventure: Adventure,
+    pub rooms: HashMap<String, Room>,
+    pub inventory: Vec<String>,
+    //Items that turned out badly, on top of the known ones
+    pub deadly: Vec<String>,
+    //Doors to take from the start to reach the checkpoint
+    checkpoint_path: Option<Vec<String>>,
+    //The door from the checkpoint to the floor that weighs the droid
+    pressure_door: Option<String>,
+}
+impl Explorer {
+    pub fn new(adventure: Adventure) -> Self {
+        Explorer {
+            adventure,
+            rooms: HashMap::new(),
+            inventory: vec![],
+            deadly: vec![],
+            checkpoint_path: None,
+            pressure_door: None,
+        }
+    }
+
+    fn send(&mut self, command: &str) -> String {
+        match self.adventure.send(command) {
+            Reply::Prompt(text) => text,
+            reply => panic!("The game ended on \"{}\": {:?}", command, reply),
+        }
+    }
+
+    fn current_room(text: &str) -> Room {
+        parse_rooms(text)
+            .pop()
+            .unwrap_or_else(|| panic!("No room in \"{}\"", text))
+    }
+
+    //Maps the ship from the starting room, and comes back to it
+    pub fn explore(&mut self) {
+        let text = self.send("");
+        self.visit(Self::current_room(&text), &mut vec![]);
+    }
+
+    fn visit(&mut self, room: Room, path: &mut Vec<String>) {
+        self.rooms.insert(room.name.clone(), room.clone());
+        if room.name == CHECKPOINT {
+            self.checkpoint_path = Some(path.clone());
+        }
+        for item in &room.items {
+            if !DEADLY.contains(&item.as_str()) {
+                self.take_safely(item);
+            }
+        }
+        for door in &room.doors {
+            //That's where we came from
+            if path.last().map(|d| opposite(d)) == Some(door.as_str()) {
+                continue;
+            }
+            let next = Self::current_room(&self.send(door));
+            if next.name == room.name {
+                //Thrown back: that's the pressure-sensitive floor
+                self.pressure_door = Some(door.clone());
+            } else if self.rooms.contains_key(&next.name) {
+                self.send(opposite(door));
+            } else {
+                path.push(door.clone());
+                self.visit(next, path);
+                path.pop();
+                self.send(opposite(door));
+            }
+        }
+    }
+
+    fn take_safely(&mut self, item: &str) {
+        let snapshot = self.adventure.snapshot();
+        match self.adventure.send(&format!("take {}", item)) {
+            Reply::Prompt(_) => self.inventory.push(item.to_string()),
+            _ => {
+                self.adventure.restore(&snapshot);
+                self.deadly.push(item.to_string());
+            }
+        }
+    }
+
+    /* Tries every combination of items on the pressure-sensitive floor until one weighs just right.
+     * Gives back what the game said when it let the droid through.
+     */
+    pub fn crack_checkpoint(&mut self) -> Option<String> {
+        let path = self
+            .checkpoint_path
+            .clone()
+            .expect("The checkpoint wasn't found");
+        let door = self
+            .pressure_door
+            .clone()
+            .expect("The pressure-sensitive floor wasn't found");
+        for door in &path {
+            self.send(door);
+        }
+        let items = self.inventory.clone();
+        let mut holding = (1 << items.len()) - 1;
+        for combination in 0..1 << items.len() {
+            for (index, item) in items.iter().enumerate() {
+                let (held, wanted) = (holding & 1 << index != 0, combination & 1 << index != 0);
+                if held && !wanted {
+                    self.send(&format!("drop {}", item));
+                } else if wanted && !held {
+                    self.send(&format!("take {}", item));
+                }
+            }
+            holding = combination;
+            match self.adventure.send(&door) {
+                Reply::Halted(text) => return Some(text),
+                Reply::Prompt(_) => {}
+                Reply::Stuck => panic!("The checkpoint never answered"),
+            }
+        }
+        None
+    }
+}
+
+/* Lets a person play: the game's text goes to stdout, and lines from stdin go to the game.
+ * Stops when the game is over or stdin is closed.
+ */
+pub fn play(adventure: &mut Adventure) {
+    let mut command = String::new();
+    loop {
+        match adventure.send(command.trim_end()) {
+            Reply::Prompt(text) => print!("{}", text),
+            Reply::Halted(text) => {
+                print!("{}", text);
+                return;
+            }
+            Reply::Stuck => {
+                println!("(The game stopped answering)");
+                return;
+            }
+        }
+        command.clear();
+        if std::io::stdin().read_line(&mut command).unwrap_or(0) == 0 {
+            return;
+        }
+    }
+}
@@ -0,0 +1,229 @@
+mod adventure;
+use adventure::*;
+use intcode_computer::*;
+
+fn main() {
+    //No input.txt in the repo for this one: pass the puzzle input's path
+    //Add --play to play the game yourself instead
+    let mut args = std::env::args().skip(1);
+    let path = args
+        .next()
+        .expect("Usage: day_25 <path to puzzle input> [--play]");
+    let program = load_program(path, &[]).unwrap_or_else(|error| panic!("{}", error));
+    let mut adventure = Adventure::new(Vm::new(&program));
+    if args.next().as_deref() == Some("--play") {
+        play(&mut adventure);
+        return;
+    }
+
+    //Pick up everything, then find the right weight for the pressure-sensitive floor
+    let mut explorer = Explorer::new(adventure);
+    explorer.explore();
+    println!(
+        "{} rooms, carrying {:?} (not touching {:?})",
+        explorer.rooms.len(),
+        explorer.inventory,
+        explorer.deadly
+    );
+    let text = explorer
+        .crack_checkpoint()
+        .expect("No combination of items got past the checkpoint");
+    match password(&text) {
+        Some(password) => println!("{}", password),
+        None => println!("{}", text),
+    }
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    //A tiny ship, with the pressure-sensitive floor east of the checkpoint
+    const ROOMS: [(&str, [Option<usize>; 4]); 4] = [
+        //Doors: north, south, west, east
+        ("Hull Breach", [Some(1), None, None, Some(2)]),
+        ("Kitchen", [None, Some(0), None, None]),
+        (
+            "Security Checkpoint",
+            [Some(3), None, Some(0), Some(PRESSURE)],
+        ),
+        ("Library", [None, Some(2), None, None]),
+    ];
+    const PRESSURE: usize = 100;
+    const DOORS: [&str; 4] = ["north", "south", "west", "east"];
+    //Name, starting room, weight
+    const ITEMS: [(&str, i64, i64); 4] = [
+        ("mug", 1, 3),
+        ("cursed idol", 1, 0),
+        ("book", 3, 5),
+        ("lamp", 3, 4),
+    ];
+    const RIGHT_WEIGHT: i64 = 7;
+    //Where items and the droid are stored in memory, so snapshots bring them back
+    const ROOM: usize = 101;
+    const ITEM_ROOMS: usize = 110;
+    const INVENTORY: i64 = 99;
+    const LOOP: usize = 7;
+
+    /* The whole game as a custom opcode: "60 c" runs a command that starts with the character c,
+     * the rest of the line being still in the input (c is 0 at the very start).
+     */
+    struct FakeGame;
+    impl FakeGame {
+        //Items don't move until they're taken, so 0 means wherever they started
+        fn item_room(memory: &Vec<i64>, item: usize) -> i64 {
+            match memory.read(ITEM_ROOMS + item) {
+                0 => ITEMS[item].1,
+                room => room - 1,
+            }
+        }
+
+        fn describe(memory: &Vec<i64>, room: usize) -> String {
+            let mut text = format!(
+                "\n\n\n== {} ==\nA room.\n\nDoors here lead:\n",
+                ROOMS[room].0
+            );
+            for (door, to) in DOORS.iter().zip(&ROOMS[room].1) {
+                if to.is_some() {
+                    text += &format!("- {}\n", door);
+                }
+            }
+            let items: Vec<usize> = (0..ITEMS.len())
+                .filter(|&item| Self::item_room(memory, item) == room as i64)
+                .collect();
+            if !items.is_empty() {
+                text += "\nItems here:\n";
+                for item in items {
+                    text += &format!("- {}\n", ITEMS[item].0);
+                }
+            }
+            text
+        }
+    }
+    impl OpcodeHandler for FakeGame {
+        fn opcode(&self) -> i64 {
+            60
+        }
+        fn arity(&self) -> usize {
+            1
+        }
+        fn execute(&mut self, args: &[i64], context: &mut HandlerContext) {
+            let mut command = String::new();
+            if args[0] != 0 {
+                command.push(args[0] as u8 as char);
+                while let Some(c) = context.input.pop_front() {
+                    if c == 10 {
+                        break;
+                    }
+                    command.push(c as u8 as char);
+                }
+            }
+            let memory = &mut *context.memory;
+            let room = memory.read(ROOM) as usize;
+            let mut text = match command.split_once(' ') {
+                _ if command.is_empty() => Self::describe(memory, room),
+                None => match DOORS.iter().position(|&door| door == command) {
+                    Some(door) => match ROOMS[room].1[door] {
+                        Some(PRESSURE) => {
+                            let weight: i64 = (0..ITEMS.len())
+                                .filter(|&item| Self::item_room(memory, item) == INVENTORY)
+                                .map(|item| ITEMS[item].2)
+                                .sum();
+                            if weight == RIGHT_WEIGHT {
+                                *context.ip = 1_000_000;
+                                "\nOh, hello! You should be able to get in by typing 1234 on the keypad.\n".to_string()
+                            } else {
+                                "\n\n\n== Pressure-Sensitive Floor ==\nA loud voice says you are ejected back to the checkpoint.\n".to_string()
+                                    + &Self::describe(memory, room)
+                            }
+                        }
+                        Some(next) => {
+                            memory.write(ROOM, next as i64);
+                            Self::describe(memory, next)
+                        }
+                        None => "\nYou can't go that way.\n".to_string(),
+                    },
+                    None => "\nUnrecognized command.\n".to_string(),
+                },
+                Some((verb, name)) => {
+                    let item = ITEMS.iter().position(|item| item.0 == name).unwrap();
+                    let (from, to) = match verb {
+                        "take" => (room as i64, INVENTORY),
+                        _ => (INVENTORY, room as i64),
+                    };
+                    if name == "cursed idol" {
+                        *context.ip = LOOP;
+                        return;
+                    }
+                    assert_eq!(
+                        Self::item_room(memory, item),
+                        from,
+                        "Can't {} {}",
+                        verb,
+                        name
+                    );
+                    memory.write(ITEM_ROOMS + item, to + 1);
+                    format!("\nYou {} the {}.\n", verb, name)
+                }
+            };
+            if *context.ip != 1_000_000 {
+                text += "\nCommand?\n";
+            }
+            context.output.extend(text.bytes().map(|b| b as i64));
+        }
+    }
+
+    fn fake_game() -> Adventure {
+        let mut registry = OpcodeRegistry::new();
+        registry.register(FakeGame);
+        //Run a command, read the first character of the next one, start over. 7 loops forever
+        Adventure::new(
+            VmBuilder::new(&[60, 100, 3, 100, 1105, 1, 0, 1105, 1, 7])
+                .registry(registry)
+                .build(),
+        )
+    }
+
+    #[test]
+    fn rooms() {
+        let mut game = fake_game();
+        let start = match game.send("") {
+            Reply::Prompt(text) => parse_rooms(&text),
+            reply => panic!("{:?}", reply),
+        };
+        assert_eq!(
+            start,
+            vec![Room {
+                name: "Hull Breach".to_string(),
+                doors: vec!["north".to_string(), "east".to_string()],
+                items: vec![],
+            }]
+        );
+        game.send("east");
+        let thrown_back = match game.send("east") {
+            Reply::Prompt(text) => parse_rooms(&text),
+            reply => panic!("{:?}", reply),
+        };
+        assert_eq!(thrown_back.len(), 2);
+        assert_eq!(thrown_back[0].name, "Pressure-Sensitive Floor");
+        assert_eq!(thrown_back[1].name, "Security Checkpoint");
+        assert_eq!(thrown_back[1].doors, vec!["north", "west", "east"]);
+        game.send("west");
+        if let Reply::Prompt(text) = game.send("north") {
+            assert_eq!(parse_rooms(&text)[0].items, vec!["mug", "cursed idol"]);
+        }
+        assert_eq!(game.send("take cursed idol"), Reply::Stuck);
+    }
+
+    #[test]
+    fn explore_and_crack() {
+        let mut explorer = Explorer::new(fake_game());
+        explorer.explore();
+        assert_eq!(explorer.rooms.len(), 4);
+        assert_eq!(explorer.inventory, vec!["mug", "book", "lamp"]);
+        assert_eq!(explorer.deadly, vec!["cursed idol"]);
+        let text = explorer.crack_checkpoint().unwrap();
+        assert_eq!(password(&text), Some("1234".to_string()));
+    }
+}
@@ -0,0 +1,29 @@
+#![no_main]
+//cargo +nightly fuzz run differential (from the intcode-computer directory)
+use intcode_computer::differential::compare;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    //First byte: how many input values, then the inputs, then the program. Every word is 2 bytes.
+    if data.is_empty() {
+        return;
+    }
+    let words: Vec<i64> = data[1..]
+        .chunks_exact(2)
+        .map(|w| i16::from_le_bytes([w[0], w[1]]) as i64)
+        .collect();
+    let input_count = (data[0] as usize % 4).min(words.len());
+    let (input, program) = words.split_at(input_count);
+    if program.is_empty() {
+        return;
+    }
+
+    //libfuzzer aborts on any panic, but crashing programs are fine as long as every backend crashes the same way
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let result = compare(program, input, 10_000);
+    std::panic::set_hook(hook);
+    if let Err(mismatch) = result {
+        panic!("{}", mismatch);
+    }
+});
@@ -0,0 +1,23 @@
+# The examples from day 2: add and multiply, and what memory looks like afterwards
+
+[1,0,0,0,99 becomes 2,0,0,0,99 (1 + 1 = 2)]
+program: 1,0,0,0,99
+memory: 2,0,0,0,99
+steps: 2
+
+[2,3,0,3,99 becomes 2,3,0,6,99 (3 * 2 = 6)]
+program: 2,3,0,3,99
+memory: 2,3,0,6,99
+
+[2,4,4,5,99,0 becomes 2,4,4,5,99,9801 (99 * 99 = 9801)]
+program: 2,4,4,5,99,0
+memory: 2,4,4,5,99,9801
+
+[1,1,1,4,99,5,6,0,99 becomes 30,1,1,4,2,5,6,0,99]
+program: 1,1,1,4,99,5,6,0,99
+memory: 30,1,1,4,2,5,6,0,99
+steps: 3
+
+[the longer example]
+program: 1,9,10,3,2,3,11,0,99,30,40,50
+memory: 0=3500, 3=70
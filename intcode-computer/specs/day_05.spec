@@ -0,0 +1,70 @@
+# The examples from day 5: input/output, parameter modes, comparisons and jumps
+
+[outputs whatever it gets as input]
+program: 3,0,4,0,99
+input: 3
+output: 3
+
+[immediate mode]
+program: 1002,4,3,4,33
+memory: 4=99
+
+[negative numbers]
+program: 1101,100,-1,4,0
+memory: 4=99
+
+[equal to 8, position mode: yes]
+program: 3,9,8,9,10,9,4,9,99,-1,8
+input: 8
+output: 1
+
+[equal to 8, position mode: no]
+program: 3,9,8,9,10,9,4,9,99,-1,8
+input: 7
+output: 0
+
+[less than 8, position mode]
+program: 3,9,7,9,10,9,4,9,99,-1,8
+input: 5
+output: 1
+
+[equal to 8, immediate mode]
+program: 3,3,1108,-1,8,3,4,3,99
+input: 8
+output: 1
+
+[less than 8, immediate mode]
+program: 3,3,1107,-1,8,3,4,3,99
+input: 9
+output: 0
+
+[jump, position mode: zero]
+program: 3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9
+input: 0
+output: 0
+
+[jump, immediate mode: not zero]
+program: 3,3,1105,-1,9,1101,0,0,12,4,12,99,1
+input: 5
+output: 1
+
+[compared to 8: below]
+program: 3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,
+         1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,
+         999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99
+input: 7
+output: 999
+
+[compared to 8: equal]
+program: 3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,
+         1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,
+         999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99
+input: 8
+output: 1000
+
+[compared to 8: above]
+program: 3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,
+         1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,
+         999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99
+input: 9
+output: 1001
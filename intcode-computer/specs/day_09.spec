@@ -0,0 +1,21 @@
+# The examples from day 9: relative mode, big numbers and memory past the end of the program
+
+[quine]
+program: 109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99
+output: 109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99
+memory: 100=16
+
+[16 digit number]
+program: 1102,34915192,34915192,7,4,7,99,0
+output: 1219070632396864
+
+[large number]
+program: 104,1125899906842624,99
+output: 1125899906842624
+steps: 2
+
+[relative mode write]
+program: 109,10,203,0,204,0,99
+input: 42
+output: 42
+memory: 10=42
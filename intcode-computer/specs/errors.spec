@@ -0,0 +1,23 @@
+# Programs the computer should refuse to run
+
+[negative address]
+program: 1,0,0,-1,99
+error: negative address
+
+[negative jump]
+program: 1105,1,-1
+error: jump to a negative address
+
+[unknown opcode]
+program: 42
+error: invalid instruction type
+
+[invalid parameter mode]
+program: 304,0,99
+error: Invalid flag
+
+[no input left]
+program: 3,0,3,0,99
+input: 1
+error: Ran out of input
+
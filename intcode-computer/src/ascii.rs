@@ -0,0 +1,64 @@
+use crate::Vm;
+
+/* Output of an ASCII-capable program: the text it printed,
+ * and whatever it output that isn't an ASCII character (usually the answer, at the very end).
+ */
+#[derive(Debug, Default, PartialEq)]
+pub struct AsciiOutput {
+    pub text: String,
+    pub values: Vec<i64>,
+}
+
+//Talking to programs that read and write text, one character per value
+impl Vm {
+    //Queues the line followed by a newline, which is how these programs know the line is over
+    pub fn push_line(&mut self, line: &str) {
+        for c in line.chars() {
+            if !c.is_ascii() {
+                panic!("Only ASCII can be sent to the computer: {:?}", c);
+            }
+            self.push_input(c as i64);
+        }
+        self.push_input('\n' as i64);
+    }
+
+    //Everything that was output since the last call, decoded
+    pub fn take_ascii(&mut self) -> AsciiOutput {
+        let mut output = AsciiOutput::default();
+        for value in self.take_output() {
+            match value {
+                0..=127 => output.text.push(value as u8 as char),
+                _ => output.values.push(value),
+            }
+        }
+        output
+    }
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn echo() {
+        //Outputs everything it reads, then 1000 once it reads a newline
+        let mut vm = Vm::new(&[
+            3, 100, 4, 100, 1008, 100, 10, 101, 1006, 101, 0, 104, 1000, 99,
+        ]);
+        vm.push_line("Hi!");
+        assert_eq!(vm.run(), State::Halted);
+        assert_eq!(
+            vm.take_ascii(),
+            AsciiOutput {
+                text: "Hi!\n".to_string(),
+                values: vec![1000],
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Only ASCII")]
+    fn not_ascii() {
+        Vm::new(&[99]).push_line("é");
+    }
+}
@@ -0,0 +1,147 @@
+use crate::{execute_at, Instruction};
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use std::fmt;
+
+//Why an async run stopped before the program halted
+#[derive(Debug, PartialEq)]
+pub enum AsyncError<E> {
+    //The program wanted input, but the input stream was over
+    InputEnded,
+    //The output sink refused a value
+    Output(E),
+}
+impl<E: fmt::Debug> fmt::Display for AsyncError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsyncError::InputEnded => write!(
+                f,
+                "The input stream ended while the program was waiting for input"
+            ),
+            AsyncError::Output(error) => write!(f, "The output sink failed: {:?}", error),
+        }
+    }
+}
+
+/* An intcode computer reading from a Stream and writing to a Sink, one instruction at a time with execute_at.
+ * An In instruction awaits the next input, so the run yields to the executor whenever no input is ready yet:
+ * VMs chained with channels all make progress on a single thread, whatever runs them.
+ */
+pub struct AsyncIntcodeVm<I, O> {
+    memory: Vec<i64>,
+    ip: usize,
+    relative_base: i64,
+    input: I,
+    output: O,
+}
+impl<I, O> AsyncIntcodeVm<I, O>
+where
+    I: Stream<Item = i64> + Unpin,
+    O: Sink<i64> + Unpin,
+{
+    pub fn new(program: &[i64], input: I, output: O) -> Self {
+        AsyncIntcodeVm {
+            memory: program.to_vec(),
+            ip: 0,
+            relative_base: 0,
+            input,
+            output,
+        }
+    }
+
+    pub fn memory(&self) -> &[i64] {
+        &self.memory
+    }
+
+    //Gives the streams back, once they're not needed anymore
+    pub fn into_parts(self) -> (I, O) {
+        (self.input, self.output)
+    }
+
+    //Runs until the program halts
+    pub async fn run(&mut self) -> Result<(), AsyncError<O::Error>> {
+        let mut produced = vec![];
+        while self.ip < self.memory.len() {
+            if self.memory[self.ip] % 100 == 3 {
+                //Decode it first, a broken In should panic like anywhere else instead of waiting
+                Instruction::new(&self.memory, &mut self.ip.clone(), self.relative_base);
+                //Whoever we're waiting for might be waiting for our output
+                self.output.flush().await.map_err(AsyncError::Output)?;
+                let value = self.input.next().await.ok_or(AsyncError::InputEnded)?;
+                execute_at(
+                    &mut self.ip,
+                    &mut self.relative_base,
+                    &mut self.memory,
+                    &mut [value].iter(),
+                    &mut produced,
+                );
+            } else {
+                execute_at(
+                    &mut self.ip,
+                    &mut self.relative_base,
+                    &mut self.memory,
+                    &mut std::iter::empty(),
+                    &mut produced,
+                );
+            }
+            for value in produced.drain(..) {
+                self.output.feed(value).await.map_err(AsyncError::Output)?;
+            }
+        }
+        self.output.flush().await.map_err(AsyncError::Output)
+    }
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use futures::channel::mpsc;
+    use futures::executor::{block_on, LocalPool};
+    use futures::stream;
+    use futures::task::LocalSpawnExt;
+
+    //Reads n, outputs 2n, stops after n = 0
+    const DOUBLER: [i64; 12] = [3, 20, 1002, 20, 2, 21, 4, 21, 1005, 20, 0, 99];
+
+    #[test]
+    fn same_as_execute() {
+        let program = [3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+        let mut vm = AsyncIntcodeVm::new(&program, stream::iter(vec![8]), vec![]);
+        assert_eq!(block_on(vm.run()), Ok(()));
+        assert_eq!(vm.into_parts().1, execute(&mut program.to_vec(), &vec![8]));
+    }
+
+    #[test]
+    fn input_ended() {
+        let mut vm = AsyncIntcodeVm::new(&DOUBLER, stream::iter(vec![1, 2]), vec![]);
+        assert_eq!(block_on(vm.run()), Err(AsyncError::InputEnded));
+        assert_eq!(vm.into_parts().1, vec![2, 4]);
+    }
+
+    #[test]
+    fn pipeline() {
+        //Two doublers in a row, on a single thread
+        let (input, first_in) = mpsc::unbounded();
+        let (first_out, second_in) = mpsc::unbounded();
+        let (second_out, mut output) = mpsc::unbounded();
+        let mut pool = LocalPool::new();
+        for (input, output) in [(first_in, first_out), (second_in, second_out)] {
+            let mut vm = AsyncIntcodeVm::new(&DOUBLER, input, output);
+            pool.spawner()
+                .spawn_local(async move { vm.run().await.unwrap() })
+                .unwrap();
+        }
+        //Both are waiting on In, nothing to do
+        pool.run_until_stalled();
+        assert!(output.try_recv().unwrap_err().is_empty());
+        input.unbounded_send(3).unwrap();
+        pool.run_until_stalled();
+        assert_eq!(output.try_recv().ok(), Some(12));
+        input.unbounded_send(0).unwrap();
+        //Both halt after a 0
+        pool.run();
+        assert_eq!(output.try_recv().ok(), Some(0));
+        //Closed, the second one is done too
+        assert!(output.try_recv().unwrap_err().is_closed());
+    }
+}
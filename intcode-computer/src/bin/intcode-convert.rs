@@ -0,0 +1,32 @@
+use intcode_computer::*;
+use std::io::Write;
+
+const USAGE: &str = "Usage: intcode-convert <text|binary|hex|json> <input file> [output file]
+  Converts a program or memory dump to that format. The input format is detected,
+  the output goes to stdout when there's no output file";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (format, input, output) = match &args[..] {
+        [format, input] => (format, input, None),
+        [format, input, output] => (format, input, Some(output)),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    let fail = |error: String| -> ! {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    };
+    let format: Format = format.parse().unwrap_or_else(|error| fail(error));
+    let program = load_program(input, &[]).unwrap_or_else(|error| fail(error));
+    let bytes = format.encode(&program);
+    let written = match output {
+        Some(path) => std::fs::write(path, &bytes),
+        None => std::io::stdout().write_all(&bytes),
+    };
+    if let Err(error) = written {
+        fail(format!("Can't write the output: {}", error));
+    }
+}
@@ -0,0 +1,230 @@
+use intcode_computer::*;
+use std::io::{BufRead, Write};
+
+const USAGE: &str =
+    "Usage: intcode-dbg --core <file>\n       intcode-dbg <program file> [input values, comma separated]";
+const LIVE_HELP: &str = "Commands:
+  break <expr>            stop when expr is true after a step, e.g. break mem[223] > 1000
+  watch <expr>            stop when the value of expr changes
+  log <expr> \"<message>\"  print the message when expr is true, without stopping: log produced \"out: {out}\"
+  points                  list them
+  delete <id>
+  run                     run until a point stops it, or the program halts or needs input
+  step [n]                execute n instructions (default 1), points are still checked
+  input <values>          queue input, comma separated
+  print <expr>            e.g. print mem[rb + 1]
+  dis [address] [n]       disassemble n instructions from address (default: ip)
+  mem <address> [n]       n raw words from address
+  quit
+Expressions: numbers, mem[...], + - * / % == != < <= > >= ! && || and parentheses, with the variables
+  ip rb steps, at opcode (of the last instruction executed), consumed in produced out (by it), inputs outputs (so far)";
+const HELP: &str = "Commands:
+  info                 where it crashed, and why
+  trace                the last instructions executed before the crash
+  dis [address] [n]    disassemble n instructions from address (default: the crash site)
+  mem <address> [n]    n raw words from address
+  quit";
+
+//Disassembles from address on, with addresses counted from the start of memory
+fn listing(memory: &[i64], address: usize, count: usize, ip: usize) -> String {
+    let mut text = String::new();
+    if address >= memory.len() {
+        return text;
+    }
+    for line in disassemble(&memory[address..]).iter().take(count) {
+        let address = address + line.address;
+        let marker = if address == ip { '>' } else { ' ' };
+        text += &format!("{} {:>6} | {}\n", marker, address, line.text);
+    }
+    text
+}
+
+fn info(core: &CoreDump) -> String {
+    format!(
+        "Crashed after {} steps: {}\nip: {}, relative base: {}\npending input: {:?}\noutput: {:?}\n",
+        core.steps, core.error, core.ip, core.relative_base, core.pending_input, core.output
+    )
+}
+
+//Runs a single command, and gives back what to print (None to quit)
+fn command(core: &CoreDump, line: &str) -> Option<String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let number = |index: usize| words.get(index).and_then(|word| word.parse::<usize>().ok());
+    Some(match words.first() {
+        None => String::new(),
+        Some(&"quit") | Some(&"q") => return None,
+        Some(&"info") => info(core),
+        Some(&"trace") => core
+            .trace
+            .iter()
+            .map(|event| format!("{:>8} {:>6} | {}\n", event.step, event.ip, event.text))
+            .collect(),
+        //Starts at the crash site by default: the sweep has to start on an instruction, and that one is
+        Some(&"dis") => listing(
+            &core.memory,
+            number(1).unwrap_or(core.ip),
+            number(2).unwrap_or(10),
+            core.ip,
+        ),
+        Some(&"mem") => mem(&core.memory, number(1), number(2)),
+        Some(_) => format!("{}\n", HELP),
+    })
+}
+
+fn mem(memory: &[i64], address: Option<usize>, count: Option<usize>) -> String {
+    match address {
+        Some(address) => {
+            let end = (address + count.unwrap_or(1)).min(memory.len());
+            let words = memory.get(address..end).unwrap_or(&[]);
+            format!("{:>6} | {:?}\n", address, words)
+        }
+        None => "mem needs an address\n".to_string(),
+    }
+}
+
+fn parse_input(text: &str) -> Result<Vec<i64>, String> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| format!("Invalid input \"{}\"", value))
+        })
+        .collect()
+}
+
+fn stop(debugger: &mut Debugger, stop: Stop) -> String {
+    //Logs were already printed as they fired
+    let vm = debugger.vm();
+    let mut text = match stop {
+        Stop::State(State::Running) => String::new(),
+        Stop::State(State::WaitingForInput) => "Waiting for input\n".to_string(),
+        Stop::State(State::Halted) => "Halted\n".to_string(),
+        Stop::Break(id) => format!("Breakpoint {} hit after {} steps\n", id, vm.steps()),
+        Stop::Watch { id, old, new } => format!("Watch {}: {} -> {}\n", id, old, new),
+    };
+    let output = debugger.vm_mut().take_output();
+    if !output.is_empty() {
+        text += &format!("output: {:?}\n", output);
+    }
+    text + &listing(
+        debugger.vm().memory(),
+        debugger.vm().ip(),
+        1,
+        debugger.vm().ip(),
+    )
+}
+
+//Same as command, on a VM that's still running
+fn live_command(debugger: &mut Debugger, line: &str) -> Option<String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let number = |index: usize| words.get(index).and_then(|word| word.parse::<usize>().ok());
+    //Everything after the command
+    let rest = line
+        .trim()
+        .split_once(char::is_whitespace)
+        .map_or("", |(_, rest)| rest);
+    Some(match words.first() {
+        None => String::new(),
+        Some(&"quit") | Some(&"q") => return None,
+        Some(&"break") | Some(&"watch") | Some(&"log") => match line.parse() {
+            Ok(point) => format!("Watchpoint {} added\n", debugger.add(point)),
+            Err(error) => format!("{}\n", error),
+        },
+        Some(&"points") => debugger
+            .points()
+            .map(|(id, point)| format!("{:>3} {}\n", id, point))
+            .collect(),
+        Some(&"delete") => match number(1).and_then(|id| debugger.delete(id)) {
+            Some(point) => format!("Deleted {}\n", point),
+            None => "No such point\n".to_string(),
+        },
+        Some(&"run") | Some(&"c") => {
+            let result = debugger.run();
+            stop(debugger, result)
+        }
+        Some(&"step") | Some(&"s") => {
+            let mut result = Stop::State(State::Running);
+            for _ in 0..number(1).unwrap_or(1) {
+                result = debugger.step();
+                if result != Stop::State(State::Running) {
+                    break;
+                }
+            }
+            stop(debugger, result)
+        }
+        Some(&"input") => match parse_input(rest) {
+            Ok(values) => {
+                for value in values {
+                    debugger.vm_mut().push_input(value);
+                }
+                String::new()
+            }
+            Err(error) => format!("{}\n", error),
+        },
+        Some(&"print") | Some(&"p") => match rest.parse() {
+            Ok(expr) => format!("{}\n", debugger.evaluate(&expr)),
+            Err(error) => format!("{}\n", error),
+        },
+        Some(&"dis") => {
+            let ip = debugger.vm().ip();
+            listing(
+                debugger.vm().memory(),
+                number(1).unwrap_or(ip),
+                number(2).unwrap_or(10),
+                ip,
+            )
+        }
+        Some(&"mem") => mem(debugger.vm().memory(), number(1), number(2)),
+        Some(_) => format!("{}\n", LIVE_HELP),
+    })
+}
+
+fn prompt<F: FnMut(&str) -> Option<String>>(mut command: F) {
+    let stdin = std::io::stdin();
+    loop {
+        print!("(dbg) ");
+        std::io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        match command(&line) {
+            Some(output) => print!("{}", output),
+            None => break,
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let path = match &args[..] {
+        [flag, path] if flag == "--core" => path,
+        [program] | [program, _] if !program.starts_with('-') => {
+            let input = parse_input(args.get(1).map_or("", String::as_str))
+                .unwrap_or_else(|error| panic!("{}", error));
+            let program = load_program(program, &[]).unwrap_or_else(|error| panic!("{}", error));
+            let mut debugger = Debugger::new(VmBuilder::new(&program).input(&input).build());
+            print!("{}", listing(&program, 0, 5, 0));
+            prompt(|line| live_command(&mut debugger, line));
+            return;
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    let text = std::fs::read_to_string(path)
+        .unwrap_or_else(|error| panic!("Can't read {}: {}", path, error));
+    let core: CoreDump = text
+        .parse()
+        .unwrap_or_else(|error| panic!("{} isn't a core dump: {}", path, error));
+
+    print!("{}", info(&core));
+    if let Some(last) = core.trace.last() {
+        println!("crashed on: {}", last.text);
+    }
+    print!("{}", listing(&core.memory, core.ip, 5, core.ip));
+    prompt(|line| command(&core, line));
+}
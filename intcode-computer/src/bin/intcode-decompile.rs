@@ -0,0 +1,26 @@
+use intcode_computer::*;
+
+const USAGE: &str = "Usage: intcode-decompile <program file>
+  Prints the program as C-like pseudocode";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let path = match &args[..] {
+        [path] => path,
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    let program = load_program(path, &[]).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    });
+    match decompile(&program) {
+        Ok(code) => print!("{}", code),
+        Err(error) => {
+            eprintln!("Can't decompile {}: {}", path, error);
+            std::process::exit(1);
+        }
+    }
+}
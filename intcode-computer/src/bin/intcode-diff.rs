@@ -0,0 +1,48 @@
+use intcode_computer::*;
+
+const USAGE: &str = "Usage: intcode-diff [--patch] <first program> <second program>
+  Lines up the two programs by instructions. --patch prints address=value lines turning the first into the second instead";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (as_patch, paths) = match &args[..] {
+        [flag, a, b] if flag == "--patch" => (true, [a, b]),
+        [a, b] => (false, [a, b]),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    let [a, b] = paths.map(|path| {
+        load_program(path, &[]).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        })
+    });
+
+    if as_patch {
+        if b.len() < a.len() {
+            eprintln!(
+                "The second program is {} words shorter, a patch can't remove them",
+                a.len() - b.len()
+            );
+        }
+        print!("{}", Patch::between(&a, &b));
+        return;
+    }
+    let lines = diff(&a, &b);
+    for line in &lines {
+        match line {
+            DiffLine::Same(a, b) => println!("  {:>6} {:>6} | {}", a.address, b.address, a.text),
+            DiffLine::Removed(a) => println!("- {:>6} {:>6} | {}", a.address, "", a.text),
+            DiffLine::Added(b) => println!("+ {:>6} {:>6} | {}", "", b.address, b.text),
+        }
+    }
+    //Same exit codes as diff: 1 when something differs
+    if lines
+        .iter()
+        .any(|line| !matches!(line, DiffLine::Same(_, _)))
+    {
+        std::process::exit(1);
+    }
+}
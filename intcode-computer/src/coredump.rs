@@ -0,0 +1,143 @@
+use std::fmt;
+use std::str::FromStr;
+
+//The message a panic was given, from the payload catch_unwind returns
+pub fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    match payload.downcast_ref::<String>() {
+        Some(message) => message.clone(),
+        None => match payload.downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => "(unknown panic)".to_string(),
+        },
+    }
+}
+
+//An instruction the VM executed: the step it was, where it was, and what it looked like
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceEvent {
+    pub step: u64,
+    pub ip: usize,
+    pub text: String,
+}
+
+/* Everything a VM had when it crashed, so it can be looked at after the fact (see intcode-dbg --core).
+ * ip points at the instruction that failed. Saved as text:
+ *  error: Attempting to access a negative address: -1
+ *  ip: 4
+ *  relative_base: 0
+ *  steps: 1
+ *  input: 5,6
+ *  output:
+ *  trace:
+ *  0 0 ADD [9], [10], [11]
+ *  1 4 ADD [-1], [0], [0]
+ *  memory: 1,9,10,11,1,-1,0,0,99,1,2,3
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoreDump {
+    pub error: String,
+    pub memory: Vec<i64>,
+    pub ip: usize,
+    pub relative_base: i64,
+    pub steps: u64,
+    //Oldest first
+    pub trace: Vec<TraceEvent>,
+    pub pending_input: Vec<i64>,
+    pub output: Vec<i64>,
+}
+
+fn join(values: &[i64]) -> String {
+    values
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn split(text: &str) -> Result<Vec<i64>, String> {
+    if text.is_empty() {
+        return Ok(vec![]);
+    }
+    text.split(',')
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| format!("Invalid value \"{}\"", value))
+        })
+        .collect()
+}
+
+impl fmt::Display for CoreDump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        //Panic messages can span lines, only the first one is kept
+        writeln!(f, "error: {}", self.error.lines().next().unwrap_or(""))?;
+        writeln!(f, "ip: {}", self.ip)?;
+        writeln!(f, "relative_base: {}", self.relative_base)?;
+        writeln!(f, "steps: {}", self.steps)?;
+        writeln!(f, "input: {}", join(&self.pending_input))?;
+        writeln!(f, "output: {}", join(&self.output))?;
+        writeln!(f, "trace:")?;
+        for event in &self.trace {
+            writeln!(f, "{} {} {}", event.step, event.ip, event.text)?;
+        }
+        writeln!(f, "memory: {}", join(&self.memory))
+    }
+}
+impl FromStr for CoreDump {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut lines = text.lines();
+        let mut field = |name: &str| -> Result<String, String> {
+            let line = lines.next().ok_or(format!("Missing {}", name))?;
+            match line
+                .strip_prefix(name)
+                .and_then(|rest| rest.strip_prefix(':'))
+            {
+                Some(value) => Ok(value.trim().to_string()),
+                None => Err(format!("Expected {}, got \"{}\"", name, line)),
+            }
+        };
+        let number = |name: &str, value: String| format!("Invalid {}: \"{}\"", name, value);
+        let error = field("error")?;
+        let ip = field("ip")?;
+        let ip = ip.parse().map_err(|_| number("ip", ip))?;
+        let relative_base = field("relative_base")?;
+        let relative_base = relative_base
+            .parse()
+            .map_err(|_| number("relative_base", relative_base))?;
+        let steps = field("steps")?;
+        let steps = steps.parse().map_err(|_| number("steps", steps))?;
+        let pending_input = split(&field("input")?)?;
+        let output = split(&field("output")?)?;
+        field("trace")?;
+        let mut trace = vec![];
+        loop {
+            let line = lines.next().ok_or("Missing memory")?;
+            if let Some(memory) = line.strip_prefix("memory:") {
+                return Ok(CoreDump {
+                    error,
+                    memory: split(memory.trim())?,
+                    ip,
+                    relative_base,
+                    steps,
+                    trace,
+                    pending_input,
+                    output,
+                });
+            }
+            let mut words = line.splitn(3, ' ');
+            let invalid = || format!("Invalid trace event \"{}\"", line);
+            trace.push(TraceEvent {
+                step: words
+                    .next()
+                    .and_then(|w| w.parse().ok())
+                    .ok_or_else(invalid)?,
+                ip: words
+                    .next()
+                    .and_then(|w| w.parse().ok())
+                    .ok_or_else(invalid)?,
+                text: words.next().ok_or_else(invalid)?.to_string(),
+            });
+        }
+    }
+}
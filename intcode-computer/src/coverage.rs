@@ -0,0 +1,185 @@
+use crate::{disassemble, execute_at, Instruction, Memory};
+use std::collections::HashMap;
+
+//How many times a conditional jump went each way
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BranchHits {
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+/* Keeps track of which instructions of a program were executed (and how many times), and which way each conditional jump went.
+ * Running the same Coverage over several inputs adds everything up, so a whole test suite can share one.
+ */
+pub struct Coverage {
+    //The listing is always built from the program as it was before running it (self-modifying programs, hello)
+    program: Vec<i64>,
+    hits: HashMap<usize, u64>,
+    branches: HashMap<usize, BranchHits>,
+}
+impl Coverage {
+    pub fn new(program: &[i64]) -> Self {
+        Coverage {
+            program: program.to_vec(),
+            hits: HashMap::new(),
+            branches: HashMap::new(),
+        }
+    }
+
+    //Same as execute(), except every instruction gets counted on the way
+    pub fn execute<M: Memory + ?Sized>(&mut self, intcode: &mut M, input: &[i64]) -> Vec<i64> {
+        let mut index: usize = 0;
+        let mut relative_base = 0;
+        let mut output = vec![];
+        let mut input_iter = input.iter();
+        while index < intcode.len() {
+            *self.hits.entry(index).or_insert(0) += 1;
+            //Peek at the instruction before running it, so we know which way the jumps are going to go
+            let mut next = index;
+            let taken = match Instruction::new(intcode, &mut next, relative_base) {
+                Instruction::JumpIfTrue { cond, .. } => Some(cond.actual_value(intcode) != 0),
+                Instruction::JumpIfFalse { cond, .. } => Some(cond.actual_value(intcode) == 0),
+                _ => None,
+            };
+            if let Some(taken) = taken {
+                let branch = self.branches.entry(index).or_default();
+                if taken {
+                    branch.taken += 1;
+                } else {
+                    branch.not_taken += 1;
+                }
+            }
+            execute_at(
+                &mut index,
+                &mut relative_base,
+                intcode,
+                &mut input_iter,
+                &mut output,
+            );
+        }
+        output
+    }
+
+    //How many times the instruction starting at this address was executed
+    pub fn hits(&self, address: usize) -> u64 {
+        *self.hits.get(&address).unwrap_or(&0)
+    }
+
+    //None if there is no conditional jump there, or if it never ran
+    pub fn branch(&self, address: usize) -> Option<BranchHits> {
+        self.branches.get(&address).copied()
+    }
+
+    /* Disassembly of the program with hit counts in front of every line, and branch counts after the jumps.
+     *        3 |    0 | IN [12]
+     *        - |   11 | HALT
+     * There is no header, so line N of the listing is line N in the lcov export.
+     */
+    pub fn listing(&self) -> String {
+        let mut listing = String::new();
+        for line in disassemble(&self.program) {
+            let hits = match self.hits.get(&line.address) {
+                Some(hits) => hits.to_string(),
+                None => "-".to_string(),
+            };
+            listing += &format!("{:>8} | {:>4} | {}", hits, line.address, line.text);
+            if let Some(branch) = self.branch(line.address) {
+                listing += &format!("  (taken {}, not taken {})", branch.taken, branch.not_taken);
+            }
+            listing += "\n";
+        }
+        listing
+    }
+
+    /* lcov tracefile, so coverage tools (genhtml and friends) can read it.
+     * "Lines" are lines of listing(): save the listing as source_file and everything lines up.
+     * DATA lines are not counted unless something actually executed them.
+     */
+    pub fn lcov(&self, test_name: &str, source_file: &str) -> String {
+        let mut lcov = format!("TN:{}\nSF:{}\n", test_name, source_file);
+        let (mut lines_found, mut lines_hit) = (0, 0);
+        let (mut branches_found, mut branches_hit) = (0, 0);
+        for (number, line) in disassemble(&self.program).iter().enumerate() {
+            let number = number + 1;
+            let hits = self.hits(line.address);
+            if line.is_data && hits == 0 {
+                continue;
+            }
+            lcov += &format!("DA:{},{}\n", number, hits);
+            lines_found += 1;
+            if hits > 0 {
+                lines_hit += 1;
+            }
+            //Conditional jumps have two branches each: 0 is "taken", 1 is "not taken"
+            let opcode = self.program[line.address] % 100;
+            if !line.is_data && (opcode == 5 || opcode == 6) {
+                branches_found += 2;
+                match self.branch(line.address) {
+                    Some(branch) => {
+                        lcov += &format!("BRDA:{},0,0,{}\n", number, branch.taken);
+                        lcov += &format!("BRDA:{},0,1,{}\n", number, branch.not_taken);
+                        branches_hit += (branch.taken > 0) as u32 + (branch.not_taken > 0) as u32;
+                    }
+                    //lcov uses "-" for branches that were never even reached
+                    None => lcov += &format!("BRDA:{},0,0,-\nBRDA:{},0,1,-\n", number, number),
+                }
+            }
+        }
+        lcov += &format!("BRF:{}\nBRH:{}\n", branches_found, branches_hit);
+        lcov += &format!("LF:{}\nLH:{}\n", lines_found, lines_hit);
+        lcov += "end_of_record\n";
+        lcov
+    }
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    //Outputs 0 if the input was 0, 1 otherwise (from day 5's puzzle text)
+    const JUMP_TEST: [i64; 16] = [3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+
+    #[test]
+    fn hits_and_branches() {
+        let mut coverage = Coverage::new(&JUMP_TEST);
+        let mut intcode = JUMP_TEST;
+        assert_eq!(coverage.execute(&mut intcode, &[0]), vec![0]);
+        assert_eq!(coverage.hits(0), 1);
+        assert_eq!(coverage.hits(5), 0);
+        assert_eq!(
+            coverage.branch(2),
+            Some(BranchHits {
+                taken: 1,
+                not_taken: 0
+            })
+        );
+
+        let mut intcode = JUMP_TEST;
+        assert_eq!(coverage.execute(&mut intcode, &[7]), vec![1]);
+        assert_eq!(coverage.hits(0), 2);
+        assert_eq!(coverage.hits(5), 1);
+        assert_eq!(coverage.branch(2).unwrap().not_taken, 1);
+    }
+
+    #[test]
+    fn listing_and_lcov() {
+        let mut coverage = Coverage::new(&JUMP_TEST);
+        let mut intcode = JUMP_TEST;
+        coverage.execute(&mut intcode, &[0]);
+
+        let listing = coverage.listing();
+        let lines: Vec<&str> = listing.lines().collect();
+        assert_eq!(lines[0], "       1 |    0 | IN [12]");
+        assert_eq!(
+            lines[1],
+            "       1 |    2 | JF [12], [15]  (taken 1, not taken 0)"
+        );
+        assert_eq!(lines[2], "       - |    5 | ADD [13], [14], [13]");
+
+        let lcov = coverage.lcov("jump_test", "jump_test.lst");
+        assert!(lcov.starts_with("TN:jump_test\nSF:jump_test.lst\nDA:1,1\nDA:2,1\n"));
+        assert!(lcov.contains("BRDA:2,0,0,1\nBRDA:2,0,1,0\n"));
+        assert!(lcov.contains("BRF:2\nBRH:1\nLF:5\nLH:4\n"));
+        assert!(lcov.ends_with("end_of_record\n"));
+    }
+}
@@ -0,0 +1,1462 @@
+/* Turns a program back into something readable: C-like pseudocode, with functions, loops and if/else.
+ * Functions are found through the calling convention compiled intcode uses, with the relative base as a stack pointer:
+ *  21101, ret, 0, 0    [rb+0] = the return address
+ *  21101, 5, 0, 1      [rb+1], [rb+2]... = the arguments
+ *  1105, 1, f          jump to the function, it comes back to ret
+ *  f: 109, n           the function makes room for its frame
+ *  ...                 its arguments are in [rb-n+1]..., its local variables in the rest of the frame
+ *  109, -n             and gives the room back
+ *  2105, 1, 0          then jumps to the return address (the result, if any, is left in the first argument)
+ * Inside a function, frame slots are numbered from the relative base it was called with: 0 is the return address,
+ * then come the arguments (as many as the callers ever pass), then the local variables.
+ * The start of the program is main(), its relative base starts at 0, so its slots are plain memory.
+ *
+ * This is a static analysis, it only knows what can be decoded without running anything:
+ * the relative base has to move by constants only, the same way on every path to an instruction.
+ * Loops and ifs that don't fit the usual shapes are left as gotos.
+ */
+use crate::disasm::opcode_info;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Operand {
+    Const(i64),
+    Mem(i64),
+    //A frame slot, from the relative base the function was called with
+    Slot(i64),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Target {
+    Addr(usize),
+    Return,
+    //Wherever that operand says, that can't be followed
+    Unknown(Operand),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Kind {
+    Add(Operand, Operand, Operand),
+    Mul(Operand, Operand, Operand),
+    LessThan(Operand, Operand, Operand),
+    Equals(Operand, Operand, Operand),
+    In(Operand),
+    Out(Operand),
+    //Jumps when the operand is non-zero (true) or zero (false)
+    Branch(bool, Operand, Target),
+    Goto(Target),
+    //A jump that comes back to the next instruction
+    Call(usize),
+    //Moving the relative base, or a jump that's never taken
+    Nop,
+    Halt,
+    Crash,
+}
+impl Kind {
+    fn dest(&self) -> Option<Operand> {
+        match *self {
+            Kind::Add(_, _, dest)
+            | Kind::Mul(_, _, dest)
+            | Kind::LessThan(_, _, dest)
+            | Kind::Equals(_, _, dest)
+            | Kind::In(dest) => Some(dest),
+            _ => None,
+        }
+    }
+
+    fn reads(&self) -> Vec<Operand> {
+        match *self {
+            Kind::Add(lhs, rhs, _)
+            | Kind::Mul(lhs, rhs, _)
+            | Kind::LessThan(lhs, rhs, _)
+            | Kind::Equals(lhs, rhs, _) => vec![lhs, rhs],
+            Kind::Out(src) => vec![src],
+            Kind::Branch(_, cond, Target::Unknown(target)) => vec![cond, target],
+            Kind::Branch(_, cond, _) => vec![cond],
+            Kind::Goto(Target::Unknown(target)) => vec![target],
+            _ => vec![],
+        }
+    }
+
+    //What it computes, when that's known without running anything
+    fn constant(&self) -> Option<i64> {
+        match *self {
+            Kind::Add(Operand::Const(lhs), Operand::Const(rhs), _) => lhs.checked_add(rhs),
+            Kind::Mul(Operand::Const(lhs), Operand::Const(rhs), _) => lhs.checked_mul(rhs),
+            _ => None,
+        }
+    }
+
+    fn falls_through(&self) -> bool {
+        !matches!(self, Kind::Goto(_) | Kind::Halt | Kind::Crash)
+    }
+
+    fn ends_block(&self) -> bool {
+        matches!(
+            self,
+            Kind::Branch(..) | Kind::Goto(_) | Kind::Halt | Kind::Crash
+        )
+    }
+
+    //Where it can go next, the next instruction first
+    fn successors(&self, address: usize, len: usize) -> Vec<usize> {
+        let mut next = vec![];
+        if self.falls_through() {
+            next.push(address + len);
+        }
+        if let Kind::Branch(_, _, Target::Addr(target)) | Kind::Goto(Target::Addr(target)) = *self {
+            next.push(target);
+        }
+        next
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Instr {
+    len: usize,
+    //Where the relative base is before it runs, from the one the function was called with
+    delta: i64,
+    kind: Kind,
+    //The cell its jump target was read from
+    target_cell: Option<i64>,
+}
+
+//Every instruction reachable from the entry, without going into the functions it calls
+#[derive(Clone, Debug)]
+struct Function {
+    entry: usize,
+    instrs: BTreeMap<usize, Instr>,
+}
+impl Function {
+    fn is_main(&self) -> bool {
+        self.entry == 0
+    }
+
+    fn calls(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.instrs
+            .iter()
+            .filter_map(|(&address, instr)| match instr.kind {
+                Kind::Call(callee) => Some((address, callee)),
+                _ => None,
+            })
+    }
+
+    //The instruction that runs right before this one when nothing jumps
+    fn previous(&self, address: usize) -> Option<usize> {
+        self.instrs
+            .range(..address)
+            .next_back()
+            .filter(|(&previous, instr)| {
+                previous + instr.len == address && instr.kind.falls_through()
+            })
+            .map(|(&previous, _)| previous)
+    }
+
+    //Memory addresses it writes, main's slots included
+    fn memory_writes(&self) -> impl Iterator<Item = i64> + '_ {
+        let main = self.is_main();
+        self.instrs
+            .values()
+            .filter_map(move |instr| match instr.kind.dest()? {
+                Operand::Mem(address) => Some(address),
+                Operand::Slot(slot) if main => Some(slot),
+                _ => None,
+            })
+    }
+
+    //Memory addresses it reads, main's slots included
+    fn memory_reads(&self) -> impl Iterator<Item = i64> + '_ {
+        let main = self.is_main();
+        self.instrs
+            .values()
+            .flat_map(|instr| instr.kind.reads())
+            .filter_map(move |operand| match operand {
+                Operand::Mem(address) => Some(address),
+                Operand::Slot(slot) if main => Some(slot),
+                _ => None,
+            })
+    }
+}
+
+//(length, what it does, the relative base after it, the cell the jump target comes from)
+type Decoded = (usize, Kind, i64, Option<i64>);
+
+//Cells in dynamic hold jump targets that change, anything else is read straight from the program
+fn decode(
+    program: &[i64],
+    address: usize,
+    delta: i64,
+    main: bool,
+    dynamic: &BTreeSet<i64>,
+) -> Result<Decoded, String> {
+    let crash = Ok((1, Kind::Crash, delta, None));
+    let code = match program.get(address) {
+        Some(&code) if code >= 0 => code,
+        _ => return crash,
+    };
+    let arity = match opcode_info(code % 100) {
+        Some((_, arity)) if address + arity < program.len() => arity,
+        _ => return crash,
+    };
+    let modes: Vec<i64> = (0..arity)
+        .map(|n| (code / 10i64.pow(2 + n as u32)) % 10)
+        .collect();
+    if modes.iter().any(|&mode| mode > 2) {
+        return crash;
+    }
+    let operand = |n: usize| {
+        let value = program[address + n];
+        match modes[n - 1] {
+            0 => Operand::Mem(value),
+            1 => Operand::Const(value),
+            _ => Operand::Slot(delta + value),
+        }
+    };
+    //Writes ignore the mode, an immediate parameter is still an address
+    let dest = |n: usize| match operand(n) {
+        Operand::Const(value) => Operand::Mem(value),
+        operand => operand,
+    };
+    let mut target_cell = None;
+    let mut target = |n: usize| match operand(n) {
+        Operand::Const(value) if value >= 0 => Target::Addr(value as usize),
+        Operand::Mem(cell) if cell >= 0 && !dynamic.contains(&cell) => {
+            target_cell = Some(cell);
+            match program.get(cell as usize).copied().unwrap_or(0) {
+                value if value >= 0 => Target::Addr(value as usize),
+                value => Target::Unknown(Operand::Const(value)),
+            }
+        }
+        Operand::Slot(0) if !main => Target::Return,
+        operand => Target::Unknown(operand),
+    };
+    let kind = match code % 100 {
+        1 => Kind::Add(operand(1), operand(2), dest(3)),
+        2 => Kind::Mul(operand(1), operand(2), dest(3)),
+        3 => Kind::In(dest(1)),
+        4 => Kind::Out(operand(1)),
+        opcode @ 5 | opcode @ 6 => {
+            let when = opcode == 5;
+            match (operand(1), target(2)) {
+                (Operand::Const(cond), target) if (cond != 0) == when => Kind::Goto(target),
+                (Operand::Const(_), _) => {
+                    target_cell = None;
+                    Kind::Nop
+                }
+                (cond, target) => Kind::Branch(when, cond, target),
+            }
+        }
+        7 => Kind::LessThan(operand(1), operand(2), dest(3)),
+        8 => Kind::Equals(operand(1), operand(2), dest(3)),
+        9 => {
+            return match operand(1) {
+                Operand::Const(offset) => Ok((2, Kind::Nop, delta + offset, None)),
+                _ => Err(format!(
+                    "The relative base moves by a computed amount at {}",
+                    address
+                )),
+            }
+        }
+        _ => Kind::Halt,
+    };
+    Ok((1 + arity, kind, delta, target_cell))
+}
+
+fn walk(program: &[i64], entry: usize, dynamic: &BTreeSet<i64>) -> Result<Function, String> {
+    let main = entry == 0;
+    let mut instrs: BTreeMap<usize, Instr> = BTreeMap::new();
+    let mut todo = vec![(entry, 0)];
+    while let Some((mut address, mut delta)) = todo.pop() {
+        //The return address written to [rb+0] since the last jump, a jump right before it is a call
+        let mut return_address = None;
+        loop {
+            if let Some(instr) = instrs.get(&address) {
+                if instr.delta != delta {
+                    return Err(format!(
+                        "The relative base at {} depends on how it's reached",
+                        address
+                    ));
+                }
+                break;
+            }
+            let (len, mut kind, next_delta, target_cell) =
+                decode(program, address, delta, main, dynamic)?;
+            if let Kind::Goto(Target::Addr(callee)) = kind {
+                if return_address == Some((address + len) as i64) {
+                    kind = Kind::Call(callee);
+                }
+            }
+            if kind.dest() == Some(Operand::Slot(delta)) {
+                return_address = kind.constant();
+            }
+            if next_delta != delta {
+                return_address = None;
+            }
+            let mut successors = kind.successors(address, len).into_iter();
+            let next = if kind.falls_through() {
+                successors.next()
+            } else {
+                None
+            };
+            todo.extend(successors.map(|target| (target, next_delta)));
+            instrs.insert(
+                address,
+                Instr {
+                    len,
+                    delta,
+                    kind,
+                    target_cell,
+                },
+            );
+            delta = next_delta;
+            match next {
+                Some(next) => address = next,
+                None => break,
+            }
+        }
+    }
+
+    let mut previous_end = 0;
+    for (&address, instr) in &instrs {
+        if address < previous_end {
+            return Err(format!(
+                "The instruction at {} overlaps the one before it",
+                address
+            ));
+        }
+        previous_end = address + instr.len;
+    }
+    Ok(Function { entry, instrs })
+}
+
+//main, then every function it calls, and every function those call...
+fn walk_all(program: &[i64]) -> Result<BTreeMap<usize, Function>, String> {
+    let mut dynamic = BTreeSet::new();
+    loop {
+        let mut functions = BTreeMap::new();
+        let mut todo = vec![0];
+        while let Some(entry) = todo.pop() {
+            if functions.contains_key(&entry) {
+                continue;
+            }
+            let function = walk(program, entry, &dynamic)?;
+            todo.extend(function.calls().map(|(_, callee)| callee));
+            functions.insert(entry, function);
+        }
+
+        //Jump targets were read from the program, that's wrong for the cells that get written: try again without them
+        let written: BTreeSet<i64> = functions
+            .values()
+            .flat_map(|function| function.memory_writes())
+            .collect();
+        let changing: Vec<i64> = functions
+            .values()
+            .flat_map(|function| function.instrs.values())
+            .filter_map(|instr| instr.target_cell)
+            .filter(|cell| written.contains(cell) && !dynamic.contains(cell))
+            .collect();
+        if changing.is_empty() {
+            return Ok(functions);
+        }
+        dynamic.extend(changing);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Cond {
+    lhs: String,
+    op: &'static str,
+    rhs: String,
+}
+impl Cond {
+    fn negate(self) -> Self {
+        let op = match self.op {
+            "<" => ">=",
+            ">=" => "<",
+            "==" => "!=",
+            _ => "==",
+        };
+        Cond { op, ..self }
+    }
+}
+impl fmt::Display for Cond {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.lhs, self.op, self.rhs)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Stmt {
+    Line(String),
+    //Only shown when something jumps there
+    Label(usize),
+    If(Cond, Vec<Stmt>, Vec<Stmt>),
+    //None loops forever
+    While(Option<Cond>, Vec<Stmt>),
+    DoWhile(Vec<Stmt>, Cond),
+    Goto(usize),
+    Break,
+    Continue,
+}
+
+//Whether a continue in there would be for the loop around it
+fn continues(stmts: &[Stmt]) -> bool {
+    stmts.iter().any(|stmt| match stmt {
+        Stmt::Continue => true,
+        Stmt::If(_, then, otherwise) => continues(then) || continues(otherwise),
+        _ => false,
+    })
+}
+
+//Loops come out as while (1), that's where they get their real shape
+fn make_loop(mut body: Vec<Stmt>) -> Stmt {
+    if let Some(Stmt::If(cond, then, otherwise)) = body.first() {
+        if then == &[Stmt::Break] && otherwise.is_empty() {
+            let cond = cond.clone().negate();
+            body.remove(0);
+            return Stmt::While(Some(cond), body);
+        }
+    }
+    let len = body.len();
+    match &body[..] {
+        [rest @ .., Stmt::If(cond, then, otherwise), Stmt::Break]
+            if then == &[Stmt::Continue] && otherwise.is_empty() && !continues(rest) =>
+        {
+            Stmt::DoWhile(rest.to_vec(), cond.clone())
+        }
+        [rest @ .., Stmt::If(cond, then, otherwise)]
+            if then == &[Stmt::Break] && otherwise.is_empty() && !continues(rest) =>
+        {
+            Stmt::DoWhile(body[..len - 1].to_vec(), cond.clone().negate())
+        }
+        _ => Stmt::While(None, body),
+    }
+}
+
+//What's known about every function, and needed to write any of them
+struct Program {
+    functions: BTreeMap<usize, Function>,
+    //For each call, the instructions writing its arguments, by slot (0 being the return address)
+    args: HashMap<usize, BTreeMap<i64, usize>>,
+    //Calls whose result is used by the instruction right after them
+    results: HashMap<usize, usize>,
+    params: HashMap<usize, i64>,
+    returns: HashSet<usize>,
+    memory_reads: HashMap<i64, usize>,
+    written: HashSet<i64>,
+}
+
+//A basic block: instructions that always run one after the other
+struct Block {
+    start: usize,
+    addresses: Vec<usize>,
+}
+
+struct Writer<'a> {
+    program: &'a Program,
+    function: &'a Function,
+    blocks: Vec<Block>,
+    index: HashMap<usize, usize>,
+    //Instructions written as part of another one
+    skip: HashSet<usize>,
+    //Branches on a comparison that's only used there, and the comparison
+    compares: HashMap<usize, usize>,
+    //Returns, and the instruction writing the value they return
+    returned: HashMap<usize, usize>,
+    //Loop headers, and where their loops go on after a break
+    headers: BTreeMap<usize, Option<usize>>,
+    //The block every path from a block goes through first, if any
+    joins: Vec<Option<usize>>,
+    //(header, exit) of the loops around what's being written
+    loops: Vec<(usize, Option<usize>)>,
+    emitted: HashSet<usize>,
+    labels: BTreeSet<usize>,
+    locals: RefCell<BTreeSet<i64>>,
+}
+impl<'a> Writer<'a> {
+    fn new(program: &'a Program, function: &'a Function) -> Self {
+        let mut leaders = jump_targets(function);
+        leaders.extend(
+            function
+                .instrs
+                .keys()
+                .filter(|&&address| function.previous(address).is_none()),
+        );
+        let mut blocks: Vec<Block> = vec![];
+        for &address in function.instrs.keys() {
+            match blocks.last_mut() {
+                Some(block) if !leaders.contains(&address) => block.addresses.push(address),
+                _ => blocks.push(Block {
+                    start: address,
+                    addresses: vec![address],
+                }),
+            }
+        }
+        let index: HashMap<usize, usize> = blocks
+            .iter()
+            .enumerate()
+            .map(|(index, block)| (block.start, index))
+            .collect();
+
+        let mut skip = HashSet::new();
+        for (call, _) in function.calls() {
+            skip.extend(program.args[&call].values());
+            skip.extend(program.results.get(&call));
+        }
+        let params = program.params.get(&function.entry).copied().unwrap_or(0);
+        let mut slot_reads = HashMap::new();
+        for operand in function
+            .instrs
+            .values()
+            .flat_map(|instr| instr.kind.reads())
+        {
+            *slot_reads.entry(operand).or_insert(0) += 1;
+        }
+        let read_once = |operand: Operand| match operand {
+            Operand::Mem(address) => program.memory_reads.get(&address) == Some(&1),
+            Operand::Slot(slot) if function.is_main() => {
+                program.memory_reads.get(&slot) == Some(&1)
+            }
+            Operand::Slot(slot) => slot > params && slot_reads.get(&operand) == Some(&1),
+            Operand::Const(_) => false,
+        };
+
+        let mut compares = HashMap::new();
+        let mut returned = HashMap::new();
+        for (&address, instr) in &function.instrs {
+            let previous = match function.previous(address) {
+                Some(previous) if !leaders.contains(&address) && !skip.contains(&previous) => {
+                    previous
+                }
+                _ => continue,
+            };
+            match &instr.kind {
+                Kind::Branch(_, cond, _) => match function.instrs[&previous].kind {
+                    Kind::LessThan(_, _, dest) | Kind::Equals(_, _, dest)
+                        if dest == *cond && read_once(dest) =>
+                    {
+                        compares.insert(address, previous);
+                    }
+                    _ => {}
+                },
+                Kind::Goto(Target::Return) if program.returns.contains(&function.entry) => {
+                    //The result is written right before the frame goes away
+                    let mut writer = Some(previous);
+                    while let Some(before) = writer {
+                        match function.instrs[&before].kind {
+                            Kind::Nop if !leaders.contains(&before) => {
+                                writer = function.previous(before)
+                            }
+                            _ => break,
+                        }
+                    }
+                    if let Some(writer) = writer.filter(|writer| !skip.contains(writer)) {
+                        if function.instrs[&writer].kind.dest() == Some(Operand::Slot(1)) {
+                            returned.insert(address, writer);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        skip.extend(compares.values());
+        skip.extend(returned.values());
+
+        let successors: Vec<Vec<usize>> = blocks
+            .iter()
+            .map(|block| {
+                let last = *block.addresses.last().unwrap();
+                let instr = &function.instrs[&last];
+                instr
+                    .kind
+                    .successors(last, instr.len)
+                    .iter()
+                    .map(|address| index[address])
+                    .collect()
+            })
+            .collect();
+        let (headers, joins) = loops_and_joins(&successors, index[&function.entry]);
+
+        Writer {
+            program,
+            function,
+            blocks,
+            index,
+            skip,
+            compares,
+            returned,
+            headers,
+            joins,
+            loops: vec![],
+            emitted: HashSet::new(),
+            labels: BTreeSet::new(),
+            locals: RefCell::new(BTreeSet::new()),
+        }
+    }
+
+    fn name(&self, operand: Operand) -> String {
+        let params = self
+            .program
+            .params
+            .get(&self.function.entry)
+            .copied()
+            .unwrap_or(0);
+        match operand {
+            Operand::Const(value) => value.to_string(),
+            Operand::Mem(address) => format!("mem[{}]", address),
+            Operand::Slot(slot) if self.function.is_main() => format!("mem[{}]", slot),
+            Operand::Slot(slot) if slot < 0 => format!("frame[{}]", slot),
+            Operand::Slot(0) => "return_address".to_string(),
+            Operand::Slot(slot) if slot <= params => format!("arg{}", slot),
+            Operand::Slot(slot) => {
+                self.locals.borrow_mut().insert(slot);
+                format!("local{}", slot)
+            }
+        }
+    }
+
+    //Names the operand, unless it's the one replaced by a call's result
+    fn value(&self, operand: Operand, result: Option<(Operand, &str)>) -> String {
+        match result {
+            Some((replaced, call)) if replaced == operand => call.to_string(),
+            _ => self.name(operand),
+        }
+    }
+
+    fn expression(&self, kind: &Kind, result: Option<(Operand, &str)>) -> String {
+        if let Some(value) = kind.constant() {
+            return value.to_string();
+        }
+        let value = |operand| self.value(operand, result);
+        match *kind {
+            Kind::Add(Operand::Const(0), other, _) | Kind::Add(other, Operand::Const(0), _) => {
+                value(other)
+            }
+            Kind::Add(lhs, Operand::Const(rhs), _) if rhs < 0 => {
+                format!("{} - {}", value(lhs), rhs.unsigned_abs())
+            }
+            Kind::Add(lhs, rhs, _) => format!("{} + {}", value(lhs), value(rhs)),
+            Kind::Mul(Operand::Const(1), other, _) | Kind::Mul(other, Operand::Const(1), _) => {
+                value(other)
+            }
+            Kind::Mul(Operand::Const(-1), other, _) | Kind::Mul(other, Operand::Const(-1), _) => {
+                format!("-{}", value(other))
+            }
+            Kind::Mul(lhs, rhs, _) => format!("{} * {}", value(lhs), value(rhs)),
+            Kind::LessThan(lhs, rhs, _) => format!("{} < {}", value(lhs), value(rhs)),
+            Kind::Equals(lhs, rhs, _) => format!("{} == {}", value(lhs), value(rhs)),
+            _ => "input()".to_string(),
+        }
+    }
+
+    fn assignment(&self, kind: &Kind, result: Option<(Operand, &str)>) -> String {
+        let dest = kind.dest().unwrap();
+        let name = self.name(dest);
+        //x = x + 1 reads better as x += 1
+        let compound = match *kind {
+            Kind::Add(lhs, rhs, _) | Kind::Mul(lhs, rhs, _) if lhs == dest || rhs == dest => {
+                let other = if lhs == dest { rhs } else { lhs };
+                match (kind, other) {
+                    (_, other) if result.is_some_and(|(replaced, _)| replaced == other) => None,
+                    (Kind::Add(..), Operand::Const(value)) if value < 0 => {
+                        Some(format!("{} -= {};", name, value.unsigned_abs()))
+                    }
+                    (Kind::Add(..), other) => Some(format!("{} += {};", name, self.name(other))),
+                    (_, other) => Some(format!("{} *= {};", name, self.name(other))),
+                }
+            }
+            _ => None,
+        };
+        compound.unwrap_or_else(|| format!("{} = {};", name, self.expression(kind, result)))
+    }
+
+    fn call(&self, address: usize, callee: usize) -> String {
+        let args = &self.program.args[&address];
+        let last = args.keys().copied().max().unwrap_or(0);
+        let delta = self.function.instrs[&address].delta;
+        let args: Vec<String> = (1..=last)
+            .map(|slot| match args.get(&slot) {
+                Some(writer) => self.expression(&self.function.instrs[writer].kind, None),
+                None => self.name(Operand::Slot(delta + slot)),
+            })
+            .collect();
+        format!("fn_{}({})", callee, args.join(", "))
+    }
+
+    fn return_statement(&self, address: usize) -> String {
+        if !self.program.returns.contains(&self.function.entry) {
+            return "return;".to_string();
+        }
+        match self.returned.get(&address) {
+            Some(writer) => format!(
+                "return {};",
+                self.expression(&self.function.instrs[writer].kind, None)
+            ),
+            None => format!("return {};", self.name(Operand::Slot(1))),
+        }
+    }
+
+    //The block's instructions, its jumps aside
+    fn block(&self, index: usize) -> Vec<Stmt> {
+        let block = &self.blocks[index];
+        let mut stmts = vec![];
+        for &address in &block.addresses {
+            if self.skip.contains(&address) {
+                continue;
+            }
+            let instr = &self.function.instrs[&address];
+            let line = match instr.kind {
+                Kind::Add(..)
+                | Kind::Mul(..)
+                | Kind::LessThan(..)
+                | Kind::Equals(..)
+                | Kind::In(_) => self.assignment(&instr.kind, None),
+                Kind::Out(src) => format!("output({});", self.name(src)),
+                Kind::Call(callee) => {
+                    let call = self.call(address, callee);
+                    match self.program.results.get(&address) {
+                        Some(user) => {
+                            let result = Some((Operand::Slot(instr.delta + 1), call.as_str()));
+                            match self.function.instrs[user].kind {
+                                Kind::Out(src) => format!("output({});", self.value(src, result)),
+                                ref kind => self.assignment(kind, result),
+                            }
+                        }
+                        None => format!("{};", call),
+                    }
+                }
+                Kind::Goto(Target::Return) => self.return_statement(address),
+                Kind::Goto(Target::Unknown(target)) => format!("goto *{};", self.name(target)),
+                Kind::Halt => "halt();".to_string(),
+                //Self-modifying code looks like that, until it gets written
+                Kind::Crash if self.program.written.contains(&(address as i64)) => {
+                    "crash(); /* unless that code gets written before it runs */".to_string()
+                }
+                Kind::Crash => "crash();".to_string(),
+                _ => continue,
+            };
+            stmts.push(Stmt::Line(line));
+        }
+        stmts
+    }
+
+    fn cond(&self, address: usize) -> Cond {
+        let (when, cond) = match self.function.instrs[&address].kind {
+            Kind::Branch(when, cond, _) => (when, cond),
+            _ => unreachable!(),
+        };
+        let cond = match self.compares.get(&address) {
+            Some(compare) => match self.function.instrs[compare].kind {
+                Kind::LessThan(lhs, rhs, _) => Cond {
+                    lhs: self.name(lhs),
+                    op: "<",
+                    rhs: self.name(rhs),
+                },
+                Kind::Equals(lhs, rhs, _) => Cond {
+                    lhs: self.name(lhs),
+                    op: "==",
+                    rhs: self.name(rhs),
+                },
+                _ => unreachable!(),
+            },
+            None => Cond {
+                lhs: self.name(cond),
+                op: "!=",
+                rhs: "0".to_string(),
+            },
+        };
+        if when {
+            cond
+        } else {
+            cond.negate()
+        }
+    }
+
+    //Arriving at a block that's already taken care of: the loop's own jumps, or a goto
+    fn arrive(&mut self, index: usize) -> Option<Stmt> {
+        match self.loops.last() {
+            Some(&(header, _)) if header == index => return Some(Stmt::Continue),
+            Some(&(_, exit)) if exit == Some(index) => return Some(Stmt::Break),
+            _ => {}
+        }
+        if !self.emitted.contains(&index) {
+            return None;
+        }
+        let address = self.blocks[index].start;
+        self.labels.insert(address);
+        Some(Stmt::Goto(address))
+    }
+
+    //Whether a jump there is a break or continue
+    fn leaves_loop(&self, index: usize) -> bool {
+        matches!(self.loops.last(), Some(&(header, exit)) if header == index || exit == Some(index))
+    }
+
+    /* Everything from start on, until stop (where whatever is around goes on).
+     * in_loop is for a loop's body, which starts at its header and stops when it gets back there.
+     */
+    fn region(&mut self, start: usize, stop: Option<usize>, in_loop: bool) -> Vec<Stmt> {
+        let function = self.function;
+        let mut stmts = vec![];
+        let mut current = Some(start);
+        let mut first = in_loop;
+        while let Some(index) = current {
+            if !first {
+                if Some(index) == stop {
+                    break;
+                }
+                if let Some(jump) = self.arrive(index) {
+                    stmts.push(jump);
+                    break;
+                }
+                stmts.push(Stmt::Label(self.blocks[index].start));
+                if let Some(&exit) = self.headers.get(&index) {
+                    self.loops.push((index, exit));
+                    let body = self.region(index, Some(index), true);
+                    self.loops.pop();
+                    stmts.push(make_loop(body));
+                    current = exit;
+                    continue;
+                }
+            }
+            first = false;
+            self.emitted.insert(index);
+            stmts.extend(self.block(index));
+
+            let last = *self.blocks[index].addresses.last().unwrap();
+            let instr = &function.instrs[&last];
+            let fallthrough = self.index.get(&(last + instr.len)).copied();
+            current = match instr.kind {
+                Kind::Branch(_, _, Target::Addr(target)) => {
+                    let cond = self.cond(last);
+                    let (target, fallthrough) = (self.index[&target], fallthrough.unwrap());
+                    if self.leaves_loop(target) {
+                        stmts.extend(
+                            self.arrive(target)
+                                .map(|jump| Stmt::If(cond, vec![jump], vec![])),
+                        );
+                        Some(fallthrough)
+                    } else if self.leaves_loop(fallthrough) {
+                        stmts.extend(
+                            self.arrive(fallthrough)
+                                .map(|jump| Stmt::If(cond.negate(), vec![jump], vec![])),
+                        );
+                        Some(target)
+                    } else {
+                        //Both ways meet again where every path from here goes through first
+                        let join = self.joins[index];
+                        let then = self.region(fallthrough, join, false);
+                        let otherwise = self.region(target, join, false);
+                        match (then.is_empty(), otherwise.is_empty()) {
+                            (true, true) => {}
+                            (true, false) => stmts.push(Stmt::If(cond, otherwise, vec![])),
+                            _ => stmts.push(Stmt::If(cond.negate(), then, otherwise)),
+                        }
+                        join
+                    }
+                }
+                Kind::Branch(_, _, target) => {
+                    let cond = self.cond(last);
+                    let line = match target {
+                        Target::Unknown(target) => format!("goto *{};", self.name(target)),
+                        _ => self.return_statement(last),
+                    };
+                    stmts.push(Stmt::If(cond, vec![Stmt::Line(line)], vec![]));
+                    fallthrough
+                }
+                Kind::Goto(Target::Addr(target)) => Some(self.index[&target]),
+                Kind::Goto(_) | Kind::Halt | Kind::Crash => None,
+                _ => fallthrough,
+            };
+        }
+        stmts
+    }
+
+    fn write(&self, stmts: &[Stmt], depth: usize, out: &mut String) {
+        let indent = "    ".repeat(depth);
+        for stmt in stmts {
+            match stmt {
+                Stmt::Line(line) => out.push_str(&format!("{}{}\n", indent, line)),
+                Stmt::Label(address) if self.labels.contains(address) => {
+                    out.push_str(&format!("{}L{}:\n", indent, address))
+                }
+                Stmt::Label(_) => {}
+                Stmt::If(cond, then, otherwise) => {
+                    out.push_str(&format!("{}if ({}) {{\n", indent, cond));
+                    self.write(then, depth + 1, out);
+                    let mut otherwise = otherwise;
+                    //else if chains stay flat
+                    while let [Stmt::If(cond, then, rest)] = &otherwise[..] {
+                        out.push_str(&format!("{}}} else if ({}) {{\n", indent, cond));
+                        self.write(then, depth + 1, out);
+                        otherwise = rest;
+                    }
+                    if !otherwise.is_empty() {
+                        out.push_str(&format!("{}}} else {{\n", indent));
+                        self.write(otherwise, depth + 1, out);
+                    }
+                    out.push_str(&format!("{}}}\n", indent));
+                }
+                Stmt::While(cond, body) => {
+                    let cond = cond
+                        .as_ref()
+                        .map_or("1".to_string(), |cond| cond.to_string());
+                    out.push_str(&format!("{}while ({}) {{\n", indent, cond));
+                    self.write(body, depth + 1, out);
+                    out.push_str(&format!("{}}}\n", indent));
+                }
+                Stmt::DoWhile(body, cond) => {
+                    out.push_str(&format!("{}do {{\n", indent));
+                    self.write(body, depth + 1, out);
+                    out.push_str(&format!("{}}} while ({});\n", indent, cond));
+                }
+                Stmt::Goto(address) => out.push_str(&format!("{}goto L{};\n", indent, address)),
+                Stmt::Break => out.push_str(&format!("{}break;\n", indent)),
+                Stmt::Continue => out.push_str(&format!("{}continue;\n", indent)),
+            }
+        }
+    }
+
+    fn function(mut self) -> String {
+        let stmts = self.region(self.index[&self.function.entry], None, false);
+        let mut body = String::new();
+        self.write(&stmts, 1, &mut body);
+
+        let entry = self.function.entry;
+        let signature = if self.function.is_main() {
+            "void main()".to_string()
+        } else {
+            let params: Vec<String> = (1..=self.program.params.get(&entry).copied().unwrap_or(0))
+                .map(|slot| format!("long arg{}", slot))
+                .collect();
+            let returns = if self.program.returns.contains(&entry) {
+                "long"
+            } else {
+                "void"
+            };
+            format!("{} fn_{}({})", returns, entry, params.join(", "))
+        };
+        let locals: Vec<String> = self
+            .locals
+            .borrow()
+            .iter()
+            .map(|slot| format!("local{}", slot))
+            .collect();
+        let locals = if locals.is_empty() {
+            String::new()
+        } else {
+            format!("    long {};\n", locals.join(", "))
+        };
+        format!("{} {{\n{}{}}}\n", signature, locals, body)
+    }
+}
+
+/* The loops of a graph of blocks (a block jumping back to one it can only be reached through),
+ * with where each one goes on afterwards, and where the paths out of each block meet again (its immediate post-dominator).
+ */
+fn loops_and_joins(
+    successors: &[Vec<usize>],
+    entry: usize,
+) -> (BTreeMap<usize, Option<usize>>, Vec<Option<usize>>) {
+    let len = successors.len();
+    let mut predecessors = vec![vec![]; len];
+    for (from, targets) in successors.iter().enumerate() {
+        for &to in targets {
+            predecessors[to].push(from);
+        }
+    }
+    //Sets of blocks, as one bool per block, narrowed down until nothing changes
+    let fixpoint = |edges: &[Vec<usize>], roots: &[usize]| {
+        let mut sets = vec![vec![true; len]; len];
+        for &root in roots {
+            sets[root] = (0..len).map(|other| other == root).collect();
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in (0..len).filter(|block| !roots.contains(block)) {
+                let mut set = vec![true; len];
+                for &other in &edges[block] {
+                    for (into, &from) in set.iter_mut().zip(&sets[other]) {
+                        *into &= from;
+                    }
+                }
+                set[block] = true;
+                if set != sets[block] {
+                    sets[block] = set;
+                    changed = true;
+                }
+            }
+        }
+        sets
+    };
+    let dominators = fixpoint(&predecessors, &[entry]);
+    let exits: Vec<usize> = (0..len)
+        .filter(|&block| successors[block].is_empty())
+        .collect();
+    let post_dominators = fixpoint(successors, &exits);
+
+    let mut headers = BTreeMap::new();
+    for header in 0..len {
+        let latches: Vec<usize> = predecessors[header]
+            .iter()
+            .copied()
+            .filter(|&latch| dominators[latch][header])
+            .collect();
+        if latches.is_empty() {
+            continue;
+        }
+        //Everything that gets back to a latch without going through the header
+        let mut body = BTreeSet::new();
+        body.insert(header);
+        let mut todo = latches;
+        while let Some(block) = todo.pop() {
+            if body.insert(block) {
+                todo.extend(predecessors[block].iter().copied());
+            }
+        }
+        let exit = body
+            .iter()
+            .flat_map(|&block| successors[block].iter().copied())
+            .filter(|block| !body.contains(block))
+            .min();
+        headers.insert(header, exit);
+    }
+
+    //Blocks that never get to an exit (stuck in a loop) don't have one
+    let mut reach_exit = vec![false; len];
+    let mut todo = exits;
+    while let Some(block) = todo.pop() {
+        if !reach_exit[block] {
+            reach_exit[block] = true;
+            todo.extend(predecessors[block].iter().copied());
+        }
+    }
+    let count = |block: usize| post_dominators[block].iter().filter(|&&is| is).count();
+    let joins = (0..len)
+        .map(|block| {
+            if !reach_exit[block] {
+                return None;
+            }
+            (0..len)
+                .filter(|&other| other != block && post_dominators[block][other])
+                .find(|&other| count(other) + 1 == count(block))
+        })
+        .collect();
+    (headers, joins)
+}
+
+//The arguments written right before a call, by slot
+fn call_args(function: &Function, call: usize, leaders: &BTreeSet<usize>) -> BTreeMap<i64, usize> {
+    let delta = function.instrs[&call].delta;
+    let mut args = BTreeMap::new();
+    let mut address = call;
+    while !leaders.contains(&address) {
+        address = match function.previous(address) {
+            Some(previous) => previous,
+            None => break,
+        };
+        match function.instrs[&address].kind.dest() {
+            Some(Operand::Slot(slot)) if slot >= delta && !args.contains_key(&(slot - delta)) => {
+                args.insert(slot - delta, address);
+            }
+            _ => break,
+        }
+    }
+    args
+}
+
+//Where jumps land, they can't be merged into what comes before them
+fn jump_targets(function: &Function) -> BTreeSet<usize> {
+    let mut targets: BTreeSet<usize> = function
+        .instrs
+        .iter()
+        .filter(|(_, instr)| instr.kind.ends_block())
+        .flat_map(|(&address, instr)| instr.kind.successors(address, instr.len))
+        .collect();
+    targets.insert(function.entry);
+    targets
+}
+
+pub fn decompile(program: &[i64]) -> Result<String, String> {
+    let functions = walk_all(program)?;
+    let mut args = HashMap::new();
+    let mut results = HashMap::new();
+    let mut params = HashMap::new();
+    let mut returns = HashSet::new();
+    let mut memory_reads = HashMap::new();
+    let mut written = HashSet::new();
+    for function in functions.values() {
+        let leaders = jump_targets(function);
+        let mut consumed = HashSet::new();
+        for (call, callee) in function.calls() {
+            let written = call_args(function, call, &leaders);
+            let last = written.keys().copied().max().unwrap_or(0);
+            let known = params.entry(callee).or_insert(0);
+            *known = last.max(*known);
+            consumed.extend(written.values().copied());
+            args.insert(call, written);
+        }
+        //A result is read from the first argument's slot, by what comes right after the call
+        for (call, callee) in function.calls() {
+            let instr = &function.instrs[&call];
+            let after = call + instr.len;
+            let result = Operand::Slot(instr.delta + 1);
+            match function.instrs.get(&after) {
+                Some(user)
+                    if !leaders.contains(&after)
+                        && !consumed.contains(&after)
+                        && user.delta == instr.delta
+                        && user.kind.reads().contains(&result)
+                        && matches!(
+                            user.kind,
+                            Kind::Add(..)
+                                | Kind::Mul(..)
+                                | Kind::LessThan(..)
+                                | Kind::Equals(..)
+                                | Kind::Out(_)
+                        ) =>
+                {
+                    results.insert(call, after);
+                    returns.insert(callee);
+                }
+                _ => {}
+            }
+        }
+        for address in function.memory_reads() {
+            *memory_reads.entry(address).or_insert(0) += 1;
+        }
+        written.extend(function.memory_writes());
+    }
+    let program = Program {
+        functions,
+        args,
+        results,
+        params,
+        returns,
+        memory_reads,
+        written,
+    };
+    let functions: Vec<String> = program
+        .functions
+        .values()
+        .map(|function| Writer::new(&program, function).function())
+        .collect();
+    Ok(functions.join("\n"))
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    fn assembled(lines: &[&str]) -> Vec<i64> {
+        lines
+            .iter()
+            .flat_map(|line| assemble(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn function_with_a_loop() {
+        //Reads n, and prints 1 + 2 + ... + n, computed by a function
+        let program = assembled(&[
+            "ARB 100",
+            "IN [60]",
+            "ADD 15, 0, [rb+0]",
+            "ADD [60], 0, [rb+1]",
+            "JT 1, 20",
+            "OUT [rb+1]",
+            "HALT",
+            "DATA 0",
+            "DATA 0",
+            //20: fn_20(n), with its sum in slot 2 and a comparison in slot 3
+            "ARB 3",
+            "ADD 0, 0, [rb-1]",
+            "LT 0, [rb-2], [rb+0]",
+            "JF [rb+0], 44",
+            "ADD [rb-1], [rb-2], [rb-1]",
+            "ADD [rb-2], -1, [rb-2]",
+            "JT 1, 26",
+            "ADD [rb-1], 0, [rb-2]",
+            "ARB -3",
+            "JT 1, [rb+0]",
+        ]);
+        assert_eq!(program.len(), 53);
+        assert_eq!(execute(&mut program.clone(), &vec![10]), vec![55]);
+        assert_eq!(
+            decompile(&program).unwrap(),
+            "void main() {
+    mem[60] = input();
+    output(fn_20(mem[60]));
+    halt();
+}
+
+long fn_20(long arg1) {
+    long local2;
+    local2 = 0;
+    while (0 < arg1) {
+        local2 += arg1;
+        arg1 -= 1;
+    }
+    return local2;
+}
+"
+        );
+    }
+
+    #[test]
+    fn if_else() {
+        //Day 5: prints 999 below 8, 1000 for 8, 1001 above
+        let program = vec![
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99,
+        ];
+        assert_eq!(
+            decompile(&program).unwrap(),
+            "void main() {
+    mem[21] = input();
+    mem[20] = mem[21] == 8;
+    if (mem[20] == 0) {
+        mem[20] = 8 < mem[21];
+        if (mem[20] != 0) {
+            mem[20] = 1001;
+            output(mem[20]);
+        } else {
+            output(999);
+        }
+    } else {
+        mem[20] = mem[21] * 125;
+        output(mem[20]);
+    }
+    halt();
+}
+"
+        );
+    }
+
+    #[test]
+    fn several_call_sites() {
+        //Reads a and b, and prints a² + b², squaring through the same function twice
+        let program = assembled(&[
+            "ARB 100",
+            "IN [90]",
+            "IN [91]",
+            "ADD 17, 0, [rb+0]",
+            "ADD [90], 0, [rb+1]",
+            "JT 1, 39",
+            "ADD [rb+1], 0, [92]",
+            "ADD 32, 0, [rb+0]",
+            "ADD [91], 0, [rb+1]",
+            "JT 1, 39",
+            "ADD [rb+1], [92], [92]",
+            "OUT [92]",
+            "HALT",
+            //39: fn_39(x)
+            "ARB 2",
+            "MUL [rb-1], [rb-1], [rb-1]",
+            "ARB -2",
+            "JT 1, [rb+0]",
+        ]);
+        assert_eq!(execute(&mut program.clone(), &vec![3, 4]), vec![25]);
+        assert_eq!(
+            decompile(&program).unwrap(),
+            "void main() {
+    mem[90] = input();
+    mem[91] = input();
+    mem[92] = fn_39(mem[90]);
+    mem[92] = fn_39(mem[91]) + mem[92];
+    output(mem[92]);
+    halt();
+}
+
+long fn_39(long arg1) {
+    return arg1 * arg1;
+}
+"
+        );
+    }
+
+    #[test]
+    fn recursion() {
+        //Reads n, and prints n!
+        let program = assembled(&[
+            "ARB 100",
+            "IN [90]",
+            "ADD 15, 0, [rb+0]",
+            "ADD [90], 0, [rb+1]",
+            "JT 1, 18",
+            "OUT [rb+1]",
+            "HALT",
+            //18: fn_18(n), returning early below 2, and calling itself with n - 1 otherwise
+            "ARB 3",
+            "LT [rb-2], 2, [rb-1]",
+            "JF [rb-1], 36",
+            "ADD 1, 0, [rb-2]",
+            "ARB -3",
+            "JT 1, [rb+0]",
+            "ADD 47, 0, [rb+0]",
+            "ADD [rb-2], -1, [rb+1]",
+            "JT 1, 18",
+            "MUL [rb-2], [rb+1], [rb-2]",
+            "ARB -3",
+            "JT 1, [rb+0]",
+        ]);
+        assert_eq!(execute(&mut program.clone(), &vec![5]), vec![120]);
+        assert_eq!(
+            decompile(&program).unwrap(),
+            "void main() {
+    mem[90] = input();
+    output(fn_18(mem[90]));
+    halt();
+}
+
+long fn_18(long arg1) {
+    if (arg1 < 2) {
+        return 1;
+    } else {
+        arg1 = arg1 * fn_18(arg1 - 1);
+        return arg1;
+    }
+}
+"
+        );
+    }
+
+    #[test]
+    fn nested_loops() {
+        //Reads n, and prints i * j for every j < i < n
+        let program = assembled(&[
+            "IN [100]",
+            "ADD 0, 0, [101]",
+            //6: the outer loop
+            "LT [101], [100], [103]",
+            "JF [103], 44",
+            "ADD 0, 0, [102]",
+            //17: the inner loop
+            "LT [102], [101], [103]",
+            "JF [103], 37",
+            "MUL [101], [102], [104]",
+            "OUT [104]",
+            "ADD [102], 1, [102]",
+            "JT 1, 17",
+            "ADD [101], 1, [101]",
+            "JT 1, 6",
+            "HALT",
+        ]);
+        assert_eq!(execute(&mut program.clone(), &vec![3]), vec![0, 0, 2]);
+        assert_eq!(
+            decompile(&program).unwrap(),
+            "void main() {
+    mem[100] = input();
+    mem[101] = 0;
+    while (1) {
+        mem[103] = mem[101] < mem[100];
+        if (mem[103] == 0) {
+            break;
+        }
+        mem[102] = 0;
+        while (1) {
+            mem[103] = mem[102] < mem[101];
+            if (mem[103] == 0) {
+                break;
+            }
+            mem[104] = mem[101] * mem[102];
+            output(mem[104]);
+            mem[102] += 1;
+        }
+        mem[101] += 1;
+    }
+    halt();
+}
+"
+        );
+    }
+
+    #[test]
+    fn break_and_continue() {
+        //Echoes its input until a 0, skipping negative numbers
+        let program = assembled(&[
+            "IN [100]",
+            "EQ [100], 0, [101]",
+            "JT [101], 21",
+            "LT [100], 0, [101]",
+            "JT [101], 0",
+            "OUT [100]",
+            "JT 1, 0",
+            "HALT",
+        ]);
+        assert_eq!(
+            execute(&mut program.clone(), &vec![3, -1, 4, 0]),
+            vec![3, 4]
+        );
+        assert_eq!(
+            decompile(&program).unwrap(),
+            "void main() {
+    while (1) {
+        mem[100] = input();
+        mem[101] = mem[100] == 0;
+        if (mem[101] != 0) {
+            break;
+        }
+        mem[101] = mem[100] < 0;
+        if (mem[101] != 0) {
+            continue;
+        }
+        output(mem[100]);
+    }
+    halt();
+}
+"
+        );
+    }
+
+    #[test]
+    fn goto_fallback() {
+        //A loop that can be entered in two places, which no while can say
+        let program = assembled(&[
+            "IN [100]",
+            "JT [100], 7",
+            //5: the first way in
+            "OUT 1",
+            //7: the second one
+            "OUT 2",
+            "IN [101]",
+            "JT [101], 5",
+            "HALT",
+        ]);
+        assert_eq!(
+            execute(&mut program.clone(), &vec![0, 1, 0]),
+            vec![1, 2, 1, 2]
+        );
+        assert_eq!(
+            decompile(&program).unwrap(),
+            "void main() {
+    mem[100] = input();
+    if (mem[100] == 0) {
+        L5:
+        output(1);
+    }
+    output(2);
+    mem[101] = input();
+    if (mem[101] != 0) {
+        goto L5;
+    }
+    halt();
+}
+"
+        );
+    }
+
+    #[test]
+    fn refused() {
+        assert!(decompile(&[109, 1, 9, 0, 99])
+            .unwrap_err()
+            .contains("computed"));
+        //The relative base is 1 or 2 at 12, depending on the input
+        let error = decompile(&[3, 20, 1005, 20, 10, 109, 1, 1105, 1, 12, 109, 2, 99]).unwrap_err();
+        assert!(error.contains("depends on how it's reached"), "{}", error);
+    }
+}
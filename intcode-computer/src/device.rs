@@ -0,0 +1,243 @@
+use crate::Memory;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::ops::Range;
+
+/* Something plugged into a range of addresses: reading or writing there goes to the device instead of memory.
+ * Offsets are relative to the start of the range. Reads can't change anything through &self,
+ * so devices that react to being read (keyboards, clocks) keep that state in a Cell.
+ */
+pub trait Device: Any {
+    //How many addresses the device takes
+    fn size(&self) -> usize;
+    fn read(&self, offset: usize) -> i64;
+    fn write(&mut self, offset: usize, value: i64);
+}
+
+/* Memory with devices mapped over parts of it.
+ * It's still Memory, so execute() runs on it as usual, and Add/Mul/In writing to a device address talks to the device.
+ * Only execute() and execute_at() though: Vm keeps its memory in a Vec<i64> (custom opcodes, snapshots and core dumps
+ * all count on it), so devices can't be attached to a pausable VM.
+ */
+pub struct Bus<M: Memory> {
+    memory: M,
+    devices: Vec<(Range<usize>, Box<dyn Device>)>,
+}
+impl<M: Memory> Bus<M> {
+    pub fn new(memory: M) -> Self {
+        Bus {
+            memory,
+            devices: vec![],
+        }
+    }
+
+    //Maps the device from address start on, panics if it overlaps another device
+    pub fn attach<D: Device>(&mut self, start: usize, device: D) {
+        let range = start..start + device.size();
+        if let Some((other, _)) = self
+            .devices
+            .iter()
+            .find(|(other, _)| other.start < range.end && range.start < other.end)
+        {
+            panic!(
+                "A device at {:?} overlaps another one at {:?}",
+                range, other
+            );
+        }
+        self.devices.push((range, Box::new(device)));
+    }
+
+    //The device mapped at start, if it's a D
+    pub fn device<D: Device>(&self, start: usize) -> Option<&D> {
+        self.devices
+            .iter()
+            .find(|(range, _)| range.start == start)
+            .and_then(|(_, device)| (&**device as &dyn Any).downcast_ref())
+    }
+
+    //What's under the devices
+    pub fn memory(&self) -> &M {
+        &self.memory
+    }
+
+    fn find(&self, address: usize) -> Option<usize> {
+        self.devices
+            .iter()
+            .position(|(range, _)| range.contains(&address))
+    }
+}
+impl<M: Memory> Memory for Bus<M> {
+    //Device addresses past the end of memory still count, or programs couldn't reach them
+    fn len(&self) -> usize {
+        self.devices
+            .iter()
+            .map(|(range, _)| range.end)
+            .fold(self.memory.len(), usize::max)
+    }
+    fn read(&self, address: usize) -> i64 {
+        match self.find(address) {
+            Some(index) => {
+                let (range, device) = &self.devices[index];
+                device.read(address - range.start)
+            }
+            None => self.memory.read(address),
+        }
+    }
+    fn write(&mut self, address: usize, value: i64) {
+        match self.find(address) {
+            Some(index) => {
+                let (range, device) = &mut self.devices[index];
+                device.write(address - range.start, value)
+            }
+            None => self.memory.write(address, value),
+        }
+    }
+}
+
+//A width * height screen, one address per pixel, row after row. Reading gives back the pixel
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<i64>,
+}
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        assert!(
+            width > 0,
+            "A framebuffer needs to be at least one pixel wide"
+        );
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> i64 {
+        self.pixels[y * self.width + x]
+    }
+
+    //One character per pixel, picked by the palette
+    pub fn render<F: Fn(i64) -> char>(&self, palette: F) -> String {
+        let mut picture = String::new();
+        for row in self.pixels.chunks(self.width) {
+            picture.extend(row.iter().map(|&pixel| palette(pixel)));
+            picture.push('\n');
+        }
+        picture
+    }
+}
+impl Device for Framebuffer {
+    fn size(&self) -> usize {
+        self.pixels.len()
+    }
+    fn read(&self, offset: usize) -> i64 {
+        self.pixels[offset]
+    }
+    fn write(&mut self, offset: usize, value: i64) {
+        self.pixels[offset] = value;
+    }
+}
+
+/* A tick counter: every read gives the next tick, so a program can tell how many times it looked.
+ * Writing sets the counter. Real time would make runs impossible to reproduce.
+ */
+#[derive(Default)]
+pub struct Clock {
+    ticks: Cell<i64>,
+}
+impl Clock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ticks(&self) -> i64 {
+        self.ticks.get()
+    }
+}
+impl Device for Clock {
+    fn size(&self) -> usize {
+        1
+    }
+    fn read(&self, _offset: usize) -> i64 {
+        let tick = self.ticks.get();
+        self.ticks.set(tick + 1);
+        tick
+    }
+    fn write(&mut self, _offset: usize, value: i64) {
+        self.ticks.set(value);
+    }
+}
+
+//Keys pressed ahead of time: every read takes the next one, or 0 if nothing was pressed. Writes are ignored
+#[derive(Default)]
+pub struct Keyboard {
+    keys: RefCell<VecDeque<i64>>,
+}
+impl Keyboard {
+    pub fn new(keys: &[i64]) -> Self {
+        Keyboard {
+            keys: RefCell::new(keys.iter().copied().collect()),
+        }
+    }
+}
+impl Device for Keyboard {
+    fn size(&self) -> usize {
+        1
+    }
+    fn read(&self, _offset: usize) -> i64 {
+        self.keys.borrow_mut().pop_front().unwrap_or(0)
+    }
+    fn write(&mut self, _offset: usize, _value: i64) {}
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn framebuffer() {
+        //Copies a key to pixel (1, 0), then an input to pixel (0, 1), then clears (1, 0)
+        let mut bus = Bus::new(vec![1001, 200, 0, 101, 3, 102, 1101, 0, 0, 101, 99]);
+        bus.attach(100, Framebuffer::new(2, 2));
+        bus.attach(200, Keyboard::new(&[35]));
+        execute(&mut bus, &vec![35]);
+        let screen: &Framebuffer = bus.device(100).unwrap();
+        assert_eq!(screen.pixel(0, 1), 35);
+        assert_eq!(
+            screen.render(|p| if p == 0 { '.' } else { p as u8 as char }),
+            "..\n#.\n"
+        );
+        //Nothing got written to the memory under the devices
+        assert_eq!(bus.memory().len(), 11);
+        assert!(bus.device::<Clock>(100).is_none());
+    }
+
+    #[test]
+    fn clock() {
+        //Reads the clock twice and outputs how many ticks went by
+        let mut bus = Bus::new(vec![
+            1001, 50, 0, 20, 1002, 20, -1, 20, 1, 50, 20, 21, 4, 21, 99,
+        ]);
+        bus.attach(50, Clock::new());
+        assert_eq!(execute(&mut bus, &vec![]), vec![1]);
+        assert_eq!(bus.device::<Clock>(50).unwrap().ticks(), 2);
+        bus.write(50, 100);
+        assert_eq!(bus.read(50), 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one pixel wide")]
+    fn empty_framebuffer() {
+        Framebuffer::new(0, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "overlaps")]
+    fn overlap() {
+        let mut bus = Bus::new(vec![]);
+        bus.attach(10, Framebuffer::new(4, 4));
+        bus.attach(25, Clock::new());
+    }
+}
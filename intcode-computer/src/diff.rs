@@ -0,0 +1,256 @@
+use crate::disasm::{disassemble, DisasmLine};
+use std::collections::HashMap;
+
+//One line of a diff between two programs
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiffLine {
+    //The same instruction in both, wherever it lives in each
+    Same(DisasmLine, DisasmLine),
+    //Only in the first program
+    Removed(DisasmLine),
+    //Only in the second program
+    Added(DisasmLine),
+}
+
+/* Lines up two programs instruction by instruction, with a longest common subsequence of their listings.
+ * Comparing raw words, a single inserted instruction shifts everything after it and the whole rest looks different;
+ * comparing decoded instructions, it shows up as one added line.
+ * Memory dumps can be big, so this is Myers' algorithm in linear space: it takes time in proportion to
+ * the size of the programs times the number of lines that differ, and memory in proportion to the size only.
+ */
+pub fn diff(a: &[i64], b: &[i64]) -> Vec<DiffLine> {
+    let (a, b) = (disassemble(a), disassemble(b));
+    //Lines are compared a lot, as numbers it's faster
+    let mut ids = HashMap::new();
+    let mut id = |line: &DisasmLine| {
+        let next = ids.len();
+        *ids.entry(line.text.clone()).or_insert(next)
+    };
+    let a_ids: Vec<usize> = a.iter().map(&mut id).collect();
+    let b_ids: Vec<usize> = b.iter().map(&mut id).collect();
+    let mut common = vec![];
+    common_lines(&a_ids, &b_ids, (0, 0), &mut common);
+
+    let mut lines = vec![];
+    let (mut i, mut j) = (0, 0);
+    for (next_i, next_j) in common.into_iter().chain(Some((a.len(), b.len()))) {
+        lines.extend(a[i..next_i].iter().cloned().map(DiffLine::Removed));
+        lines.extend(b[j..next_j].iter().cloned().map(DiffLine::Added));
+        if next_i < a.len() {
+            lines.push(DiffLine::Same(a[next_i].clone(), b[next_j].clone()));
+        }
+        i = next_i + 1;
+        j = next_j + 1;
+    }
+    lines
+}
+
+//Adds the indexes of the lines a and b have in common to common, in order (start is where a and b are in the whole listings)
+fn common_lines(a: &[usize], b: &[usize], start: (usize, usize), common: &mut Vec<(usize, usize)>) {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let (a_rest, b_rest) = (&a[prefix..], &b[prefix..]);
+    let suffix = a_rest
+        .iter()
+        .rev()
+        .zip(b_rest.iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a_mid, b_mid) = (
+        &a_rest[..a_rest.len() - suffix],
+        &b_rest[..b_rest.len() - suffix],
+    );
+    common.extend((0..prefix).map(|k| (start.0 + k, start.1 + k)));
+    //Once both ends are trimmed, each half has at least one difference, so both are smaller than the whole
+    if !a_mid.is_empty() && !b_mid.is_empty() {
+        let (x, y, u, v) = middle_snake(a_mid, b_mid);
+        let (i, j) = (start.0 + prefix, start.1 + prefix);
+        common_lines(&a_mid[..x], &b_mid[..y], (i, j), common);
+        common.extend((0..u - x).map(|k| (i + x + k, j + y + k)));
+        common_lines(&a_mid[u..], &b_mid[v..], (i + u, j + v), common);
+    }
+    let (i, j) = (start.0 + a.len() - suffix, start.1 + b.len() - suffix);
+    common.extend((0..suffix).map(|k| (i + k, j + k)));
+}
+
+/* The snake (run of common lines, maybe empty) in the middle of a shortest edit script from a to b,
+ * as (x, y, u, v): it goes from a[x], b[y] to a[u], b[v].
+ * Paths are followed from both ends at once, one more difference at a time, until they meet.
+ * forward[k] is how far in a the furthest path from the start got on diagonal k (x - y == k),
+ * backward[k] how far from the end the furthest path from the end got on diagonal k (counted from the end as well).
+ */
+fn middle_snake(a: &[usize], b: &[usize]) -> (usize, usize, usize, usize) {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m + 1) / 2;
+    let offset = max + 1;
+    let mut forward = vec![0; 2 * max as usize + 3];
+    let mut backward = vec![0; 2 * max as usize + 3];
+    let at = |k: isize| (k + offset) as usize;
+    let delta = n - m;
+    let odd = delta % 2 != 0;
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                forward[at(k + 1)]
+            } else {
+                forward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let (x0, y0) = (x, y);
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[at(k)] = x;
+            let c = delta - k;
+            if odd && -d < c && c < d && x + backward[at(c)] >= n {
+                return (x0 as usize, y0 as usize, x as usize, y as usize);
+            }
+        }
+        for c in (-d..=d).step_by(2) {
+            let mut x = if c == -d || (c != d && backward[at(c - 1)] < backward[at(c + 1)]) {
+                backward[at(c + 1)]
+            } else {
+                backward[at(c - 1)] + 1
+            };
+            let mut y = x - c;
+            let (x0, y0) = (x, y);
+            while x < n && y < m && a[(n - x - 1) as usize] == b[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[at(c)] = x;
+            let k = delta - c;
+            if !odd && -d <= k && k <= d && forward[at(k)] + x >= n {
+                return (
+                    (n - x) as usize,
+                    (m - y) as usize,
+                    (n - x0) as usize,
+                    (m - y0) as usize,
+                );
+            }
+        }
+    }
+    unreachable!("The paths from both ends always meet")
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    fn texts(lines: &[DiffLine]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| match line {
+                DiffLine::Same(a, b) => format!("{} {} {}", a.address, b.address, a.text),
+                DiffLine::Removed(a) => format!("- {} {}", a.address, a.text),
+                DiffLine::Added(b) => format!("+ {} {}", b.address, b.text),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn inserted_instruction() {
+        let a = [3, 0, 4, 0, 99];
+        let b = [3, 0, 1001, 0, 1, 0, 4, 0, 99];
+        assert_eq!(
+            texts(&diff(&a, &b)),
+            vec![
+                "0 0 IN [0]",
+                "+ 2 ADD [0], 1, [0]",
+                "2 6 OUT [0]",
+                "4 8 HALT"
+            ]
+        );
+    }
+
+    #[test]
+    fn changed_instruction() {
+        let a = [1101, 1, 2, 0, 4, 0, 99];
+        let b = [1102, 1, 2, 0, 4, 0, 99];
+        assert_eq!(
+            texts(&diff(&a, &b)),
+            vec![
+                "- 0 ADD 1, 2, [0]",
+                "+ 0 MUL 1, 2, [0]",
+                "4 4 OUT [0]",
+                "6 6 HALT"
+            ]
+        );
+        assert!(diff(&a, &a)
+            .iter()
+            .all(|line| matches!(line, DiffLine::Same(_, _))));
+    }
+
+    //Programs of OUT instructions, each printing one of a few values
+    fn outputs(values: &[i64]) -> Vec<i64> {
+        values.iter().flat_map(|&value| vec![104, value]).collect()
+    }
+
+    #[test]
+    fn shortest() {
+        //Against the plain quadratic longest common subsequence, on small pseudo-random programs
+        let mut seed = 12345u64;
+        let mut next = |limit: u64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((seed >> 33) % limit) as i64
+        };
+        for _ in 0..300 {
+            let a: Vec<i64> = (0..next(12)).map(|_| next(3)).collect();
+            let b: Vec<i64> = (0..next(12)).map(|_| next(3)).collect();
+            let mut lengths = vec![vec![0; b.len() + 1]; a.len() + 1];
+            for i in (0..a.len()).rev() {
+                for j in (0..b.len()).rev() {
+                    lengths[i][j] = if a[i] == b[j] {
+                        lengths[i + 1][j + 1] + 1
+                    } else {
+                        lengths[i + 1][j].max(lengths[i][j + 1])
+                    };
+                }
+            }
+            let lines = diff(&outputs(&a), &outputs(&b));
+            let same = lines
+                .iter()
+                .filter(|line| matches!(line, DiffLine::Same(a, b) if a.text == b.text))
+                .count();
+            assert_eq!(same, lengths[0][0], "{:?} {:?}", a, b);
+            //Both programs can be read back from the diff
+            let side = |first: bool| -> Vec<i64> {
+                lines
+                    .iter()
+                    .filter_map(|line| match (line, first) {
+                        (DiffLine::Same(a, _), true) | (DiffLine::Removed(a), true) => Some(a),
+                        (DiffLine::Same(_, b), false) | (DiffLine::Added(b), false) => Some(b),
+                        _ => None,
+                    })
+                    .map(|line| line.address as i64 / 2)
+                    .collect()
+            };
+            assert_eq!(side(true), (0..a.len() as i64).collect::<Vec<_>>());
+            assert_eq!(side(false), (0..b.len() as i64).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn big_dumps() {
+        //500 000 lines each: a full table of their common lengths would take a terabyte
+        let a: Vec<i64> = (0..500_000).map(|i| i % 7).collect();
+        let mut b = a.clone();
+        b.remove(1000);
+        b.insert(400_000, 9);
+        b[250_000] = 8;
+        let lines = diff(&outputs(&a), &outputs(&b));
+        let changed: Vec<String> = lines
+            .iter()
+            .filter_map(|line| match line {
+                DiffLine::Same(_, _) => None,
+                DiffLine::Removed(a) => Some(format!("- {}", a.text)),
+                DiffLine::Added(b) => Some(format!("+ {}", b.text)),
+            })
+            .collect();
+        assert_eq!(changed.len(), 4, "{:?}", changed);
+        assert!(changed.contains(&"+ OUT 9".to_string()));
+        assert!(changed.contains(&"+ OUT 8".to_string()));
+    }
+}
@@ -0,0 +1,138 @@
+/* Runs the same program through every way we have of executing intcode, and complains when they disagree.
+ * Used by the property tests (tests/differential.rs) and the fuzz target (fuzz/), so it has to be public.
+ * Errors are still panics in the computer, so they are caught and compared by message.
+ */
+use crate::{execute, panic_message, Coverage, State, Vm, VmBuilder};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+//Everything a run leaves behind
+#[derive(Debug, PartialEq)]
+pub struct Outcome {
+    pub memory: Vec<i64>,
+    pub output: Vec<i64>,
+    //The panic message, if the program crashed
+    pub error: Option<String>,
+}
+
+//The VM pauses when it runs out of input, everything else panics: this is the message they use
+const MISSING_INPUT: &str = "Input instruction cannot be executed without an input!";
+
+//None if the program was still running after max_steps instructions (it probably loops forever)
+pub fn run_vm(program: &[i64], input: &[i64], max_steps: usize) -> Option<Outcome> {
+    let mut vm: Vm = VmBuilder::new(program).input(input).build();
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        for _ in 0..max_steps {
+            match vm.step() {
+                State::Running => {}
+                state => return Some(state),
+            }
+        }
+        None
+    }));
+    let error = match result {
+        Ok(None) => return None,
+        Ok(Some(State::WaitingForInput)) => Some(MISSING_INPUT.to_string()),
+        Ok(Some(_)) => None,
+        Err(payload) => Some(panic_message(&*payload)),
+    };
+    Some(Outcome {
+        memory: vm.memory().to_vec(),
+        output: vm.take_output(),
+        error,
+    })
+}
+
+//execute() has no step limit, only call this on programs that are known to stop
+pub fn run_execute(program: &[i64], input: &[i64]) -> Outcome {
+    let mut memory = program.to_vec();
+    let result = catch_unwind(AssertUnwindSafe(|| execute(&mut memory, &input.to_vec())));
+    //The output vector is lost when execute() panics, so it can only be compared on success
+    match result {
+        Ok(output) => Outcome {
+            memory,
+            output,
+            error: None,
+        },
+        Err(payload) => Outcome {
+            memory,
+            output: vec![],
+            error: Some(panic_message(&*payload)),
+        },
+    }
+}
+
+//Same as run_execute, through the coverage collector
+pub fn run_coverage(program: &[i64], input: &[i64]) -> Outcome {
+    let mut memory = program.to_vec();
+    let mut coverage = Coverage::new(program);
+    let result = catch_unwind(AssertUnwindSafe(|| coverage.execute(&mut memory, input)));
+    match result {
+        Ok(output) => Outcome {
+            memory,
+            output,
+            error: None,
+        },
+        Err(payload) => Outcome {
+            memory,
+            output: vec![],
+            error: Some(panic_message(&*payload)),
+        },
+    }
+}
+
+/* Ok if every backend ended up with the same memory, output and error.
+ * The VM goes first with a step limit: programs that don't stop in time are skipped, since execute() would never return.
+ */
+pub fn compare(program: &[i64], input: &[i64], max_steps: usize) -> Result<(), String> {
+    let reference = match run_vm(program, input, max_steps) {
+        Some(outcome) => outcome,
+        None => return Ok(()),
+    };
+    for (name, mut outcome) in [
+        ("execute", run_execute(program, input)),
+        ("coverage", run_coverage(program, input)),
+    ] {
+        let mut expected = Outcome {
+            memory: reference.memory.clone(),
+            output: reference.output.clone(),
+            error: reference.error.clone(),
+        };
+        if outcome.error.is_some() {
+            //No output to compare after a panic (see run_execute)
+            expected.output.clear();
+            outcome.output.clear();
+        }
+        if outcome != expected {
+            return Err(format!(
+                "{} disagrees with the VM on {:?} with input {:?}:\n  vm: {:?}\n  {}: {:?}",
+                name, program, input, expected, name, outcome
+            ));
+        }
+    }
+    Ok(())
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::differential::*;
+    #[test]
+    fn agreement() {
+        assert_eq!(compare(&[3, 0, 4, 0, 99], &[3], 100), Ok(()));
+        //Errors have to match too
+        assert_eq!(compare(&[3, 0, 4, 0, 99], &[], 100), Ok(()));
+        assert_eq!(compare(&[1, 0, 0], &[], 100), Ok(()));
+        //Infinite loops are skipped
+        assert_eq!(compare(&[1105, 1, 0], &[], 100), Ok(()));
+    }
+
+    #[test]
+    fn outcomes() {
+        let outcome = run_vm(&[3, 0, 99], &[], 100).unwrap();
+        assert_eq!(outcome.error.as_deref(), Some(MISSING_INPUT));
+        assert!(run_vm(&[1105, 1, 0], &[], 100).is_none());
+        let outcome = run_execute(&[1, 0, 0, 0, 99], &[]);
+        assert_eq!(outcome.memory, vec![2, 0, 0, 0, 99]);
+        assert_eq!(outcome.error, None);
+    }
+}
@@ -0,0 +1,180 @@
+//One line of a disassembly listing: where it starts, how many words it covers, and something a human can read
+#[derive(Clone, Debug, PartialEq)]
+pub struct DisasmLine {
+    pub address: usize,
+    pub len: usize,
+    pub text: String,
+    //Raw words that didn't decode to an instruction
+    pub is_data: bool,
+}
+
+//Mnemonic and parameter count for every opcode the computer knows about
+pub(crate) fn opcode_info(opcode: i64) -> Option<(&'static str, usize)> {
+    match opcode {
+        1 => Some(("ADD", 3)),
+        2 => Some(("MUL", 3)),
+        3 => Some(("IN", 1)),
+        4 => Some(("OUT", 1)),
+        5 => Some(("JT", 2)),
+        6 => Some(("JF", 2)),
+        7 => Some(("LT", 3)),
+        8 => Some(("EQ", 3)),
+        9 => Some(("ARB", 1)),
+        99 => Some(("HALT", 0)),
+        _ => None,
+    }
+}
+
+//Same idea as Instruction::new, except nothing panics: anything that isn't a valid instruction gives None
+pub(crate) fn decode(intcode: &[i64], address: usize) -> Option<DisasmLine> {
+    let code = intcode[address];
+    if code < 0 {
+        return None;
+    }
+    let (mnemonic, arity) = opcode_info(code % 100)?;
+    //The instruction might be cut short by the end of the program
+    if address + arity >= intcode.len() {
+        return None;
+    }
+    let mut operands = vec![];
+    for offset in 0..arity {
+        let value = intcode[address + 1 + offset];
+        //Position parameters are shown as [address], immediate ones as plain values, relative ones as [rb+offset]
+        match (code as u64 / 10u64.pow(2 + offset as u32)) % 10 {
+            0 => operands.push(format!("[{}]", value)),
+            1 => operands.push(format!("{}", value)),
+            2 => operands.push(format!("[rb{:+}]", value)),
+            _ => return None,
+        }
+    }
+    Some(DisasmLine {
+        address,
+        len: 1 + arity,
+        text: if operands.is_empty() {
+            mnemonic.to_string()
+        } else {
+            format!("{} {}", mnemonic, operands.join(", "))
+        },
+        is_data: false,
+    })
+}
+
+/* Walks through the program from the start, one instruction after the other.
+ * Words that can't be decoded (variables stored after the code, usually) show up as DATA.
+ * This is a linear sweep, so code that is only reached by jumping into the middle of something else will look weird!
+ */
+pub fn disassemble(intcode: &[i64]) -> Vec<DisasmLine> {
+    let mut lines = vec![];
+    let mut address = 0;
+    while address < intcode.len() {
+        let line = decode(intcode, address).unwrap_or_else(|| DisasmLine {
+            address,
+            len: 1,
+            text: format!("DATA {}", intcode[address]),
+            is_data: true,
+        });
+        address += line.len;
+        lines.push(line);
+    }
+    lines
+}
+
+/* The other way around: turns one line of a listing ("ADD [4], 3, [rb-2]", "HALT", "DATA 7") back into words.
+ * Whatever disassemble() prints assembles back to the same words.
+ */
+pub fn assemble(line: &str) -> Result<Vec<i64>, String> {
+    let line = line.trim();
+    let (mnemonic, operands) = line.split_once(' ').unwrap_or((line, ""));
+    let operands: Vec<&str> = operands
+        .split(',')
+        .map(|operand| operand.trim())
+        .filter(|operand| !operand.is_empty())
+        .collect();
+    let number = |text: &str| {
+        text.parse::<i64>()
+            .map_err(|_| format!("Invalid operand \"{}\" in \"{}\"", text, line))
+    };
+    if mnemonic.eq_ignore_ascii_case("DATA") {
+        return operands.iter().map(|&operand| number(operand)).collect();
+    }
+    let (opcode, arity) = (1..=9)
+        .chain(std::iter::once(99))
+        .filter_map(|opcode| opcode_info(opcode).map(|(name, arity)| (opcode, name, arity)))
+        .find(|(_, name, _)| name.eq_ignore_ascii_case(mnemonic))
+        .map(|(opcode, _, arity)| (opcode, arity))
+        .ok_or_else(|| format!("Unknown instruction \"{}\"", mnemonic))?;
+    if operands.len() != arity {
+        return Err(format!(
+            "{} takes {} operands, \"{}\" has {}",
+            mnemonic,
+            arity,
+            line,
+            operands.len()
+        ));
+    }
+    let mut words = vec![opcode];
+    for (offset, operand) in operands.iter().enumerate() {
+        //Same notation as decode: [address], value, [rb+offset]
+        let (mode, value) = match operand
+            .strip_prefix('[')
+            .and_then(|inner| inner.strip_suffix(']'))
+        {
+            Some(inner) => match inner.strip_prefix("rb") {
+                Some(offset) => (2, number(offset.trim_start_matches('+'))?),
+                None => (0, number(inner)?),
+            },
+            None => (1, number(operand)?),
+        };
+        words[0] += mode * 10i64.pow(2 + offset as u32);
+        words.push(value);
+    }
+    Ok(words)
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn listing() {
+        let lines = disassemble(&[1002, 4, 3, 4, 33, 1105, 1, 0, 99, 7]);
+        let text: Vec<&str> = lines.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(
+            text,
+            vec!["MUL [4], 3, [4]", "DATA 33", "JT 1, 0", "HALT", "DATA 7"]
+        );
+        assert_eq!(lines[2].address, 5);
+        assert_eq!(lines[2].len, 3);
+        assert!(lines[1].is_data);
+    }
+
+    #[test]
+    fn relative_mode() {
+        let lines = disassemble(&[109, 19, 204, -34, 21101, 1, 2, 0]);
+        let text: Vec<&str> = lines.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(text, vec!["ARB 19", "OUT [rb-34]", "ADD 1, 2, [rb+0]"]);
+    }
+
+    #[test]
+    fn truncated_instruction() {
+        let lines = disassemble(&[99, 1, 2]);
+        let text: Vec<&str> = lines.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(text, vec!["HALT", "DATA 1", "DATA 2"]);
+    }
+
+    #[test]
+    fn assemble_listing() {
+        let program = [
+            1002, 4, 3, 4, 33, 1105, 1, 0, 109, 19, 204, -34, 21101, 1, 2, 0, 99, 7,
+        ];
+        let words: Vec<i64> = disassemble(&program)
+            .iter()
+            .flat_map(|line| assemble(&line.text).unwrap())
+            .collect();
+        assert_eq!(words, program);
+        assert_eq!(assemble("data 1, 2"), Ok(vec![1, 2]));
+        assert!(assemble("ADD 1, 2").is_err());
+        assert!(assemble("JMP 4").is_err());
+        assert!(assemble("OUT [x]").is_err());
+    }
+}
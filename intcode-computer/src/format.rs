@@ -0,0 +1,503 @@
+use crate::memory::check_address;
+use std::fmt;
+use std::str::FromStr;
+
+const MAGIC: &[u8; 7] = b"INTCODE";
+const VERSION: u8 = 1;
+const HEADER: usize = 17;
+//Words per line in hex listings
+const ROW: usize = 8;
+
+/* The ways a program (or any memory image) can be stored:
+ *  Text    the puzzle input way, 1,0,0,3,99
+ *  Binary  "INTCODE", a version byte (1), how many bytes each word takes (1, 2, 4 or 8, the least that fits them all),
+ *          the number of words as a u64, then the words, all little-endian. Compact, for big memory dumps
+ *  Hex     lines of 8 words in hex, after the address of the first one: "0010: 1 -2 ff 0 0 0 0 63".
+ *          Lines of zeros are left out (memory dumps are mostly zeros), except the last one, which gives the length
+ *  Json    {"memory": [1, 0, 0, 3, 99]} (a plain array is fine too). Any JSON document will do:
+ *          other fields, in any order, are read and ignored
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Text,
+    Binary,
+    Hex,
+    Json,
+}
+impl Format {
+    //Guesses the format from the first bytes: binary has its header, json starts with { or [, hex lines with an address
+    pub fn detect(bytes: &[u8]) -> Format {
+        if bytes.starts_with(MAGIC) {
+            return Format::Binary;
+        }
+        let text = String::from_utf8_lossy(bytes);
+        let text = text.trim_start();
+        if text.starts_with('{') || text.starts_with('[') {
+            Format::Json
+        } else if text.lines().next().is_some_and(|line| line.contains(':')) {
+            Format::Hex
+        } else {
+            Format::Text
+        }
+    }
+
+    pub fn encode(self, memory: &[i64]) -> Vec<u8> {
+        match self {
+            Format::Text => format!("{}\n", join(memory, ",")).into_bytes(),
+            Format::Binary => encode_binary(memory),
+            Format::Hex => encode_hex(memory).into_bytes(),
+            Format::Json => format!("{{\"memory\": [{}]}}\n", join(memory, ", ")).into_bytes(),
+        }
+    }
+
+    pub fn decode(self, bytes: &[u8]) -> Result<Vec<i64>, String> {
+        if self == Format::Binary {
+            return decode_binary(bytes);
+        }
+        let text =
+            std::str::from_utf8(bytes).map_err(|_| format!("A {} program should be text", self))?;
+        match self {
+            Format::Text => numbers(text, 10),
+            Format::Hex => decode_hex(text),
+            _ => decode_json(text),
+        }
+    }
+}
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Format::Text => "text",
+            Format::Binary => "binary",
+            Format::Hex => "hex",
+            Format::Json => "json",
+        };
+        write!(f, "{}", name)
+    }
+}
+impl FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "binary" => Ok(Format::Binary),
+            "hex" => Ok(Format::Hex),
+            "json" => Ok(Format::Json),
+            _ => Err(format!(
+                "Unknown format \"{}\", expected text, binary, hex or json",
+                s
+            )),
+        }
+    }
+}
+
+//Decodes a program in whatever format it's in
+pub fn decode_program(bytes: &[u8]) -> Result<Vec<i64>, String> {
+    Format::detect(bytes).decode(bytes)
+}
+
+fn join(values: &[i64], separator: &str) -> String {
+    values
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+//Comma separated, in that radix (a - in front for negative numbers)
+fn numbers(text: &str, radix: u32) -> Result<Vec<i64>, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(vec![]);
+    }
+    text.split(',')
+        .map(|value| {
+            let value = value.trim();
+            i64::from_str_radix(value, radix).map_err(|_| format!("Invalid value \"{}\"", value))
+        })
+        .collect()
+}
+
+fn encode_binary(memory: &[i64]) -> Vec<u8> {
+    let width = [1, 2, 4, 8]
+        .iter()
+        .copied()
+        .find(|&width: &u32| {
+            let limit = 1i128 << (8 * width - 1);
+            memory
+                .iter()
+                .all(|&value| (-limit..limit).contains(&(value as i128)))
+        })
+        .unwrap() as usize;
+    let mut bytes = Vec::with_capacity(HEADER + memory.len() * width);
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(VERSION);
+    bytes.push(width as u8);
+    bytes.extend_from_slice(&(memory.len() as u64).to_le_bytes());
+    for value in memory {
+        bytes.extend_from_slice(&value.to_le_bytes()[..width]);
+    }
+    bytes
+}
+
+fn decode_binary(bytes: &[u8]) -> Result<Vec<i64>, String> {
+    if bytes.len() < HEADER || !bytes.starts_with(MAGIC) {
+        return Err("Not a binary intcode program".to_string());
+    }
+    if bytes[7] != VERSION {
+        return Err(format!("Unknown binary format version {}", bytes[7]));
+    }
+    let width = bytes[8] as usize;
+    if ![1, 2, 4, 8].contains(&width) {
+        return Err(format!("Invalid word width {}", width));
+    }
+    let mut count = [0; 8];
+    count.copy_from_slice(&bytes[9..HEADER]);
+    let count = u64::from_le_bytes(count);
+    let words = &bytes[HEADER..];
+    if count.checked_mul(width as u64) != Some(words.len() as u64) {
+        return Err(format!(
+            "Expected {} words of {} bytes, got {} bytes",
+            count,
+            width,
+            words.len()
+        ));
+    }
+    Ok(words
+        .chunks(width)
+        .map(|word| {
+            //Sign extension: the missing high bytes are all ones for negative numbers
+            let fill = if word[width - 1] & 0x80 != 0 { 0xff } else { 0 };
+            let mut full = [fill; 8];
+            full[..width].copy_from_slice(word);
+            i64::from_le_bytes(full)
+        })
+        .collect())
+}
+
+fn hex(value: i64) -> String {
+    if value < 0 {
+        format!("-{:x}", value.unsigned_abs())
+    } else {
+        format!("{:x}", value)
+    }
+}
+
+fn encode_hex(memory: &[i64]) -> String {
+    let rows = memory.chunks(ROW).count();
+    let mut text = String::new();
+    for (row, words) in memory.chunks(ROW).enumerate() {
+        if row + 1 < rows && words.iter().all(|&word| word == 0) {
+            continue;
+        }
+        let words: Vec<String> = words.iter().map(|&word| hex(word)).collect();
+        text += &format!("{:04x}: {}\n", row * ROW, words.join(" "));
+    }
+    text
+}
+
+fn decode_hex(text: &str) -> Result<Vec<i64>, String> {
+    let mut memory = vec![];
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let (address, words) = line
+            .split_once(':')
+            .ok_or_else(|| format!("Expected address: words, got \"{}\"", line))?;
+        let address = usize::from_str_radix(address.trim(), 16)
+            .map_err(|_| format!("Invalid address \"{}\"", address))?;
+        if address < memory.len() {
+            return Err(format!("Address {:x} overlaps the line before it", address));
+        }
+        check_address(address)?;
+        memory.resize(address, 0);
+        memory.extend(numbers(
+            &words.split_whitespace().collect::<Vec<_>>().join(","),
+            16,
+        )?);
+    }
+    Ok(memory)
+}
+
+//Just enough of JSON to find the memory in a document, everything else gets checked and ignored
+enum Json<'a> {
+    //Only parsed if it's part of the memory
+    Number(&'a str),
+    Array(Vec<Json<'a>>),
+    Object(Vec<(String, Json<'a>)>),
+    //Strings, true, false and null: nothing here cares what they say
+    Other,
+}
+
+//Documents nested deeper than that are refused, instead of running out of stack
+const MAX_DEPTH: usize = 128;
+
+struct JsonParser<'a> {
+    text: &'a str,
+    at: usize,
+}
+impl<'a> JsonParser<'a> {
+    fn error(&self, expected: &str) -> String {
+        format!("Expected {} at byte {} of the JSON", expected, self.at)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.at).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.at += 1;
+        }
+    }
+
+    //Skips whitespace, then takes that byte if it's next
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_whitespace();
+        let found = self.peek() == Some(byte);
+        if found {
+            self.at += 1;
+        }
+        found
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Json<'a>, String> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("less nesting"));
+        }
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => {
+                self.at += 1;
+                let mut fields = vec![];
+                if !self.eat(b'}') {
+                    loop {
+                        self.skip_whitespace();
+                        let key = self.string()?;
+                        if !self.eat(b':') {
+                            return Err(self.error(":"));
+                        }
+                        fields.push((key, self.value(depth + 1)?));
+                        if self.eat(b'}') {
+                            break;
+                        }
+                        if !self.eat(b',') {
+                            return Err(self.error(", or }"));
+                        }
+                    }
+                }
+                Ok(Json::Object(fields))
+            }
+            Some(b'[') => {
+                self.at += 1;
+                let mut values = vec![];
+                if !self.eat(b']') {
+                    loop {
+                        values.push(self.value(depth + 1)?);
+                        if self.eat(b']') {
+                            break;
+                        }
+                        if !self.eat(b',') {
+                            return Err(self.error(", or ]"));
+                        }
+                    }
+                }
+                Ok(Json::Array(values))
+            }
+            Some(b'"') => {
+                self.string()?;
+                Ok(Json::Other)
+            }
+            Some(b'-' | b'0'..=b'9') => {
+                let start = self.at;
+                while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') = self.peek() {
+                    self.at += 1;
+                }
+                Ok(Json::Number(&self.text[start..self.at]))
+            }
+            _ => {
+                let literal = ["true", "false", "null"]
+                    .iter()
+                    .find(|word| self.text[self.at..].starts_with(*word))
+                    .ok_or_else(|| self.error("a value"))?;
+                self.at += literal.len();
+                Ok(Json::Other)
+            }
+        }
+    }
+
+    //Four hex digits, after \u
+    fn code_unit(&mut self) -> Result<u32, String> {
+        let digits = self
+            .text
+            .get(self.at..self.at + 4)
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| self.error("4 hex digits"))?;
+        self.at += 4;
+        Ok(u32::from_str_radix(digits, 16).unwrap())
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.peek() != Some(b'"') {
+            return Err(self.error("a string"));
+        }
+        self.at += 1;
+        let mut string = String::new();
+        loop {
+            //Everything up to the next quote or escape goes in as it is
+            let rest = &self.text[self.at..];
+            let end = rest
+                .find(['"', '\\'])
+                .ok_or_else(|| "Unterminated string in the JSON".to_string())?;
+            string.push_str(&rest[..end]);
+            self.at += end + 1;
+            if rest.as_bytes()[end] == b'"' {
+                return Ok(string);
+            }
+            let escape = self.peek();
+            self.at += 1;
+            let c = match escape {
+                Some(b'"') => '"',
+                Some(b'\\') => '\\',
+                Some(b'/') => '/',
+                Some(b'b') => '\u{8}',
+                Some(b'f') => '\u{c}',
+                Some(b'n') => '\n',
+                Some(b'r') => '\r',
+                Some(b't') => '\t',
+                Some(b'u') => {
+                    let mut code = self.code_unit()?;
+                    //Characters past the first 65536 come as two halves
+                    if (0xd800..0xdc00).contains(&code) && self.text[self.at..].starts_with("\\u") {
+                        self.at += 2;
+                        let low = self.code_unit()?;
+                        if !(0xdc00..0xe000).contains(&low) {
+                            return Err(self.error("the second half of a character"));
+                        }
+                        code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                    }
+                    char::from_u32(code).ok_or_else(|| self.error("a valid character"))?
+                }
+                _ => return Err(self.error("an escape")),
+            };
+            string.push(c);
+        }
+    }
+}
+
+fn decode_json(text: &str) -> Result<Vec<i64>, String> {
+    let mut parser = JsonParser { text, at: 0 };
+    let document = parser.value(0)?;
+    parser.skip_whitespace();
+    if parser.at < text.len() {
+        return Err(parser.error("the end"));
+    }
+    let values = match document {
+        Json::Array(values) => values,
+        Json::Object(fields) => match fields.into_iter().find(|(key, _)| key == "memory") {
+            Some((_, Json::Array(values))) => values,
+            Some(_) => return Err("\"memory\" should be an array".to_string()),
+            None => return Err("Expected {\"memory\": [...]}".to_string()),
+        },
+        _ => return Err("Expected {\"memory\": [...]} or an array".to_string()),
+    };
+    values
+        .into_iter()
+        .map(|value| match value {
+            Json::Number(number) => number
+                .parse::<i64>()
+                .map_err(|_| format!("Invalid value \"{}\"", number)),
+            _ => Err("The memory should only hold numbers".to_string()),
+        })
+        .collect()
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    const MEMORY: [i64; 11] = [1, 0, 0, 3, -1, 99, 34463338, 0, 0, 0, i64::MIN];
+
+    #[test]
+    fn round_trips() {
+        for format in [Format::Text, Format::Binary, Format::Hex, Format::Json] {
+            let bytes = format.encode(&MEMORY);
+            assert_eq!(Format::detect(&bytes), format);
+            assert_eq!(decode_program(&bytes), Ok(MEMORY.to_vec()), "{}", format);
+            assert_eq!(format.to_string().parse(), Ok(format));
+        }
+        assert_eq!(decode_program(b"[]"), Ok(vec![]));
+        assert_eq!(decode_program(b" [1, 2]\n"), Ok(vec![1, 2]));
+    }
+
+    #[test]
+    fn json_documents() {
+        let document = br#"{
+            "version": 2,
+            "tags": ["day \"9\"", "\u00e9\ud83d\ude00", {"nested": [true, null, 1.5e3]}],
+            "memory": [
+                109, -1,
+                99
+            ],
+            "ip": 0
+        }"#;
+        assert_eq!(Format::detect(document), Format::Json);
+        assert_eq!(decode_program(document), Ok(vec![109, -1, 99]));
+        assert_eq!(decode_program(b"{\"memory\":[]}\r\n\t"), Ok(vec![]));
+        for invalid in [
+            &b"{\"memory\": [1, 2.5]}"[..],
+            b"{\"memory\": [1, \"2\"]}",
+            b"{\"memory\": 1}",
+            b"{\"ip\": 0}",
+            b"{\"memory\": [1]} [2]",
+            b"{\"memory\": [1,]}",
+            b"{\"tag\": \"\\x\", \"memory\": [1]}",
+            b"{\"memory\": [1]",
+            b"{\"tag\": \"\\ud83d\", \"memory\": [1]}",
+            b"{\"tag\": \"\\ud83d\\u0041\", \"memory\": [1]}",
+        ] {
+            assert!(
+                decode_program(invalid).is_err(),
+                "{}",
+                String::from_utf8_lossy(invalid)
+            );
+        }
+        let deep = format!("{}{}", "[".repeat(1000), "]".repeat(1000));
+        assert!(decode_program(deep.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn compact() {
+        //Bytes fit in one byte each, 34463338 takes 4
+        let program = [1, 0, 0, 3, 99, -128];
+        assert_eq!(Format::Binary.encode(&program).len(), 17 + 6);
+        assert_eq!(Format::Binary.encode(&[34463338]).len(), 17 + 4);
+        let bytes = Format::Binary.encode(&program);
+        assert_eq!(Format::Binary.decode(&bytes), Ok(program.to_vec()));
+        assert!(Format::Binary.decode(&bytes[..20]).is_err());
+
+        //Only the first and last lines of a big dump of zeros are written
+        let mut dump = vec![0; 1000];
+        dump[3] = 255;
+        dump[999] = -1;
+        let listing = String::from_utf8(Format::Hex.encode(&dump)).unwrap();
+        assert_eq!(listing, "0000: 0 0 0 ff 0 0 0 0\n03e0: 0 0 0 0 0 0 0 -1\n");
+        assert_eq!(decode_program(listing.as_bytes()), Ok(dump));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(decode_program(b"1,x,3").is_err());
+        assert!(decode_program(b"{\"program\": [1]}").is_err());
+        assert!(decode_program(b"0008: 1\n0000: 2").is_err());
+        assert!(decode_program(b"ffffffffffff: 1").is_err());
+        assert!(Format::Json.decode(&[0xff, 0xfe]).is_err());
+        assert!("yaml".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn load() {
+        let path = std::env::temp_dir().join(format!("format-load-{}.bin", std::process::id()));
+        std::fs::write(&path, Format::Binary.encode(&[1, 0, 0, 0, 99])).unwrap();
+        let program = load_program(&path, &["1=12".parse().unwrap()]);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(program, Ok(vec![1, 12, 0, 0, 99]));
+    }
+}
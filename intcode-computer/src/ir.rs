@@ -0,0 +1,774 @@
+/* An intermediate representation for optimizing intcode programs, lifted from the decoded Instructions.
+ * Memory is split in two: the instructions, with jumps pointing at other instructions instead of addresses,
+ * and the cells the program reads and writes as data. Re-emitting lays the code out again, then the cells after it,
+ * so anything that got optimized away makes the program shorter (words of code that are also read or written as data
+ * get a cell of their own though, so a program that can't be optimized at all may come out a bit longer).
+ *
+ * That only works for programs that keep code and data apart, which a lot of intcode doesn't do!
+ * Lifting refuses (with an error saying why) programs that:
+ *  - use relative mode: addresses computed at run time could be anywhere
+ *  - jump to an address stored in a cell that changes
+ *  - write into an instruction that may run after that (reading code as data, or overwriting code that's done, is fine)
+ *  - have instructions overlapping each other, or crash on an invalid one or a jump to a negative address
+ * "Equivalent" means: same outputs for the same inputs, and the same final values in the cells the caller cares about.
+ */
+use crate::disasm::decode;
+use crate::{Instruction, ParamMode, Parameter};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+//An operand: a constant (immediate mode), or the cell at that address in the original program (position mode)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value {
+    Const(i64),
+    Cell(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Op {
+    Add(Value, Value, usize),
+    Mul(Value, Value, usize),
+    LessThan(Value, Value, usize),
+    Equals(Value, Value, usize),
+    //What folded arithmetic turns into
+    Set(usize, i64),
+    In(usize),
+    Out(Value),
+    //Jumps to the instruction at that address when the value is non-zero (true) or zero (false)
+    JumpIf(bool, Value, usize),
+    Jump(usize),
+    Halt,
+}
+impl Op {
+    //The cell it writes to
+    fn dest(&self) -> Option<usize> {
+        match *self {
+            Op::Add(_, _, dest)
+            | Op::Mul(_, _, dest)
+            | Op::LessThan(_, _, dest)
+            | Op::Equals(_, _, dest)
+            | Op::Set(dest, _)
+            | Op::In(dest) => Some(dest),
+            _ => None,
+        }
+    }
+
+    fn values(&self) -> Vec<Value> {
+        match *self {
+            Op::Add(lhs, rhs, _)
+            | Op::Mul(lhs, rhs, _)
+            | Op::LessThan(lhs, rhs, _)
+            | Op::Equals(lhs, rhs, _) => vec![lhs, rhs],
+            Op::Out(value) | Op::JumpIf(_, value, _) => vec![value],
+            _ => vec![],
+        }
+    }
+
+    //The cells it reads from
+    fn reads(&self) -> Vec<usize> {
+        self.values()
+            .into_iter()
+            .filter_map(|value| match value {
+                Value::Cell(cell) => Some(cell),
+                Value::Const(_) => None,
+            })
+            .collect()
+    }
+
+    fn map_values<F: Fn(Value) -> Value>(&self, f: F) -> Op {
+        match *self {
+            Op::Add(lhs, rhs, dest) => Op::Add(f(lhs), f(rhs), dest),
+            Op::Mul(lhs, rhs, dest) => Op::Mul(f(lhs), f(rhs), dest),
+            Op::LessThan(lhs, rhs, dest) => Op::LessThan(f(lhs), f(rhs), dest),
+            Op::Equals(lhs, rhs, dest) => Op::Equals(f(lhs), f(rhs), dest),
+            Op::Out(value) => Op::Out(f(value)),
+            Op::JumpIf(when, cond, target) => Op::JumpIf(when, f(cond), target),
+            ref op => op.clone(),
+        }
+    }
+
+    fn target(&self) -> Option<usize> {
+        match *self {
+            Op::JumpIf(_, _, target) | Op::Jump(target) => Some(target),
+            _ => None,
+        }
+    }
+
+    fn falls_through(&self) -> bool {
+        !matches!(self, Op::Jump(_) | Op::Halt)
+    }
+
+    //How many words it takes once emitted
+    fn len(&self) -> usize {
+        match self {
+            Op::Add(..) | Op::Mul(..) | Op::LessThan(..) | Op::Equals(..) | Op::Set(..) => 4,
+            Op::In(_) | Op::Out(_) => 2,
+            Op::JumpIf(..) | Op::Jump(_) => 3,
+            Op::Halt => 1,
+        }
+    }
+}
+
+/* An instruction, and the addresses in the original program that lead to it.
+ * Instructions added while lifting have none, and when an instruction is removed its addresses go to the next one.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    pub labels: Vec<usize>,
+    pub op: Op,
+}
+
+//Each node falls through to the next one. The last one is a Halt at the end of the program, where jumps past the end go
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ir {
+    pub nodes: Vec<Node>,
+    //The original program, for the initial values of the cells
+    memory: Vec<i64>,
+}
+
+//An optimized program, and where the cells of the original program ended up in it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Emitted {
+    pub program: Vec<i64>,
+    pub cells: BTreeMap<usize, usize>,
+}
+
+fn address(param: &Parameter) -> Result<usize, String> {
+    if param.value < 0 {
+        Err(format!("Accesses the negative address {}", param.value))
+    } else {
+        Ok(param.value as usize)
+    }
+}
+
+fn value(param: &Parameter) -> Result<Value, String> {
+    match param.mode {
+        ParamMode::Immediate => Ok(Value::Const(param.value)),
+        _ => address(param).map(Value::Cell),
+    }
+}
+
+impl Ir {
+    //Decodes every instruction that can be reached from the start, following jumps
+    pub fn lift(program: &[i64]) -> Result<Self, String> {
+        let end = program.len();
+        let mut decoded: BTreeMap<usize, (usize, Op)> = BTreeMap::new();
+        //Cells that jump targets come from, they'd better never change
+        let mut target_cells = vec![];
+        //Whether something falls or jumps past the end (Halt doesn't count)
+        let mut reaches_end = false;
+        //Addresses reached that don't hold an instruction (yet, maybe)
+        let mut invalid = BTreeSet::new();
+        let mut todo = vec![0];
+        while let Some(start) = todo.pop() {
+            if start >= end {
+                reaches_end = true;
+                continue;
+            }
+            if decoded.contains_key(&start) {
+                continue;
+            }
+            let line = match decode(program, start) {
+                Some(line) => line,
+                None => {
+                    invalid.insert(start);
+                    continue;
+                }
+            };
+            let code = program[start];
+            if code % 100 == 9 || (code / 100).to_string().contains('2') {
+                return Err(format!("Uses relative mode at {}", start));
+            }
+            let mut target = |param: &Parameter| -> Result<usize, String> {
+                let target = match param.mode {
+                    ParamMode::Immediate => param.value,
+                    _ => {
+                        let cell = address(param)?;
+                        target_cells.push((start, cell));
+                        program.get(cell).copied().unwrap_or(0)
+                    }
+                };
+                //Jumping there crashes, like reading or writing there does
+                if target < 0 {
+                    return Err(format!(
+                        "Jumps to the negative address {} at {}",
+                        target, start
+                    ));
+                }
+                //Out of the program means halting
+                Ok(if target as usize >= end {
+                    end
+                } else {
+                    target as usize
+                })
+            };
+            /* Jumps on a constant are settled right away: whatever comes after "1105, 1, x" is usually data,
+             * and one that never jumps goes on to the next instruction either way
+             */
+            let jump = |when: bool, cond: Value, to: usize| match cond {
+                Value::Const(cond) if (cond != 0) == when => Op::Jump(to),
+                Value::Const(_) => Op::JumpIf(when, cond, start + line.len),
+                cond => Op::JumpIf(when, cond, to),
+            };
+            let op = match Instruction::new(program, &mut start.clone(), 0) {
+                Instruction::Add { lhs, rhs, dest } => {
+                    Op::Add(value(&lhs)?, value(&rhs)?, address(&dest)?)
+                }
+                Instruction::Mul { lhs, rhs, dest } => {
+                    Op::Mul(value(&lhs)?, value(&rhs)?, address(&dest)?)
+                }
+                Instruction::LessThan { lhs, rhs, dest } => {
+                    Op::LessThan(value(&lhs)?, value(&rhs)?, address(&dest)?)
+                }
+                Instruction::Equals { lhs, rhs, dest } => {
+                    Op::Equals(value(&lhs)?, value(&rhs)?, address(&dest)?)
+                }
+                Instruction::In { dest } => Op::In(address(&dest)?),
+                Instruction::Out { src } => Op::Out(value(&src)?),
+                Instruction::JumpIfTrue { cond, target: to } => {
+                    jump(true, value(&cond)?, target(&to)?)
+                }
+                Instruction::JumpIfFalse { cond, target: to } => {
+                    jump(false, value(&cond)?, target(&to)?)
+                }
+                Instruction::AdjustRelativeBase { .. } => unreachable!(),
+                Instruction::Halt => Op::Halt,
+            };
+            if op.falls_through() {
+                todo.push(start + line.len);
+            }
+            todo.extend(op.target());
+            decoded.insert(start, (line.len, op));
+        }
+
+        let written: BTreeSet<usize> = decoded.values().filter_map(|(_, op)| op.dest()).collect();
+        if let Some(&start) = invalid.iter().next() {
+            return Err(
+                match decoded.iter().find(|(_, (_, op))| op.dest() == Some(start)) {
+                    Some((writer, _)) => format!(
+                        "The instruction at {} modifies the code at {}, which may run afterwards",
+                        writer, start
+                    ),
+                    None => format!(
+                        "No valid instruction at {}, the program would crash there",
+                        start
+                    ),
+                },
+            );
+        }
+        let mut previous_end = 0;
+        for (&start, (len, _)) in &decoded {
+            if start < previous_end {
+                return Err(format!(
+                    "The instruction at {} overlaps the one before it",
+                    start
+                ));
+            }
+            previous_end = start + len;
+        }
+        if let Some((start, cell)) = target_cells.iter().find(|(_, cell)| written.contains(cell)) {
+            return Err(format!(
+                "The jump at {} goes wherever cell {} says, and it changes",
+                start, cell
+            ));
+        }
+        //Memory grows with writes past the end, and running into it would execute whatever got written there
+        if reaches_end && written.iter().any(|&cell| cell >= end) {
+            return Err("Runs past the end of the program, after writing past it".to_string());
+        }
+        /* Writing into an instruction is fine when it already ran and can't run again:
+         * the writer comes after it, and no jump from there on goes back to it.
+         */
+        for (&writer, (_, op)) in &decoded {
+            let cell = match op.dest() {
+                Some(cell) => cell,
+                None => continue,
+            };
+            if let Some((&start, _)) = decoded
+                .range(..=cell)
+                .next_back()
+                .filter(|(&start, (len, _))| cell < start + len)
+            {
+                let jumps_back = decoded
+                    .range(start..)
+                    .any(|(_, (_, op))| op.target().is_some_and(|target| target <= start));
+                if writer < start || jumps_back {
+                    return Err(format!(
+                        "The instruction at {} modifies the code at {}, which may run afterwards",
+                        writer, start
+                    ));
+                }
+            }
+        }
+
+        let mut nodes = vec![];
+        let starts: Vec<usize> = decoded.keys().copied().collect();
+        for (index, (&start, (len, op))) in decoded.iter().enumerate() {
+            nodes.push(Node {
+                labels: vec![start],
+                op: op.clone(),
+            });
+            //Data in between, or the end of the program
+            if op.falls_through() && starts.get(index + 1) != Some(&(start + len)) {
+                nodes.push(Node {
+                    labels: vec![],
+                    op: Op::Jump(start + len),
+                });
+            }
+        }
+        nodes.push(Node {
+            labels: vec![end],
+            op: Op::Halt,
+        });
+        Ok(Ir {
+            nodes,
+            memory: program.to_vec(),
+        })
+    }
+
+    fn initial(&self, cell: usize) -> i64 {
+        self.memory.get(cell).copied().unwrap_or(0)
+    }
+
+    fn index_of(&self, address: usize) -> usize {
+        self.nodes
+            .iter()
+            .position(|node| node.labels.contains(&address))
+            .unwrap_or_else(|| panic!("No instruction at {}", address))
+    }
+
+    fn targets(&self) -> HashSet<usize> {
+        self.nodes
+            .iter()
+            .filter_map(|node| node.op.target())
+            .collect()
+    }
+
+    //The last node is never removed, so there's always a next one to take the labels
+    fn remove(&mut self, index: usize) {
+        let labels = self.nodes.remove(index).labels;
+        self.nodes[index].labels.extend(labels);
+    }
+
+    /* Replaces reads of cells with their value wherever it's known, and arithmetic on constants with its result.
+     * Cells that are never written always hold their initial value; others are only known from a Set
+     * earlier in the same straight line of code (or from the start of the program, if nothing jumps back there).
+     */
+    pub fn fold_constants(&mut self) {
+        let written: HashSet<usize> = self
+            .nodes
+            .iter()
+            .filter_map(|node| node.op.dest())
+            .collect();
+        let targets = self.targets();
+        let mut known: HashMap<usize, i64> = if targets.contains(&0) {
+            HashMap::new()
+        } else {
+            written
+                .iter()
+                .map(|&cell| (cell, self.initial(cell)))
+                .collect()
+        };
+        let mut dropped = vec![];
+        for index in 0..self.nodes.len() {
+            if self.nodes[index]
+                .labels
+                .iter()
+                .any(|label| targets.contains(label))
+            {
+                known.clear();
+            }
+            let op = self.nodes[index].op.map_values(|value| match value {
+                Value::Cell(cell) if !written.contains(&cell) => Value::Const(self.initial(cell)),
+                Value::Cell(cell) => known.get(&cell).map_or(value, |&known| Value::Const(known)),
+                value => value,
+            });
+            //Overflows are left alone, so that they still panic when the program runs
+            let op = match op {
+                Op::Add(Value::Const(lhs), Value::Const(rhs), dest)
+                    if lhs.checked_add(rhs).is_some() =>
+                {
+                    Op::Set(dest, lhs + rhs)
+                }
+                Op::Mul(Value::Const(lhs), Value::Const(rhs), dest)
+                    if lhs.checked_mul(rhs).is_some() =>
+                {
+                    Op::Set(dest, lhs * rhs)
+                }
+                Op::LessThan(Value::Const(lhs), Value::Const(rhs), dest) => {
+                    Op::Set(dest, (lhs < rhs) as i64)
+                }
+                Op::Equals(Value::Const(lhs), Value::Const(rhs), dest) => {
+                    Op::Set(dest, (lhs == rhs) as i64)
+                }
+                Op::JumpIf(when, Value::Const(cond), target) if (cond != 0) == when => {
+                    Op::Jump(target)
+                }
+                Op::JumpIf(_, Value::Const(_), _) => {
+                    dropped.push(index);
+                    continue;
+                }
+                op => op,
+            };
+            match op {
+                Op::Set(dest, value) => {
+                    known.insert(dest, value);
+                }
+                Op::Jump(_) | Op::Halt => known.clear(),
+                ref op => {
+                    if let Some(dest) = op.dest() {
+                        known.remove(&dest);
+                    }
+                }
+            }
+            self.nodes[index].op = op;
+        }
+        for index in dropped.into_iter().rev() {
+            self.remove(index);
+        }
+    }
+
+    /* Removes writes nobody can see: to cells that are never read (and that the caller doesn't care about),
+     * or that get overwritten before being read, further down the same straight line of code.
+     * Inputs are never removed, they still take a value from the input.
+     */
+    pub fn eliminate_dead_stores(&mut self, live: &[usize]) {
+        let read: HashSet<usize> = self.nodes.iter().flat_map(|node| node.op.reads()).collect();
+        let mut dropped = vec![];
+        for (index, node) in self.nodes.iter().enumerate() {
+            let dest = match node.op {
+                Op::In(_) => continue,
+                ref op => match op.dest() {
+                    Some(dest) => dest,
+                    None => continue,
+                },
+            };
+            let mut dead = !read.contains(&dest) && !live.contains(&dest);
+            for later in &self.nodes[index + 1..] {
+                if dead || later.op.reads().contains(&dest) {
+                    break;
+                }
+                if later.op.dest() == Some(dest) {
+                    dead = true;
+                } else if later.op == Op::Halt {
+                    dead = !live.contains(&dest);
+                    break;
+                } else if later.op.target().is_some() {
+                    break;
+                }
+            }
+            if dead {
+                dropped.push(index);
+            }
+        }
+        for index in dropped.into_iter().rev() {
+            self.remove(index);
+        }
+    }
+
+    /* Jumps to a jump go straight to where that one goes, unconditional jumps to a Halt become a Halt,
+     * and jumps to the very next instruction are removed.
+     */
+    pub fn thread_jumps(&mut self) {
+        for index in 0..self.nodes.len() {
+            let mut target = match self.nodes[index].op.target() {
+                Some(target) => target,
+                None => continue,
+            };
+            //Bounded, in case of a loop made of jumps
+            for _ in 0..self.nodes.len() {
+                match self.nodes[self.index_of(target)].op {
+                    Op::Jump(next) if next != target => target = next,
+                    _ => break,
+                }
+            }
+            let halts = self.nodes[self.index_of(target)].op == Op::Halt;
+            self.nodes[index].op = match self.nodes[index].op {
+                Op::Jump(_) if halts => Op::Halt,
+                Op::Jump(_) => Op::Jump(target),
+                Op::JumpIf(when, cond, _) => Op::JumpIf(when, cond, target),
+                ref op => op.clone(),
+            };
+        }
+        while let Some(index) = (0..self.nodes.len() - 1).find(|&index| {
+            self.nodes[index]
+                .op
+                .target()
+                .is_some_and(|target| self.index_of(target) == index + 1)
+        }) {
+            self.remove(index);
+        }
+    }
+
+    pub fn remove_unreachable(&mut self) {
+        let mut reachable = vec![false; self.nodes.len()];
+        let mut todo = vec![0];
+        while let Some(index) = todo.pop() {
+            if reachable[index] {
+                continue;
+            }
+            reachable[index] = true;
+            let op = &self.nodes[index].op;
+            if op.falls_through() {
+                todo.push(index + 1);
+            }
+            todo.extend(op.target().map(|target| self.index_of(target)));
+        }
+        for index in (0..self.nodes.len() - 1).rev() {
+            if !reachable[index] {
+                self.remove(index);
+            }
+        }
+    }
+
+    //Code first, then the cells it uses (and the live ones), in the order of their original addresses
+    pub fn emit(&self, live: &[usize]) -> Emitted {
+        let mut nodes: Vec<&Node> = self.nodes.iter().collect();
+        //The Halt at the end is only needed if something gets there
+        let targets = self.targets();
+        if nodes.len() > 1
+            && !nodes[nodes.len() - 2].op.falls_through()
+            && !nodes[nodes.len() - 1]
+                .labels
+                .iter()
+                .any(|label| targets.contains(label))
+        {
+            nodes.pop();
+        }
+        let mut addresses = HashMap::new();
+        let mut at = 0;
+        for node in &nodes {
+            for &label in &node.labels {
+                addresses.insert(label, at as i64);
+            }
+            at += node.op.len();
+        }
+        let used: BTreeSet<usize> = nodes
+            .iter()
+            .flat_map(|node| node.op.reads().into_iter().chain(node.op.dest()))
+            .chain(live.iter().copied())
+            .collect();
+        let cells: BTreeMap<usize, usize> = used
+            .iter()
+            .enumerate()
+            .map(|(index, &cell)| (cell, at + index))
+            .collect();
+
+        let mut program = vec![];
+        let mut emit = |opcode: i64, params: &[Value]| {
+            let mut code = opcode;
+            let mut words = vec![];
+            for (offset, &param) in params.iter().enumerate() {
+                match param {
+                    Value::Const(value) => {
+                        code += 10i64.pow(2 + offset as u32);
+                        words.push(value);
+                    }
+                    Value::Cell(cell) => words.push(cells[&cell] as i64),
+                }
+            }
+            program.push(code);
+            program.extend(words);
+        };
+        for node in &nodes {
+            match node.op {
+                Op::Add(lhs, rhs, dest) => emit(1, &[lhs, rhs, Value::Cell(dest)]),
+                Op::Mul(lhs, rhs, dest) => emit(2, &[lhs, rhs, Value::Cell(dest)]),
+                Op::LessThan(lhs, rhs, dest) => emit(7, &[lhs, rhs, Value::Cell(dest)]),
+                Op::Equals(lhs, rhs, dest) => emit(8, &[lhs, rhs, Value::Cell(dest)]),
+                Op::Set(dest, value) => emit(
+                    1,
+                    &[Value::Const(value), Value::Const(0), Value::Cell(dest)],
+                ),
+                Op::In(dest) => emit(3, &[Value::Cell(dest)]),
+                Op::Out(value) => emit(4, &[value]),
+                Op::JumpIf(when, cond, target) => emit(
+                    if when { 5 } else { 6 },
+                    &[cond, Value::Const(addresses[&target])],
+                ),
+                Op::Jump(target) => emit(5, &[Value::Const(1), Value::Const(addresses[&target])]),
+                Op::Halt => emit(99, &[]),
+            }
+        }
+        program.extend(used.iter().map(|&cell| self.initial(cell)));
+        Emitted { program, cells }
+    }
+}
+
+//Lifts the program, runs every pass until nothing changes anymore, and emits the result
+pub fn optimize(program: &[i64], live: &[usize]) -> Result<Emitted, String> {
+    let mut ir = Ir::lift(program)?;
+    loop {
+        let before = ir.nodes.clone();
+        ir.fold_constants();
+        ir.eliminate_dead_stores(live);
+        ir.thread_jumps();
+        ir.remove_unreachable();
+        if ir.nodes == before {
+            return Ok(ir.emit(live));
+        }
+    }
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::ir::*;
+    use crate::*;
+
+    const DAY_2: [i64; 121] = [
+        1, 0, 0, 3, 1, 1, 2, 3, 1, 3, 4, 3, 1, 5, 0, 3, 2, 1, 10, 19, 1, 6, 19, 23, 2, 23, 6, 27,
+        2, 6, 27, 31, 2, 13, 31, 35, 1, 10, 35, 39, 2, 39, 13, 43, 1, 43, 13, 47, 1, 6, 47, 51, 1,
+        10, 51, 55, 2, 55, 6, 59, 1, 5, 59, 63, 2, 9, 63, 67, 1, 6, 67, 71, 2, 9, 71, 75, 1, 6, 75,
+        79, 2, 79, 13, 83, 1, 83, 10, 87, 1, 13, 87, 91, 1, 91, 10, 95, 2, 9, 95, 99, 1, 5, 99,
+        103, 2, 10, 103, 107, 1, 107, 2, 111, 1, 111, 5, 0, 99, 2, 14, 0, 0,
+    ];
+
+    //Runs both, and checks the outputs and the live cells are the same
+    fn assert_equivalent(program: &[i64], optimized: &Emitted, input: &[i64], live: &[usize]) {
+        let mut original = program.to_vec();
+        let mut copy = optimized.program.clone();
+        assert_eq!(
+            execute(&mut copy, &input.to_vec()),
+            execute(&mut original, &input.to_vec()),
+            "{:?} with {:?}",
+            program,
+            input
+        );
+        for cell in live {
+            assert_eq!(copy[optimized.cells[cell]], original[*cell]);
+        }
+    }
+
+    #[test]
+    fn constant_folding() {
+        //7 = 2 + 3, then print it
+        let optimized = optimize(&[1101, 2, 3, 7, 4, 7, 99, 0], &[]).unwrap();
+        assert_eq!(optimized.program, vec![104, 5, 99]);
+        //Overflows stay, to panic at run time like they should
+        let optimized = optimize(&[1102, i64::MAX, 2, 5, 99, 0], &[5]).unwrap();
+        assert_eq!(optimized.program, vec![1102, i64::MAX, 2, 5, 99, 0]);
+    }
+
+    #[test]
+    fn dead_stores() {
+        //The first sum is overwritten before anyone reads it
+        let program = [3, 20, 1001, 20, 1, 21, 1001, 20, 2, 21, 4, 21, 99];
+        let optimized = optimize(&program, &[]).unwrap();
+        assert_eq!(
+            optimized.program,
+            vec![3, 9, 1001, 9, 2, 10, 4, 10, 99, 0, 0]
+        );
+        assert_equivalent(&program, &optimized, &[40], &[]);
+        //Unless the caller wants it
+        let optimized = optimize(&[1101, 1, 2, 5, 99, 0], &[5]).unwrap();
+        assert_eq!(optimized.program, vec![1101, 3, 0, 5, 99, 0]);
+        assert_eq!(optimized.cells[&5], 5);
+    }
+
+    #[test]
+    fn jump_threading() {
+        //Jumps to a jump, that jumps to the next instruction
+        let optimized = optimize(&[1105, 1, 3, 1105, 1, 6, 104, 7, 99], &[]).unwrap();
+        assert_eq!(optimized.program, vec![104, 7, 99]);
+        //Jumps to the Halt at the end become a Halt
+        let program = [3, 11, 1006, 11, 8, 1105, 1, 10, 104, 1, 99, 0];
+        let optimized = optimize(&program, &[]).unwrap();
+        assert_eq!(optimized.program, vec![3, 9, 1006, 9, 6, 99, 104, 1, 99, 0]);
+        for input in -1..=1 {
+            assert_equivalent(&program, &optimized, &[input], &[]);
+        }
+    }
+
+    #[test]
+    fn day_2() {
+        //Every noun and verb, with the result in address 0
+        for noun in 0..100 {
+            for verb in 0..100 {
+                let mut program = DAY_2.to_vec();
+                program[1] = noun;
+                program[2] = verb;
+                let optimized = optimize(&program, &[0]).unwrap();
+                //It all folds into a single Set
+                assert_eq!(optimized.program.len(), 6);
+                assert_equivalent(&program, &optimized, &[], &[0]);
+            }
+        }
+        //The examples
+        for program in [
+            vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50],
+            vec![1, 0, 0, 0, 99],
+            vec![2, 4, 4, 5, 99, 0],
+        ] {
+            let optimized = optimize(&program, &[0]).unwrap();
+            assert_equivalent(&program, &optimized, &[], &[0]);
+        }
+        //That one turns its Halt into a Mul
+        assert!(optimize(&[1, 1, 1, 4, 99, 5, 6, 0, 99], &[0])
+            .unwrap_err()
+            .contains("modifies the code at 4"));
+    }
+
+    #[test]
+    fn day_5() {
+        let examples = [
+            vec![3, 0, 4, 0, 99],
+            vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8],
+            vec![3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8],
+            vec![3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9],
+            vec![
+                3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36,
+                98, 0, 0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000,
+                1, 20, 4, 20, 1105, 1, 46, 98, 99,
+            ],
+        ];
+        for program in &examples {
+            let optimized = optimize(program, &[]).unwrap();
+            for input in -10..20 {
+                assert_equivalent(program, &optimized, &[input], &[]);
+            }
+        }
+        assert!(optimize(&examples[4], &[]).unwrap().program.len() < examples[4].len());
+
+        //Those write into the instruction that runs next (the input becomes one of its parameters, for some)
+        for program in [
+            vec![1002, 4, 3, 4, 33],
+            vec![1101, 100, -1, 4, 0],
+            vec![3, 3, 1108, -1, 8, 3, 4, 3, 99],
+            vec![3, 3, 1107, -1, 8, 3, 4, 3, 99],
+            vec![3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1],
+        ] {
+            let error = format!("{:?}", optimize(&program, &[]));
+            assert!(
+                error.contains("modifies the code"),
+                "{:?}: {}",
+                program,
+                error
+            );
+        }
+        //And so does the diagnostic program itself (its first input becomes part of an opcode)
+        let diagnostic = parse_program(include_str!("../../day_05/src/input.txt"));
+        assert!(optimize(&diagnostic, &[])
+            .unwrap_err()
+            .contains("modifies the code"));
+    }
+
+    #[test]
+    fn refused() {
+        assert!(optimize(&[109, 1, 99], &[])
+            .unwrap_err()
+            .contains("relative"));
+        assert!(optimize(&[1, 0, 0, -1, 99], &[])
+            .unwrap_err()
+            .contains("negative"));
+        //The jump target is read from cell 8, which comes from the input
+        let error = optimize(&[3, 8, 6, 9, 8, 99, 99, 99, 0, 0], &[]).unwrap_err();
+        assert!(error.contains("cell 8"), "{}", error);
+        //Jumps into the middle of the Add, depending on the input
+        let error = optimize(&[3, 12, 1005, 12, 6, 1101, 1, 1, 12, 99, 99, 0, 0], &[]).unwrap_err();
+        assert!(error.contains("overlap"), "{}", error);
+        assert!(optimize(&[42], &[]).unwrap_err().contains("crash"));
+        assert!(optimize(&[1106, 0, -2, 99], &[])
+            .unwrap_err()
+            .contains("negative address -2"));
+    }
+}
@@ -1,6 +1,46 @@
+mod ascii;
+mod async_vm;
+mod coredump;
+mod coverage;
+mod decompile;
+mod device;
+mod diff;
+pub mod differential;
+mod disasm;
+mod format;
+pub mod ir;
+mod memory;
+mod patch;
+mod pool;
+mod scheduler;
+mod session;
+pub mod spec;
+mod vm;
+mod watch;
+pub use ascii::AsciiOutput;
+pub use async_vm::{AsyncError, AsyncIntcodeVm};
+pub use coredump::{panic_message, CoreDump, TraceEvent};
+pub use coverage::{BranchHits, Coverage};
+pub use decompile::decompile;
+pub use device::{Bus, Clock, Device, Framebuffer, Keyboard};
+pub use diff::{diff, DiffLine};
+pub use disasm::{assemble, disassemble, DisasmLine};
+pub use format::{decode_program, Format};
+pub use memory::{CowMemory, Memory};
+pub use patch::{load_program, Patch};
+pub use pool::VmPool;
+pub use scheduler::{SchedulePolicy, Scheduler, SchedulerState, Waiting};
+pub use session::{replay, Divergence, IoEvent, Recorder, Session};
+pub use vm::{
+    execute_with_stats, HandlerContext, InputPolicy, OpcodeHandler, OpcodeRegistry, ParamRule,
+    Snapshot, State, Stats, Vm, VmBuilder,
+};
+pub use watch::{Debugger, LogOutput, Piece, Stop, WatchExpr, Watchpoint};
+
 enum ParamMode {
     Position,
     Immediate,
+    Relative,
 }
 impl ParamMode {
     /*  ABCDE
@@ -12,13 +52,14 @@ impl ParamMode {
      *  A - mode of 3rd parameter,  0 == position mode,
      *                                   omitted due to being a leading zero
      */
-    fn from_instruction_code(instruction_code: i32, offset: u32) -> Self {
+    fn from_instruction_code(instruction_code: i64, offset: u32) -> Self {
         //Remove the opcode, then %10 to get a single digit
         //offset 0 is the third digit from the end, offset 1 is the fourth digit from the end, and so on.
-        let flag = ((instruction_code as u32) / (10u32.pow(2 + offset))) % 10;
+        let flag = ((instruction_code as u64) / (10u64.pow(2 + offset))) % 10;
         match flag {
             0 => ParamMode::Position,
             1 => ParamMode::Immediate,
+            2 => ParamMode::Relative,
             _ => panic!(
                 "Invalid flag in instruction code {} at offset {}",
                 instruction_code, offset
@@ -28,16 +69,38 @@ impl ParamMode {
 }
 struct Parameter {
     mode: ParamMode,
-    value: i32,
+    value: i64,
 }
 impl Parameter {
+    //Parameters are decoded right before their instruction runs, so relative ones can be turned into plain positions straight away
+    fn new(value: i64, mode: ParamMode, relative_base: i64) -> Self {
+        match mode {
+            ParamMode::Relative => Parameter {
+                value: relative_base + value,
+                mode: ParamMode::Position,
+            },
+            mode => Parameter { value, mode },
+        }
+    }
+
+    /* Where the parameter points to, for the parameters that get written to.
+     * (Writes have always ignored the mode, so immediate parameters still work here)
+     */
+    fn address(&self) -> usize {
+        if self.value < 0 {
+            panic!("Attempting to access a negative address: {}", self.value);
+        }
+        self.value as usize
+    }
+
     /* In immediate mode, a parameter is interpreted as a value - if the parameter is 50, its value is simply 50.
      * Position mode causes the parameter to be interpreted as a position - if the parameter is 50, its value is the value stored at address 50 in memory.
+     * Relative mode is position mode, counting from the relative base (see Parameter::new).
      */
-    fn actual_value(&self, intcode: &[i32]) -> i32 {
+    fn actual_value<M: Memory + ?Sized>(&self, intcode: &M) -> i64 {
         match self.mode {
-            ParamMode::Position => intcode[self.value as usize],
             ParamMode::Immediate => self.value,
+            _ => intcode.read(self.address()),
         }
     }
 }
@@ -58,110 +121,191 @@ enum Instruction {
     Out {
         src: Parameter,
     },
+    JumpIfTrue {
+        cond: Parameter,
+        target: Parameter,
+    },
+    JumpIfFalse {
+        cond: Parameter,
+        target: Parameter,
+    },
+    LessThan {
+        lhs: Parameter,
+        rhs: Parameter,
+        dest: Parameter,
+    },
+    Equals {
+        lhs: Parameter,
+        rhs: Parameter,
+        dest: Parameter,
+    },
+    AdjustRelativeBase {
+        offset: Parameter,
+    },
     Halt,
 }
 impl Instruction {
-    //Instructions are built from a slice (1 instruction and flags, followed by 0-3 parameters, depending on the type of instruction)
+    //Instructions are built from memory (1 instruction and flags, followed by 0-3 parameters, depending on the type of instruction)
     // We also keep track of the index in the program, and hope we'll only need to move forward through those instructions ^u^'
-    fn new(params: &[i32], index: &mut usize) -> Self {
-        let opcode = params[0] % 100;
+    fn new<M: Memory + ?Sized>(intcode: &M, index: &mut usize, relative_base: i64) -> Self {
+        let start = *index;
+        let code = intcode.read(start);
+        //Parameter n is the nth word after the instruction code, and its mode is the nth flag
+        let param = |n: usize| {
+            Parameter::new(
+                intcode.read(start + n),
+                ParamMode::from_instruction_code(code, n as u32 - 1),
+                relative_base,
+            )
+        };
+        let opcode = code % 100;
         match opcode {
             1 => {
                 *index += 4;
                 Instruction::Add {
-                    lhs: Parameter {
-                        value: params[1],
-                        mode: ParamMode::from_instruction_code(params[0], 0),
-                    },
-                    rhs: Parameter {
-                        value: params[2],
-                        mode: ParamMode::from_instruction_code(params[0], 1),
-                    },
-                    dest: Parameter {
-                        value: params[3],
-                        mode: ParamMode::Immediate,
-                    },
+                    lhs: param(1),
+                    rhs: param(2),
+                    dest: param(3),
                 }
             }
             2 => {
                 *index += 4;
                 Instruction::Mul {
-                    lhs: Parameter {
-                        value: params[1],
-                        mode: ParamMode::from_instruction_code(params[0], 0),
-                    },
-                    rhs: Parameter {
-                        value: params[2],
-                        mode: ParamMode::from_instruction_code(params[0], 1),
-                    },
-                    dest: Parameter {
-                        value: params[3],
-                        mode: ParamMode::Immediate,
-                    },
+                    lhs: param(1),
+                    rhs: param(2),
+                    dest: param(3),
                 }
             }
             3 => {
                 *index += 2;
-                Instruction::In {
-                    dest: Parameter {
-                        value: params[1],
-                        mode: ParamMode::Immediate,
-                    },
-                }
+                Instruction::In { dest: param(1) }
             }
             4 => {
                 *index += 2;
-                Instruction::Out {
-                    src: Parameter {
-                        value: params[1],
-                        mode: ParamMode::Immediate,
-                    },
+                Instruction::Out { src: param(1) }
+            }
+            5 => {
+                *index += 3;
+                Instruction::JumpIfTrue {
+                    cond: param(1),
+                    target: param(2),
+                }
+            }
+            6 => {
+                *index += 3;
+                Instruction::JumpIfFalse {
+                    cond: param(1),
+                    target: param(2),
+                }
+            }
+            7 => {
+                *index += 4;
+                Instruction::LessThan {
+                    lhs: param(1),
+                    rhs: param(2),
+                    dest: param(3),
                 }
             }
+            8 => {
+                *index += 4;
+                Instruction::Equals {
+                    lhs: param(1),
+                    rhs: param(2),
+                    dest: param(3),
+                }
+            }
+            9 => {
+                *index += 2;
+                Instruction::AdjustRelativeBase { offset: param(1) }
+            }
             99 => Instruction::Halt,
             _ => {
                 panic!(
                     "Attempting to create an invalid instruction type: \"{}\" (from instruction data: {})",
-                    opcode, params[0]
+                    opcode, code
                 );
             }
         }
     }
 }
 
+//A negative target would turn into a huge index and look like the program halted, it's as wrong as a negative address
+fn jump_target(target: i64) -> usize {
+    if target < 0 {
+        panic!("Attempting to jump to a negative address: {}", target);
+    }
+    target as usize
+}
+
 //Executing an instruction means modifying the intcode program, so keep a mutable reference to it!
-pub fn execute_at(
+pub fn execute_at<M: Memory + ?Sized>(
     index: &mut usize,
-    intcode: &mut [i32],
-    input_iter: &mut dyn Iterator<Item = &i32>,
-    output: &mut Vec<i32>,
+    relative_base: &mut i64,
+    intcode: &mut M,
+    input_iter: &mut dyn Iterator<Item = &i64>,
+    output: &mut Vec<i64>,
 ) {
     //Build an instruction from data at the current position, and move the index
-    let instruction = Instruction::new(&intcode[*index..], index);
+    let instruction = Instruction::new(intcode, index, *relative_base);
     match instruction {
         Instruction::Add { lhs, rhs, dest } => {
-            intcode[dest.value as usize] = lhs.actual_value(&intcode) + rhs.actual_value(&intcode);
+            intcode.write(
+                dest.address(),
+                lhs.actual_value(intcode) + rhs.actual_value(intcode),
+            );
         }
         Instruction::Mul { lhs, rhs, dest } => {
-            intcode[dest.value as usize] = lhs.actual_value(&intcode) * rhs.actual_value(&intcode);
+            intcode.write(
+                dest.address(),
+                lhs.actual_value(intcode) * rhs.actual_value(intcode),
+            );
         }
         Instruction::Halt => {
             //That's one way of making sure the program halts ¯\_(ツ)_/¯ (with the appropriate condition in execute)
             *index = intcode.len()
         }
         Instruction::In { dest } => {
-            intcode[dest.value as usize] = *input_iter
+            let value = *input_iter
                 .next() //Consumes an input
                 .expect("Input instruction cannot be executed without an input!");
+            intcode.write(dest.address(), value);
+        }
+        Instruction::Out { src } => output.push(src.actual_value(intcode)),
+        Instruction::JumpIfTrue { cond, target } => {
+            //The index already moved past the instruction, only overwrite it when we actually jump
+            if cond.actual_value(intcode) != 0 {
+                *index = jump_target(target.actual_value(intcode));
+            }
+        }
+        Instruction::JumpIfFalse { cond, target } => {
+            if cond.actual_value(intcode) == 0 {
+                *index = jump_target(target.actual_value(intcode));
+            }
+        }
+        Instruction::LessThan { lhs, rhs, dest } => {
+            intcode.write(
+                dest.address(),
+                (lhs.actual_value(intcode) < rhs.actual_value(intcode)) as i64,
+            );
+        }
+        Instruction::Equals { lhs, rhs, dest } => {
+            intcode.write(
+                dest.address(),
+                (lhs.actual_value(intcode) == rhs.actual_value(intcode)) as i64,
+            );
+        }
+        Instruction::AdjustRelativeBase { offset } => {
+            *relative_base += offset.actual_value(intcode)
         }
-        Instruction::Out { src } => output.push(intcode[src.value as usize]),
     }
 }
 
 //Execute the intcode program, consuming inputs from the input vector, and returning an output vector.
-pub fn execute(mut intcode: &mut [i32], input: &Vec<i32>) -> Vec<i32> {
+pub fn execute<M: Memory + ?Sized>(intcode: &mut M, input: &Vec<i64>) -> Vec<i64> {
     //Current execution index
     let mut index: usize = 0;
+    //Relative mode parameters count from there
+    let mut relative_base = 0;
     //Output vector
     let mut output = vec![];
     //Iterator on the input vector, can be mutated on execution
@@ -169,11 +313,29 @@ pub fn execute(mut intcode: &mut [i32], input: &Vec<i32>) -> Vec<i32> {
     //Stop running at the end of the program (a Halt instruction will move the execution index at the end of the program too)
     while index < intcode.len() {
         //Build and execute a single instruction
-        execute_at(&mut index, &mut intcode, &mut input_iter, &mut output);
+        execute_at(
+            &mut index,
+            &mut relative_base,
+            intcode,
+            &mut input_iter,
+            &mut output,
+        );
     }
     output
 }
 
+//Programs come as comma-separated text (trailing newline and all)
+pub fn parse_program(text: &str) -> Vec<i64> {
+    text.trim()
+        .split(',')
+        .map(|s| {
+            s.trim()
+                .parse::<i64>()
+                .unwrap_or_else(|_| panic!("Invalid intcode value: \"{}\"", s))
+        })
+        .collect()
+}
+
 // TESTS
 #[cfg(test)]
 mod tests {
@@ -215,4 +377,72 @@ mod tests {
         execute(&mut intcode, &vec![98]);
         assert_eq!(intcode, [98, 0, 1, 0, 6, 6, 99]);
     }
+
+    #[test]
+    fn day5_jumps_and_comparisons() {
+        let intcode = [3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+        assert_eq!(execute(&mut intcode.clone(), &vec![8]), vec![1]);
+        assert_eq!(execute(&mut intcode.clone(), &vec![7]), vec![0]);
+
+        let intcode = [3, 3, 1107, -1, 8, 3, 4, 3, 99];
+        assert_eq!(execute(&mut intcode.clone(), &vec![7]), vec![1]);
+        assert_eq!(execute(&mut intcode.clone(), &vec![9]), vec![0]);
+
+        let intcode = [3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+        assert_eq!(execute(&mut intcode.clone(), &vec![0]), vec![0]);
+        assert_eq!(execute(&mut intcode.clone(), &vec![5]), vec![1]);
+
+        let intcode = [3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
+        assert_eq!(execute(&mut intcode.clone(), &vec![0]), vec![0]);
+        assert_eq!(execute(&mut intcode.clone(), &vec![5]), vec![1]);
+    }
+
+    #[test]
+    fn day5_immediate_output() {
+        let intcode = [
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99,
+        ];
+        assert_eq!(execute(&mut intcode.clone(), &vec![7]), vec![999]);
+        assert_eq!(execute(&mut intcode.clone(), &vec![8]), vec![1000]);
+        assert_eq!(execute(&mut intcode.clone(), &vec![9]), vec![1001]);
+    }
+
+    #[test]
+    fn day9() {
+        //Outputs a copy of itself, using relative mode and memory past the end of the program
+        let quine = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        assert_eq!(execute(&mut quine.clone(), &vec![]), quine);
+
+        let mut intcode = vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0];
+        assert_eq!(execute(&mut intcode, &vec![]), vec![1219070632396864]);
+
+        let mut intcode = vec![104, 1125899906842624, 99];
+        assert_eq!(execute(&mut intcode, &vec![]), vec![1125899906842624]);
+
+        //Relative mode writes
+        let mut intcode = vec![109, 10, 203, 0, 204, 0, 99];
+        assert_eq!(execute(&mut intcode, &vec![42]), vec![42]);
+        assert_eq!(intcode[10], 42);
+    }
+
+    #[test]
+    #[should_panic(expected = "negative address")]
+    fn negative_address() {
+        execute(&mut vec![1, 0, 0, -1, 99], &vec![]);
+    }
+
+    #[test]
+    #[should_panic(expected = "jump to a negative address: -2")]
+    fn negative_jump() {
+        execute(&mut vec![1106, 0, -2, 99], &vec![]);
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(parse_program("1,0, -3,99\n"), vec![1, 0, -3, 99]);
+    }
 }
@@ -0,0 +1,181 @@
+use std::sync::Arc;
+
+/* Anything the computer can run a program in.
+ * Plain slices and arrays work as they always did (and panic when reading or writing out of bounds, like indexing would).
+ * Vectors and CowMemory grow instead: memory past the end of the program starts at 0, and can be written to.
+ */
+pub trait Memory {
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    fn read(&self, address: usize) -> i64;
+    fn write(&mut self, address: usize, value: i64);
+}
+impl Memory for [i64] {
+    fn len(&self) -> usize {
+        <[i64]>::len(self)
+    }
+    fn read(&self, address: usize) -> i64 {
+        self[address]
+    }
+    fn write(&mut self, address: usize, value: i64) {
+        self[address] = value;
+    }
+}
+impl<const N: usize> Memory for [i64; N] {
+    fn len(&self) -> usize {
+        N
+    }
+    fn read(&self, address: usize) -> i64 {
+        self[address]
+    }
+    fn write(&mut self, address: usize, value: i64) {
+        self[address] = value;
+    }
+}
+impl Memory for Vec<i64> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+    fn read(&self, address: usize) -> i64 {
+        *self.get(address).unwrap_or(&0)
+    }
+    fn write(&mut self, address: usize, value: i64) {
+        if address >= Vec::len(self) {
+            check_growth(address);
+            self.resize(address + 1, 0);
+        }
+        self[address] = value;
+    }
+}
+
+//Growing memory to wherever a buggy program decides to write would happily eat all the RAM there is
+const MAX_ADDRESS: usize = 1 << 24;
+fn check_growth(address: usize) {
+    if check_address(address).is_err() {
+        panic!(
+            "Attempting to write too far: {} (memory stops at {})",
+            address, MAX_ADDRESS
+        );
+    }
+}
+
+//Same limit for memory laid out by a file or a patch, where a typo is an error instead of a crash
+pub(crate) fn check_address(address: usize) -> Result<(), String> {
+    if address > MAX_ADDRESS {
+        Err(format!(
+            "Address {} is too far (memory stops at {})",
+            address, MAX_ADDRESS
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+//Words per page: small enough that a write only copies a little, big enough that cloning stays cheap
+const PAGE_SIZE: usize = 64;
+
+/* Memory split into shared pages: cloning only copies a few pointers, and a page gets copied the first time a clone writes to it.
+ * Thousands of runs of the same program (hello day 2) then share everything they never write to.
+ */
+#[derive(Clone)]
+pub struct CowMemory {
+    pages: Vec<Arc<Vec<i64>>>,
+    len: usize,
+}
+impl CowMemory {
+    pub fn new(program: &[i64]) -> Self {
+        CowMemory {
+            pages: program
+                .chunks(PAGE_SIZE)
+                .map(|page| {
+                    //Every page is full size, the last one is padded with zeros
+                    let mut page = page.to_vec();
+                    page.resize(PAGE_SIZE, 0);
+                    Arc::new(page)
+                })
+                .collect(),
+            len: program.len(),
+        }
+    }
+
+    //Back to a plain vector
+    pub fn to_vec(&self) -> Vec<i64> {
+        self.pages
+            .iter()
+            .flat_map(|page| page.iter().copied())
+            .take(self.len)
+            .collect()
+    }
+}
+impl Memory for CowMemory {
+    fn len(&self) -> usize {
+        self.len
+    }
+    fn read(&self, address: usize) -> i64 {
+        match self.pages.get(address / PAGE_SIZE) {
+            Some(page) if address < self.len => page[address % PAGE_SIZE],
+            _ => 0,
+        }
+    }
+    fn write(&mut self, address: usize, value: i64) {
+        if address >= self.len {
+            check_growth(address);
+            while self.pages.len() <= address / PAGE_SIZE {
+                self.pages.push(Arc::new(vec![0; PAGE_SIZE]));
+            }
+            self.len = address + 1;
+        }
+        //make_mut only copies the page if another clone still uses it
+        Arc::make_mut(&mut self.pages[address / PAGE_SIZE])[address % PAGE_SIZE] = value;
+    }
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::sync::Arc;
+    #[test]
+    fn copy_on_write() {
+        let program: Vec<i64> = (0..200).collect();
+        let original = CowMemory::new(&program);
+        let mut copy = original.clone();
+        copy.write(150, -1);
+        assert_eq!(original.read(150), 150);
+        assert_eq!(copy.read(150), -1);
+        //Only the page that was written to got copied
+        assert!(Arc::ptr_eq(&original.pages[0], &copy.pages[0]));
+        assert!(!Arc::ptr_eq(&original.pages[2], &copy.pages[2]));
+        assert_eq!(original.to_vec(), program);
+    }
+
+    #[test]
+    fn execute_in_cow_memory() {
+        let mut memory = CowMemory::new(&[1, 1, 1, 4, 99, 5, 6, 0, 99]);
+        execute(&mut memory, &vec![]);
+        assert_eq!(memory.to_vec(), vec![30, 1, 1, 4, 2, 5, 6, 0, 99]);
+    }
+
+    #[test]
+    fn growth() {
+        let mut memory = CowMemory::new(&[1, 2, 3]);
+        assert_eq!(memory.read(1000), 0);
+        memory.write(200, 7);
+        assert_eq!(memory.len(), 201);
+        assert_eq!(memory.read(200), 7);
+        assert_eq!(memory.read(100), 0);
+
+        let mut memory = vec![1, 2, 3];
+        assert_eq!(Memory::read(&memory, 10), 0);
+        memory.write(5, 7);
+        assert_eq!(memory, vec![1, 2, 3, 0, 0, 7]);
+    }
+
+    #[test]
+    #[should_panic(expected = "the len is 3 but the index is 3")]
+    fn slices_dont_grow() {
+        [1, 2, 3].write(3, 0);
+    }
+}
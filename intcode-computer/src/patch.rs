@@ -0,0 +1,170 @@
+use crate::disasm::assemble;
+use crate::format::decode_program;
+use crate::memory::check_address;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/* Changes to make to a program before running it. One edit per line, # starts a comment:
+ *  1=12, 2=2               plain words, address=value
+ *  20: ADD [1], 5, [3]     an instruction, assembled the way the disassembler prints it
+ *  30: IN [100]; HALT      several of them, one after the other
+ * Edits are applied in order, so a later one wins when they overlap.
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Patch {
+    //Where each edit starts, and the words it writes from there
+    pub edits: Vec<(usize, Vec<i64>)>,
+}
+impl Patch {
+    //The words of b that differ from a. Applying it to a gives b, unless b is shorter (patches never remove words)
+    pub fn between(a: &[i64], b: &[i64]) -> Self {
+        let edits = b
+            .iter()
+            .enumerate()
+            .filter(|&(address, value)| a.get(address) != Some(value))
+            .map(|(address, &value)| (address, vec![value]))
+            .collect();
+        Patch { edits }
+    }
+
+    //Memory grows to fit edits past the end of the program, up to where memory stops
+    pub fn apply(&self, program: &mut Vec<i64>) -> Result<(), String> {
+        for (address, words) in &self.edits {
+            check_address(address.saturating_add(words.len().saturating_sub(1)))?;
+            if program.len() < address + words.len() {
+                program.resize(address + words.len(), 0);
+            }
+            program[*address..address + words.len()].copy_from_slice(words);
+        }
+        Ok(())
+    }
+}
+impl fmt::Display for Patch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (address, words) in &self.edits {
+            for (offset, value) in words.iter().enumerate() {
+                writeln!(f, "{}={}", address + offset, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+impl FromStr for Patch {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut edits = vec![];
+        for line in s.lines() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let address = |text: &str| {
+                text.trim()
+                    .parse::<usize>()
+                    .map_err(|_| format!("Invalid address \"{}\" in \"{}\"", text, line))
+            };
+            match line.split_once(':') {
+                Some((start, code)) => {
+                    let mut words = vec![];
+                    for instruction in code.split(';') {
+                        words.extend(assemble(instruction)?);
+                    }
+                    edits.push((address(start)?, words));
+                }
+                None => {
+                    for pair in line.split(',') {
+                        let (at, value) = pair
+                            .split_once('=')
+                            .ok_or_else(|| format!("Expected address=value, got \"{}\"", pair))?;
+                        let value = value
+                            .trim()
+                            .parse::<i64>()
+                            .map_err(|_| format!("Invalid value \"{}\" in \"{}\"", value, line))?;
+                        edits.push((address(at)?, vec![value]));
+                    }
+                }
+            }
+        }
+        Ok(Patch { edits })
+    }
+}
+
+/* Reads a program from a file, and applies the patches to it, in order.
+ * That's how to run a puzzle input with its "restore the 1202 state" kind of fix applied.
+ * The file can be in any of the formats in format.rs, whichever it is gets detected.
+ */
+pub fn load_program<P: AsRef<Path>>(path: P, patches: &[Patch]) -> Result<Vec<i64>, String> {
+    let path = path.as_ref();
+    let bytes =
+        std::fs::read(path).map_err(|error| format!("Can't read {}: {}", path.display(), error))?;
+    let mut program =
+        decode_program(&bytes).map_err(|error| format!("{}: {}", path.display(), error))?;
+    for patch in patches {
+        patch.apply(&mut program)?;
+    }
+    Ok(program)
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn parse_and_apply() {
+        let patch: Patch = "# 1202 program alarm\n1=12, 2=2\n\n4: OUT [0]; HALT # print it\n"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            patch.edits,
+            vec![(1, vec![12]), (2, vec![2]), (4, vec![4, 0, 99])]
+        );
+        let mut program = vec![1, 0, 0, 0, 99];
+        patch.apply(&mut program).unwrap();
+        assert_eq!(program, vec![1, 12, 2, 0, 4, 0, 99]);
+        assert_eq!(execute(&mut program, &vec![]), vec![2]);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!("1=x".parse::<Patch>().is_err());
+        assert!("12".parse::<Patch>().is_err());
+        assert!("-1=3".parse::<Patch>().is_err());
+        assert!("4: NOPE".parse::<Patch>().is_err());
+
+        //Way past where memory stops: an error, not a huge allocation
+        let mut program = vec![99];
+        for far in ["1000000000000=1", "18446744073709551615: HALT; HALT"] {
+            let patch: Patch = far.parse().unwrap();
+            assert!(patch.apply(&mut program).is_err(), "{}", far);
+        }
+        assert_eq!(program, vec![99]);
+    }
+
+    #[test]
+    fn between() {
+        let (a, b) = (vec![1, 0, 0, 0, 99], vec![1, 12, 2, 0, 99, 7]);
+        let patch = Patch::between(&a, &b);
+        assert_eq!(patch.to_string(), "1=12\n2=2\n5=7\n");
+        let mut patched = a.clone();
+        patch
+            .to_string()
+            .parse::<Patch>()
+            .unwrap()
+            .apply(&mut patched)
+            .unwrap();
+        assert_eq!(patched, b);
+    }
+
+    #[test]
+    fn load() {
+        let path = std::env::temp_dir().join(format!("patch-load-{}.txt", std::process::id()));
+        std::fs::write(&path, "1,0,0,0,99\n").unwrap();
+        let alarm: Patch = "1=12, 2=2".parse().unwrap();
+        let tweak: Patch = "2=3".parse().unwrap();
+        let program = load_program(&path, &[alarm, tweak]);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(program, Ok(vec![1, 12, 3, 0, 99]));
+        assert!(load_program(&path, &[]).is_err());
+    }
+}
@@ -0,0 +1,138 @@
+use crate::CowMemory;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/* Runs lots of independent jobs on the same program, spread over a few threads.
+ * Every job gets its own copy of the program image, but copies are copy-on-write so they cost (almost) nothing.
+ */
+pub struct VmPool {
+    image: CowMemory,
+    threads: usize,
+}
+impl VmPool {
+    pub fn new(program: &[i64], threads: usize) -> Self {
+        assert!(threads > 0, "A pool needs at least one thread");
+        VmPool {
+            image: CowMemory::new(program),
+            threads,
+        }
+    }
+
+    //Runs every job, and returns the results in the same order as the jobs
+    pub fn map<J, R, F>(&self, jobs: &[J], job: F) -> Vec<R>
+    where
+        J: Sync,
+        R: Send,
+        F: Fn(&J, CowMemory) -> R + Sync,
+    {
+        self.run(jobs, job, |_| false)
+    }
+
+    /* Same as map, but stops as soon as a result matches the predicate.
+     * The results come back in order and end with the first match (the first in job order, not the first one found).
+     * No job is started once a match is known, but threads that already picked up a later job still run it:
+     * its result is dropped.
+     */
+    pub fn run_until<J, R, F, P>(&self, jobs: &[J], job: F, predicate: P) -> Vec<R>
+    where
+        J: Sync,
+        R: Send,
+        F: Fn(&J, CowMemory) -> R + Sync,
+        P: Fn(&R) -> bool + Sync,
+    {
+        self.run(jobs, job, predicate)
+    }
+
+    //The first job (in order) whose result matches, with its result
+    pub fn find<'a, J, R, F, P>(&self, jobs: &'a [J], job: F, predicate: P) -> Option<(&'a J, R)>
+    where
+        J: Sync,
+        R: Send,
+        F: Fn(&J, CowMemory) -> R + Sync,
+        P: Fn(&R) -> bool + Sync,
+    {
+        let mut results = self.run(jobs, job, &predicate);
+        match results.pop() {
+            Some(result) if predicate(&result) => Some((&jobs[results.len()], result)),
+            _ => None,
+        }
+    }
+
+    fn run<J, R, F, P>(&self, jobs: &[J], job: F, predicate: P) -> Vec<R>
+    where
+        J: Sync,
+        R: Send,
+        F: Fn(&J, CowMemory) -> R + Sync,
+        P: Fn(&R) -> bool + Sync,
+    {
+        //Jobs are handed out in order, so once job N matched, every job before it has already been started
+        let next_job = AtomicUsize::new(0);
+        let first_match = AtomicUsize::new(usize::MAX);
+        let mut results: Vec<(usize, R)> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut results = vec![];
+                        loop {
+                            let index = next_job.fetch_add(1, Ordering::SeqCst);
+                            if index >= jobs.len() || index > first_match.load(Ordering::SeqCst) {
+                                break;
+                            }
+                            let result = job(&jobs[index], self.image.clone());
+                            if predicate(&result) {
+                                first_match.fetch_min(index, Ordering::SeqCst);
+                            }
+                            results.push((index, result));
+                        }
+                        results
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("A pool thread panicked"))
+                .collect()
+        });
+        results.sort_by_key(|(index, _)| *index);
+        //Some threads might have finished jobs after the first match before noticing it: drop those
+        let first_match = first_match.into_inner();
+        results
+            .into_iter()
+            .filter(|(index, _)| *index <= first_match)
+            .map(|(_, result)| result)
+            .collect()
+    }
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    //Outputs input * 3
+    const TRIPLE: [i64; 10] = [3, 9, 102, 3, 9, 9, 4, 9, 99, 0];
+
+    fn triple(input: &i64, mut memory: CowMemory) -> i64 {
+        execute(&mut memory, &vec![*input])[0]
+    }
+
+    #[test]
+    fn results_in_order() {
+        let pool = VmPool::new(&TRIPLE, 4);
+        let jobs: Vec<i64> = (0..100).collect();
+        let results = pool.map(&jobs, triple);
+        assert_eq!(results, (0..100).map(|n| n * 3).collect::<Vec<i64>>());
+    }
+
+    #[test]
+    fn early_cancellation() {
+        let pool = VmPool::new(&TRIPLE, 3);
+        let jobs: Vec<i64> = (0..1000).collect();
+        let results = pool.run_until(&jobs, triple, |&result| result > 30);
+        assert_eq!(results, (0..12).map(|n| n * 3).collect::<Vec<i64>>());
+        assert_eq!(
+            pool.find(&jobs, triple, |&result| result % 7 == 6),
+            Some((&2, 6))
+        );
+        assert_eq!(pool.find(&jobs, triple, |&result| result < 0), None);
+    }
+}
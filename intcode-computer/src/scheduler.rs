@@ -0,0 +1,315 @@
+use crate::{State, Vm};
+use std::collections::VecDeque;
+use std::fmt;
+
+//Which VM gets to run next, and for how long
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SchedulePolicy {
+    //Each VM in turn runs that many instructions (less if it blocks or halts first)
+    RoundRobin { quantum: usize },
+    //Each VM in turn runs until it blocks on input or halts
+    UntilBlocked,
+    //The runnable VM with the highest priority runs, checked again after every instruction. Equal priorities take turns
+    Priority,
+}
+
+//A VM that can't go on: it's waiting for input and there is none on its channel (None if it doesn't read one)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Waiting {
+    pub vm: usize,
+    pub channel: Option<usize>,
+}
+impl fmt::Display for Waiting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.channel {
+            Some(channel) => write!(f, "VM {} is waiting on channel {}", self.vm, channel),
+            None => write!(
+                f,
+                "VM {} is waiting for input and reads no channel",
+                self.vm
+            ),
+        }
+    }
+}
+
+//Why Scheduler::run stopped: nothing can run anymore
+#[derive(Clone, Debug, PartialEq)]
+pub enum SchedulerState {
+    AllHalted,
+    //Every VM that didn't halt is blocked on input, sending something to one of the channels gets things going again
+    Deadlock(Vec<Waiting>),
+}
+
+struct Task {
+    vm: Vm,
+    input: Option<usize>,
+    output: Option<usize>,
+    priority: i64,
+    //What the VM's last step returned
+    state: State,
+}
+
+/* Runs several VMs connected by channels (queues of values), all on one thread.
+ * A VM takes values from its input channel one at a time, only when it needs one, so VMs can share a channel.
+ * Output goes to its output channel after each instruction, in the order it was produced
+ * (VMs without an output channel keep it, see vm_mut). Scheduling only depends on the policy,
+ * so the same VMs always run in the same order and give the same results.
+ */
+pub struct Scheduler {
+    tasks: Vec<Task>,
+    channels: Vec<VecDeque<i64>>,
+    policy: SchedulePolicy,
+    //Where turns carry on from, for the policies that take turns
+    next: usize,
+}
+impl Scheduler {
+    pub fn new(policy: SchedulePolicy) -> Self {
+        if let SchedulePolicy::RoundRobin { quantum } = policy {
+            assert!(
+                quantum > 0,
+                "A round-robin quantum needs at least one instruction"
+            );
+        }
+        Scheduler {
+            tasks: vec![],
+            channels: vec![],
+            policy,
+            next: 0,
+        }
+    }
+
+    //A new empty channel, and its id
+    pub fn channel(&mut self) -> usize {
+        self.channels.push(VecDeque::new());
+        self.channels.len() - 1
+    }
+
+    //Adds a VM reading from and writing to those channels, and gives back its id
+    pub fn add(&mut self, vm: Vm, input: Option<usize>, output: Option<usize>) -> usize {
+        for &channel in input.iter().chain(&output) {
+            assert!(channel < self.channels.len(), "No channel {}", channel);
+        }
+        self.tasks.push(Task {
+            vm,
+            input,
+            output,
+            priority: 0,
+            state: State::Running,
+        });
+        self.tasks.len() - 1
+    }
+
+    //Only used by the priority policy: higher goes first, VMs start at 0
+    pub fn set_priority(&mut self, vm: usize, priority: i64) {
+        self.tasks[vm].priority = priority;
+    }
+
+    pub fn vm(&self, vm: usize) -> &Vm {
+        &self.tasks[vm].vm
+    }
+
+    pub fn vm_mut(&mut self, vm: usize) -> &mut Vm {
+        &mut self.tasks[vm].vm
+    }
+
+    pub fn send(&mut self, channel: usize, value: i64) {
+        self.channels[channel].push_back(value);
+    }
+
+    //Values on the channel that no VM took yet
+    pub fn pending(&self, channel: usize) -> &VecDeque<i64> {
+        &self.channels[channel]
+    }
+
+    //Empties the channel
+    pub fn take(&mut self, channel: usize) -> Vec<i64> {
+        self.channels[channel].drain(..).collect()
+    }
+
+    fn runnable(&self, vm: usize) -> bool {
+        let task = &self.tasks[vm];
+        match task.state {
+            State::Running => true,
+            State::Halted => false,
+            State::WaitingForInput => {
+                !task.vm.pending_input().is_empty()
+                    || task
+                        .input
+                        .is_some_and(|channel| !self.channels[channel].is_empty())
+            }
+        }
+    }
+
+    //One instruction of that VM, fed from its channel if it's waiting for input
+    fn step(&mut self, vm: usize) -> State {
+        let task = &mut self.tasks[vm];
+        let mut state = task.vm.step();
+        if state == State::WaitingForInput {
+            let channels = &mut self.channels;
+            if let Some(value) = task.input.and_then(|channel| channels[channel].pop_front()) {
+                task.vm.push_input(value);
+                state = State::Running;
+            }
+        }
+        if let Some(channel) = task.output {
+            self.channels[channel].extend(task.vm.take_output());
+        }
+        task.state = state;
+        state
+    }
+
+    //The first runnable VM from where the turns are at, with the highest priority if that counts
+    fn pick(&self) -> Option<usize> {
+        let count = self.tasks.len();
+        let mut runnable = (0..count)
+            .map(|offset| (self.next + offset) % count)
+            .filter(|&vm| self.runnable(vm));
+        match self.policy {
+            SchedulePolicy::Priority => runnable.fold(None, |best: Option<usize>, vm| match best {
+                Some(best) if self.tasks[best].priority >= self.tasks[vm].priority => Some(best),
+                _ => Some(vm),
+            }),
+            _ => runnable.next(),
+        }
+    }
+
+    //Runs the VMs until they all halted, or the ones left are all waiting for input nobody is going to send
+    pub fn run(&mut self) -> SchedulerState {
+        while let Some(vm) = self.pick() {
+            match self.policy {
+                SchedulePolicy::RoundRobin { quantum } => {
+                    for _ in 0..quantum {
+                        if self.step(vm) != State::Running {
+                            break;
+                        }
+                    }
+                }
+                SchedulePolicy::UntilBlocked => while self.step(vm) == State::Running {},
+                SchedulePolicy::Priority => {
+                    self.step(vm);
+                }
+            }
+            self.next = (vm + 1) % self.tasks.len();
+        }
+        let waiting: Vec<Waiting> = self
+            .tasks
+            .iter()
+            .enumerate()
+            .filter(|(_, task)| task.state == State::WaitingForInput)
+            .map(|(vm, task)| Waiting {
+                vm,
+                channel: task.input,
+            })
+            .collect();
+        if waiting.is_empty() {
+            SchedulerState::AllHalted
+        } else {
+            SchedulerState::Deadlock(waiting)
+        }
+    }
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    const POLICIES: [SchedulePolicy; 4] = [
+        SchedulePolicy::RoundRobin { quantum: 1 },
+        SchedulePolicy::RoundRobin { quantum: 7 },
+        SchedulePolicy::UntilBlocked,
+        SchedulePolicy::Priority,
+    ];
+
+    #[test]
+    fn feedback_loop() {
+        //Day 7 part 2 example: 5 amplifiers in a loop
+        let program = [
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ];
+        for policy in POLICIES {
+            let mut scheduler = Scheduler::new(policy);
+            let channels: Vec<usize> = (0..5).map(|_| scheduler.channel()).collect();
+            for (amp, &phase) in [9, 8, 7, 6, 5].iter().enumerate() {
+                let vm = VmBuilder::new(&program).input(&[phase]).build();
+                scheduler.add(vm, Some(channels[amp]), Some(channels[(amp + 1) % 5]));
+            }
+            scheduler.send(channels[0], 0);
+            assert_eq!(scheduler.run(), SchedulerState::AllHalted);
+            assert_eq!(scheduler.take(channels[0]), vec![139629729], "{:?}", policy);
+        }
+    }
+
+    #[test]
+    fn order() {
+        let outputs = |policy, priorities: [i64; 2]| {
+            let mut scheduler = Scheduler::new(policy);
+            let shared = scheduler.channel();
+            for (vm, &priority) in priorities.iter().enumerate() {
+                let id = vm as i64;
+                scheduler.add(
+                    Vm::new(&[104, id, 104, id, 104, id, 99]),
+                    None,
+                    Some(shared),
+                );
+                scheduler.set_priority(vm, priority);
+            }
+            assert_eq!(scheduler.run(), SchedulerState::AllHalted);
+            scheduler.take(shared)
+        };
+        let quantum = SchedulePolicy::RoundRobin { quantum: 2 };
+        assert_eq!(outputs(quantum, [0, 0]), [0, 0, 1, 1, 0, 1]);
+        assert_eq!(
+            outputs(SchedulePolicy::UntilBlocked, [0, 0]),
+            [0, 0, 0, 1, 1, 1]
+        );
+        assert_eq!(
+            outputs(SchedulePolicy::Priority, [0, 0]),
+            [0, 1, 0, 1, 0, 1]
+        );
+        assert_eq!(
+            outputs(SchedulePolicy::Priority, [0, 1]),
+            [1, 1, 1, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn deadlock() {
+        //Each one echoes what the other one sends
+        let echo = [3, 0, 4, 0, 99];
+        for policy in POLICIES {
+            let mut scheduler = Scheduler::new(policy);
+            let (a, b) = (scheduler.channel(), scheduler.channel());
+            scheduler.add(Vm::new(&echo), Some(a), Some(b));
+            scheduler.add(Vm::new(&echo), Some(b), Some(a));
+            let state = scheduler.run();
+            assert_eq!(
+                state,
+                SchedulerState::Deadlock(vec![
+                    Waiting {
+                        vm: 0,
+                        channel: Some(a)
+                    },
+                    Waiting {
+                        vm: 1,
+                        channel: Some(b)
+                    },
+                ])
+            );
+            scheduler.send(a, 7);
+            assert_eq!(scheduler.run(), SchedulerState::AllHalted);
+            assert_eq!(scheduler.pending(a), &[7]);
+        }
+        let mut scheduler = Scheduler::new(SchedulePolicy::UntilBlocked);
+        scheduler.add(Vm::new(&echo), None, None);
+        match scheduler.run() {
+            SchedulerState::Deadlock(waiting) => {
+                assert_eq!(
+                    waiting[0].to_string(),
+                    "VM 0 is waiting for input and reads no channel"
+                )
+            }
+            state => panic!("{:?}", state),
+        }
+    }
+}
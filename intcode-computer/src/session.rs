@@ -0,0 +1,263 @@
+use crate::{State, Vm};
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+//Something that went in or out of the VM, and at which step (how many instructions had been executed before it)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IoEvent {
+    Input { step: u64, value: i64 },
+    Output { step: u64, value: i64 },
+}
+
+/* Every input consumed and every output produced during a run, in order.
+ * Saved as text, one event per line:
+ *  12 in 5
+ *  15 out 10
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Session {
+    pub events: Vec<IoEvent>,
+}
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for event in &self.events {
+            match event {
+                IoEvent::Input { step, value } => writeln!(f, "{} in {}", step, value)?,
+                IoEvent::Output { step, value } => writeln!(f, "{} out {}", step, value)?,
+            }
+        }
+        Ok(())
+    }
+}
+impl FromStr for Session {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut events = vec![];
+        for (number, line) in text.lines().enumerate() {
+            let invalid = || format!("Invalid session event on line {}: \"{}\"", number + 1, line);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+            if words.len() != 3 {
+                return Err(invalid());
+            }
+            let step = words[0].parse().map_err(|_| invalid())?;
+            let value = words[2].parse().map_err(|_| invalid())?;
+            events.push(match words[1] {
+                "in" => IoEvent::Input { step, value },
+                "out" => IoEvent::Output { step, value },
+                _ => return Err(invalid()),
+            });
+        }
+        Ok(Session { events })
+    }
+}
+
+//Drives a VM like usual, and writes down everything that goes in and out of it
+pub struct Recorder {
+    vm: Vm,
+    //Same as the VM's input queue, so we still know the values after the VM consumed them
+    pending: VecDeque<i64>,
+    session: Session,
+}
+impl Recorder {
+    pub fn new(vm: Vm) -> Self {
+        Recorder {
+            pending: vm.pending_input().clone(),
+            vm,
+            session: Session::default(),
+        }
+    }
+
+    //Read-only: input has to go through the recorder, or it wouldn't be recorded
+    pub fn vm(&self) -> &Vm {
+        &self.vm
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    pub fn push_input(&mut self, value: i64) {
+        self.pending.push_back(value);
+        self.vm.push_input(value);
+    }
+
+    pub fn take_output(&mut self) -> Vec<i64> {
+        self.vm.take_output()
+    }
+
+    pub fn step(&mut self) -> State {
+        //Input from the VM's input policy never goes through push_input
+        if let Some(value) = self.vm.fill_input() {
+            self.pending.push_back(value);
+        }
+        let step = self.vm.steps();
+        let queued = self.vm.pending_input().len();
+        let produced = self.vm.output().len();
+        let state = self.vm.step();
+        for _ in self.vm.pending_input().len()..queued {
+            let value = self.pending.pop_front().unwrap();
+            self.session.events.push(IoEvent::Input { step, value });
+        }
+        for &value in &self.vm.output()[produced..] {
+            self.session.events.push(IoEvent::Output { step, value });
+        }
+        state
+    }
+
+    pub fn run(&mut self) -> State {
+        loop {
+            match self.step() {
+                State::Running => {}
+                state => return state,
+            }
+        }
+    }
+
+    pub fn finish(self) -> (Vm, Session) {
+        (self.vm, self.session)
+    }
+}
+
+//Where a replay stopped matching its recording
+#[derive(Debug, PartialEq)]
+pub struct Divergence {
+    //Position of the event in the session
+    pub event: usize,
+    //None if the VM did more than what was recorded
+    pub expected: Option<IoEvent>,
+    //None if the VM halted (or ran out of recorded input) before getting there
+    pub actual: Option<IoEvent>,
+}
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Replay diverged at event {}: expected {:?}, got {:?}",
+            self.event, self.expected, self.actual
+        )
+    }
+}
+
+/* Runs a fresh VM (no input queued) through a recorded session: recorded inputs are fed back whenever it asks for one,
+ * and every input and output it goes through has to match the recording, step counts included.
+ * Gives the VM back if everything matched, or the first event that didn't.
+ */
+pub fn replay(vm: Vm, session: &Session) -> Result<Vm, Divergence> {
+    assert!(
+        vm.pending_input().is_empty(),
+        "A replay feeds its own input, the VM shouldn't have any queued"
+    );
+    let mut inputs = session.events.iter().filter_map(|event| match event {
+        IoEvent::Input { value, .. } => Some(*value),
+        IoEvent::Output { .. } => None,
+    });
+    let mut recorder = Recorder::new(vm);
+    let mut checked = 0;
+    loop {
+        let state = recorder.step();
+        //Compare whatever happened during that step with the recording
+        while checked < recorder.session.events.len() {
+            let actual = recorder.session.events[checked];
+            let expected = session.events.get(checked).copied();
+            if expected != Some(actual) {
+                return Err(Divergence {
+                    event: checked,
+                    expected,
+                    actual: Some(actual),
+                });
+            }
+            checked += 1;
+        }
+        match state {
+            State::Running => {}
+            State::WaitingForInput => match inputs.next() {
+                Some(value) => recorder.push_input(value),
+                None => break,
+            },
+            State::Halted => break,
+        }
+    }
+    if checked < session.events.len() {
+        return Err(Divergence {
+            event: checked,
+            expected: Some(session.events[checked]),
+            actual: None,
+        });
+    }
+    Ok(recorder.vm)
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::session::*;
+    //Reads numbers and outputs them doubled, until it reads a 0
+    const DOUBLER: [i64; 16] = [
+        3, 15, 1006, 15, 14, 102, 2, 15, 15, 4, 15, 1105, 1, 0, 99, 0,
+    ];
+
+    fn record(program: &[i64], inputs: &[i64]) -> Session {
+        let mut recorder = Recorder::new(Vm::new(program));
+        for &input in inputs {
+            recorder.run();
+            recorder.push_input(input);
+        }
+        recorder.run();
+        recorder.finish().1
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let session = record(&DOUBLER, &[4, 5]);
+        assert_eq!(
+            session.events,
+            vec![
+                IoEvent::Input { step: 0, value: 4 },
+                IoEvent::Output { step: 3, value: 8 },
+                IoEvent::Input { step: 5, value: 5 },
+                IoEvent::Output { step: 8, value: 10 },
+            ]
+        );
+        let vm = replay(Vm::new(&DOUBLER), &session)
+            .unwrap_or_else(|divergence| panic!("{}", divergence));
+        assert_eq!(vm.steps(), 10);
+        assert_eq!(vm.output(), [8, 10]);
+    }
+
+    #[test]
+    fn divergence() {
+        let session = record(&DOUBLER, &[4, 5]);
+        //Triple instead of double
+        let mut tripler = DOUBLER;
+        tripler[6] = 3;
+        assert_eq!(
+            replay(Vm::new(&tripler), &session).err(),
+            Some(Divergence {
+                event: 1,
+                expected: Some(IoEvent::Output { step: 3, value: 8 }),
+                actual: Some(IoEvent::Output { step: 3, value: 12 }),
+            })
+        );
+        //Stops reading input immediately
+        let mut quitter = DOUBLER;
+        quitter[0] = 99;
+        assert_eq!(
+            replay(Vm::new(&quitter), &session).err().unwrap().actual,
+            None
+        );
+    }
+
+    #[test]
+    fn save_and_load() {
+        let session = record(&DOUBLER, &[4, 5, 0]);
+        let text = session.to_string();
+        assert!(text.starts_with("0 in 4\n3 out 8\n"));
+        assert_eq!(text.parse::<Session>(), Ok(session));
+        assert!("0 in".parse::<Session>().is_err());
+        assert!("0 up 4".parse::<Session>().is_err());
+    }
+}
@@ -0,0 +1,284 @@
+/* Test cases for intcode programs, written as data instead of code (see specs/ and tests/specs.rs).
+ * A file holds any number of cases, each one starting with its name in brackets:
+ *  [day 2, 1 + 1 = 2]
+ *  program: 1,0,0,0,99
+ *  memory: 0=2
+ *
+ *  [echo]
+ *  program: 3,0,4,0,
+ *           99         long programs can go on over several lines, as long as the line before ends with a comma
+ *  input: 42
+ *  output: 42
+ *  steps: 3
+ *
+ *  [reads a negative address]
+ *  program: 1,0,0,-1,99
+ *  error: negative address
+ * memory is either address=value pairs, or the whole memory from address 0. error only has to be part of the panic message.
+ * Anything that isn't listed isn't checked. # starts a comment, at the start of a line or after a space.
+ * Names are taken whole though, so they can have a # of their own.
+ */
+use crate::{panic_message, State, VmBuilder};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+//Programs that run longer than that fail instead of hanging the tests
+const MAX_STEPS: u64 = 10_000_000;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Spec {
+    pub name: String,
+    pub program: Vec<i64>,
+    pub input: Vec<i64>,
+    pub output: Option<Vec<i64>>,
+    pub memory: Vec<(usize, i64)>,
+    pub steps: Option<u64>,
+    pub error: Option<String>,
+}
+impl Spec {
+    //Runs the program, and tells everything that didn't go as expected
+    pub fn check(&self) -> Result<(), String> {
+        let mut vm = VmBuilder::new(&self.program).input(&self.input).build();
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            while vm.steps() < MAX_STEPS {
+                match vm.step() {
+                    State::Running => {}
+                    State::Halted => return Ok(()),
+                    State::WaitingForInput => return Err("Ran out of input".to_string()),
+                }
+            }
+            Err(format!("Still running after {} steps", MAX_STEPS))
+        }));
+        let error = match result {
+            Ok(result) => result.err(),
+            Err(payload) => Some(panic_message(&*payload)),
+        };
+
+        let mut problems = vec![];
+        match (&self.error, &error) {
+            (None, Some(error)) => problems.push(format!("failed: {}", error)),
+            (Some(expected), None) => problems.push(format!(
+                "expected an error with \"{}\", but it halted",
+                expected
+            )),
+            (Some(expected), Some(error)) if !error.contains(expected.as_str()) => problems.push(
+                format!("expected an error with \"{}\", got \"{}\"", expected, error),
+            ),
+            _ => {}
+        }
+        if let Some(output) = &self.output {
+            if output.as_slice() != vm.output() {
+                problems.push(format!(
+                    "output: expected {:?}, got {:?}",
+                    output,
+                    vm.output()
+                ));
+            }
+        }
+        for &(address, value) in &self.memory {
+            let actual = vm.memory().get(address).copied().unwrap_or(0);
+            if actual != value {
+                problems.push(format!(
+                    "memory[{}]: expected {}, got {}",
+                    address, value, actual
+                ));
+            }
+        }
+        if let Some(steps) = self.steps {
+            if steps != vm.steps() {
+                problems.push(format!("steps: expected {}, got {}", steps, vm.steps()));
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("; "))
+        }
+    }
+}
+
+fn numbers(text: &str) -> Result<Vec<i64>, String> {
+    text.split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse::<i64>()
+                .map_err(|_| format!("Invalid number \"{}\"", s))
+        })
+        .collect()
+}
+
+fn memory(text: &str) -> Result<Vec<(usize, i64)>, String> {
+    if !text.contains('=') {
+        return Ok(numbers(text)?.into_iter().enumerate().collect());
+    }
+    text.split(',')
+        .map(|pair| {
+            let (address, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("Expected address=value, got \"{}\"", pair))?;
+            let address = address.trim().parse::<usize>();
+            let value = value.trim().parse::<i64>();
+            match (address, value) {
+                (Ok(address), Ok(value)) => Ok((address, value)),
+                _ => Err(format!("Invalid memory cell \"{}\"", pair)),
+            }
+        })
+        .collect()
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut previous = ' ';
+    for (index, c) in line.char_indices() {
+        if c == '#' && previous.is_whitespace() {
+            return &line[..index];
+        }
+        previous = c;
+    }
+    line
+}
+
+//Every case in a spec file, in order
+pub fn parse(text: &str) -> Result<Vec<Spec>, String> {
+    //The values are gathered first, since they can go on over several lines
+    let mut cases: Vec<(String, Vec<(String, String)>)> = vec![];
+    for (number, line) in text.lines().enumerate() {
+        let error = |message: String| format!("Line {}: {}", number + 1, message);
+        //Names are taken whole, up to the last ], and can only be followed by a comment
+        if let Some((name, rest)) = line
+            .trim()
+            .strip_prefix('[')
+            .and_then(|l| l.rsplit_once(']'))
+        {
+            if strip_comment(rest).trim().is_empty() {
+                cases.push((name.trim().to_string(), vec![]));
+                continue;
+            }
+        }
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (_, fields) = cases
+            .last_mut()
+            .ok_or_else(|| error("Expected a [name] before anything else".to_string()))?;
+        match fields.last_mut() {
+            Some((_, value)) if value.ends_with(',') => value.push_str(line),
+            _ => {
+                let (key, value) = line
+                    .split_once(':')
+                    .ok_or_else(|| error(format!("Expected key: value, got \"{}\"", line)))?;
+                fields.push((key.trim().to_string(), value.trim().to_string()));
+            }
+        }
+    }
+
+    cases
+        .into_iter()
+        .map(|(name, fields)| {
+            let mut spec = Spec {
+                name,
+                ..Spec::default()
+            };
+            for (key, value) in fields {
+                let error = |message: String| format!("[{}] {}: {}", spec.name, key, message);
+                match key.as_str() {
+                    "program" => spec.program = numbers(&value).map_err(error)?,
+                    "input" => spec.input = numbers(&value).map_err(error)?,
+                    "output" => spec.output = Some(numbers(&value).map_err(error)?),
+                    "memory" => spec.memory.extend(memory(&value).map_err(error)?),
+                    "steps" => {
+                        spec.steps = Some(
+                            value
+                                .parse()
+                                .map_err(|_| error(format!("Invalid step count \"{}\"", value)))?,
+                        )
+                    }
+                    "error" => spec.error = Some(value),
+                    _ => return Err(error("Unknown key".to_string())),
+                }
+            }
+            if spec.program.is_empty() {
+                return Err(format!("[{}] has no program", spec.name));
+            }
+            Ok(spec)
+        })
+        .collect()
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::spec::*;
+    const SPECS: &str = "
+        # Comments are fine anywhere
+        [add]
+        program: 1,0,0,0,
+                 99
+        memory: 2,0,0,0,99
+        steps: 2
+
+        [echo]
+        program: 3,0,4,0,99 # reads, then writes back
+        input: 42
+        output: 42
+        memory: 0=42
+
+        [crash]
+        program: 1,0,0,-1,99
+        error: negative address
+    ";
+
+    #[test]
+    fn parse_and_check() {
+        let specs = parse(SPECS).unwrap();
+        assert_eq!(specs.len(), 3);
+        assert_eq!(specs[0].program, vec![1, 0, 0, 0, 99]);
+        assert_eq!(specs[0].memory.len(), 5);
+        assert_eq!(specs[1].memory, vec![(0, 42)]);
+        assert_eq!(specs[2].error.as_deref(), Some("negative address"));
+        for spec in &specs {
+            assert_eq!(spec.check(), Ok(()), "{}", spec.name);
+        }
+    }
+
+    #[test]
+    fn failures() {
+        let specs = parse(
+            "[wrong]\nprogram: 3,0,4,0,99\ninput: 1\noutput: 2\nsteps: 7\n[hungry]\nprogram: 3,0,99\n[fine]\nprogram: 99\nerror: boom",
+        )
+        .unwrap();
+        assert_eq!(
+            specs[0].check(),
+            Err("output: expected [2], got [1]; steps: expected 7, got 3".to_string())
+        );
+        assert_eq!(
+            specs[1].check(),
+            Err("failed: Ran out of input".to_string())
+        );
+        assert_eq!(
+            specs[2].check(),
+            Err("expected an error with \"boom\", but it halted".to_string())
+        );
+    }
+
+    #[test]
+    fn hashes() {
+        let specs = parse(
+            "[issue #12]\nprogram: 1,0,0,-1,99 #crashes\nerror: negative address # -1\n#[no]",
+        )
+        .unwrap();
+        assert_eq!(specs.len(), 1);
+        assert_eq!(specs[0].name, "issue #12");
+        assert_eq!(specs[0].error.as_deref(), Some("negative address"));
+        assert_eq!(specs[0].check(), Ok(()));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(parse("program: 99").is_err());
+        assert!(parse("[x]\nprogram: 99\nsteps: many").is_err());
+        assert!(parse("[x]\nprogram: 99\nflavour: strawberry").is_err());
+        assert!(parse("[x]\ninput: 3").is_err());
+        assert!(parse("[x]\nprogram: 99\nmemory: 1=").is_err());
+    }
+}
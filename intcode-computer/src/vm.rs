@@ -0,0 +1,953 @@
+use crate::disasm::{decode, opcode_info};
+use crate::{
+    execute_at, panic_message, CoreDump, Instruction, Memory, ParamMode, Parameter, TraceEvent,
+};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+
+//What a custom opcode expects from each of its parameters
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParamRule {
+    //Any mode, the handler gets the value
+    Read,
+    //Position or relative mode only, the handler gets the address to write to
+    Write,
+}
+
+//Everything a custom opcode is allowed to touch while it runs
+pub struct HandlerContext<'a> {
+    pub memory: &'a mut Vec<i64>,
+    //Already moved past the instruction: overwrite it to jump somewhere else
+    pub ip: &'a mut usize,
+    pub relative_base: &'a mut i64,
+    pub input: &'a mut VecDeque<i64>,
+    pub output: &'a mut Vec<i64>,
+}
+
+/* An opcode the computer doesn't know about, plugged in from the outside (debug prints, host calls, ...).
+ * Parameters are decoded with the usual ABCDE flags, following the rules given by param_rule.
+ */
+pub trait OpcodeHandler {
+    fn opcode(&self) -> i64;
+    fn arity(&self) -> usize;
+    //Every parameter is read by default
+    fn param_rule(&self, _index: usize) -> ParamRule {
+        ParamRule::Read
+    }
+    //args holds one value per parameter: the value for Read parameters, the address for Write ones
+    fn execute(&mut self, args: &[i64], context: &mut HandlerContext);
+}
+
+//Custom opcodes a VM can run, indexed by opcode
+#[derive(Default)]
+pub struct OpcodeRegistry {
+    handlers: HashMap<i64, Box<dyn OpcodeHandler>>,
+}
+impl OpcodeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //Built-in opcodes can't be replaced: they never even look at the registry
+    pub fn register<H: OpcodeHandler + 'static>(&mut self, handler: H) {
+        let opcode = handler.opcode();
+        if opcode_info(opcode).is_some() {
+            panic!("Opcode {} is built-in and can't be registered", opcode);
+        }
+        if !(0..100).contains(&opcode) {
+            panic!("Opcode {} can't be used, opcodes are two digits", opcode);
+        }
+        if self.handlers.insert(opcode, Box::new(handler)).is_some() {
+            panic!("Opcode {} is already registered", opcode);
+        }
+    }
+}
+
+//Why the VM stopped running
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum State {
+    //Ready to execute the next instruction
+    Running,
+    //Stopped on an In instruction, push some input to carry on
+    WaitingForInput,
+    Halted,
+}
+
+//What an In instruction does when there is no input left
+pub enum InputPolicy {
+    //Panic, like execute_at does
+    Error,
+    //Stop with State::WaitingForInput, the same In runs again once some input is pushed
+    Pause,
+    //Read that value instead (day 23 NICs read -1 when their queue is empty)
+    Default(i64),
+    //Ask the closure for a value, None pauses
+    Provider(Box<dyn FnMut() -> Option<i64>>),
+}
+
+//What a VM did since it was built (or since the snapshot it was restored from was taken)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Stats {
+    //Instructions executed, by opcode
+    pub per_opcode: BTreeMap<i64, u64>,
+    //The highest address read, written, or executed (only kept with VmBuilder::stats(true), 0 otherwise)
+    pub max_address: usize,
+    pub inputs: u64,
+    pub outputs: u64,
+    //JT/JF whose condition held (even if the target is the next instruction), and custom opcodes moving the ip
+    pub jumps_taken: u64,
+}
+impl Stats {
+    pub fn steps(&self) -> u64 {
+        self.per_opcode.values().sum()
+    }
+}
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "steps: {}", self.steps())?;
+        for (&opcode, count) in &self.per_opcode {
+            match opcode_info(opcode) {
+                Some((mnemonic, _)) => writeln!(f, "{}: {}", mnemonic, count)?,
+                None => writeln!(f, "opcode {}: {}", opcode, count)?,
+            }
+        }
+        writeln!(f, "inputs: {}", self.inputs)?;
+        writeln!(f, "outputs: {}", self.outputs)?;
+        writeln!(f, "jumps taken: {}", self.jumps_taken)?;
+        writeln!(f, "highest address: {}", self.max_address)
+    }
+}
+
+//What Stats gets built from, cheap enough to update on every step of every VM
+#[derive(Clone, Debug, PartialEq)]
+struct Counters {
+    per_opcode: [u64; 100],
+    max_address: usize,
+    inputs: u64,
+    outputs: u64,
+    jumps_taken: u64,
+}
+impl Default for Counters {
+    fn default() -> Self {
+        Counters {
+            per_opcode: [0; 100],
+            max_address: 0,
+            inputs: 0,
+            outputs: 0,
+            jumps_taken: 0,
+        }
+    }
+}
+impl Counters {
+    fn stats(&self) -> Stats {
+        Stats {
+            per_opcode: (0..)
+                .zip(self.per_opcode.iter())
+                .filter(|&(_, &count)| count > 0)
+                .map(|(opcode, &count)| (opcode, count))
+                .collect(),
+            max_address: self.max_address,
+            inputs: self.inputs,
+            outputs: self.outputs,
+            jumps_taken: self.jumps_taken,
+        }
+    }
+}
+
+/* Everything a VM needs to go back to an earlier point of its run.
+ * Custom opcodes aren't part of it: they stay with the VM the snapshot gets restored in.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    memory: Vec<i64>,
+    ip: usize,
+    relative_base: i64,
+    input: VecDeque<i64>,
+    output: Vec<i64>,
+    steps: u64,
+    counters: Counters,
+}
+
+//An intcode computer that keeps its state between runs, so it can be paused and fed more input later
+pub struct Vm {
+    memory: Vec<i64>,
+    ip: usize,
+    relative_base: i64,
+    input: VecDeque<i64>,
+    output: Vec<i64>,
+    registry: OpcodeRegistry,
+    //Instructions executed so far
+    steps: u64,
+    counters: Counters,
+    //Also keep track of the highest address touched, which takes a second look at every instruction's parameters
+    max_addresses: bool,
+    //The last trace_len instructions executed, oldest first
+    trace: VecDeque<TraceEvent>,
+    trace_len: usize,
+    //Where to write a core dump if an instruction panics
+    core_file: Option<PathBuf>,
+    on_empty_input: InputPolicy,
+}
+impl Vm {
+    //A VM with only the built-in opcodes and no input
+    pub fn new(program: &[i64]) -> Self {
+        VmBuilder::new(program).build()
+    }
+
+    pub fn memory(&self) -> &[i64] {
+        &self.memory
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    //Built on demand from what the VM counts as it goes
+    pub fn stats(&self) -> Stats {
+        self.counters.stats()
+    }
+
+    //Input that was pushed but not consumed yet
+    pub fn pending_input(&self) -> &VecDeque<i64> {
+        &self.input
+    }
+
+    //Output that hasn't been taken yet
+    pub fn output(&self) -> &[i64] {
+        &self.output
+    }
+
+    pub fn push_input(&mut self, value: i64) {
+        self.input.push_back(value);
+    }
+
+    //Everything that was output since the last call
+    pub fn take_output(&mut self) -> Vec<i64> {
+        std::mem::take(&mut self.output)
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            memory: self.memory.clone(),
+            ip: self.ip,
+            relative_base: self.relative_base,
+            input: self.input.clone(),
+            output: self.output.clone(),
+            steps: self.steps,
+            counters: self.counters.clone(),
+        }
+    }
+
+    //Back to where the snapshot was taken, even if it came from another VM
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.memory.clone_from(&snapshot.memory);
+        self.ip = snapshot.ip;
+        self.relative_base = snapshot.relative_base;
+        self.input.clone_from(&snapshot.input);
+        self.output.clone_from(&snapshot.output);
+        self.steps = snapshot.steps;
+        self.counters.clone_from(&snapshot.counters);
+    }
+
+    pub fn trace(&self) -> &VecDeque<TraceEvent> {
+        &self.trace
+    }
+
+    //The VM's whole state, with the error that stopped it
+    pub fn core_dump(&self, error: &str) -> CoreDump {
+        CoreDump {
+            error: error.to_string(),
+            memory: self.memory.clone(),
+            ip: self.ip,
+            relative_base: self.relative_base,
+            steps: self.steps,
+            trace: self.trace.iter().cloned().collect(),
+            pending_input: self.input.iter().copied().collect(),
+            output: self.output.clone(),
+        }
+    }
+
+    /* Execute a single instruction.
+     * With a core file set, a panicking instruction first writes a core dump there (ip still pointing at it),
+     * then goes on panicking.
+     */
+    pub fn step(&mut self) -> State {
+        let path = match &self.core_file {
+            Some(path) => path.clone(),
+            None => return self.execute_step(),
+        };
+        let (ip, relative_base) = (self.ip, self.relative_base);
+        match catch_unwind(AssertUnwindSafe(|| self.execute_step())) {
+            Ok(state) => state,
+            Err(payload) => {
+                self.ip = ip;
+                self.relative_base = relative_base;
+                let dump = self.core_dump(&panic_message(&*payload));
+                if let Err(error) = std::fs::write(&path, dump.to_string()) {
+                    eprintln!(
+                        "Couldn't write the core dump to {}: {}",
+                        path.display(),
+                        error
+                    );
+                }
+                resume_unwind(payload)
+            }
+        }
+    }
+
+    fn execute_step(&mut self) -> State {
+        if self.ip >= self.memory.len() {
+            return State::Halted;
+        }
+        let code = self.memory[self.ip];
+        let opcode = code % 100;
+        if opcode == 3 && self.input.is_empty() {
+            //Decode it anyway: a broken In (invalid mode...) should panic like it would in execute_at, not wait forever
+            Instruction::new(&self.memory, &mut self.ip.clone(), self.relative_base);
+            if self.fill_input().is_none() {
+                if let InputPolicy::Error = self.on_empty_input {
+                    panic!("Input instruction cannot be executed without an input!");
+                }
+                //Don't move: the same In instruction runs again once there is some input
+                return State::WaitingForInput;
+            }
+        }
+        if self.trace_len > 0 {
+            if self.trace.len() == self.trace_len {
+                self.trace.pop_front();
+            }
+            let text = match decode(&self.memory, self.ip) {
+                Some(line) => line.text,
+                None => format!("DATA {}", code),
+            };
+            self.trace.push_back(TraceEvent {
+                step: self.steps,
+                ip: self.ip,
+                text,
+            });
+        }
+        if self.max_addresses {
+            let max_address = self.max_address(code);
+            self.counters.max_address = self.counters.max_address.max(max_address);
+        }
+        let ip = self.ip;
+        let (consumed, produced) = if opcode_info(opcode).is_some() {
+            //Built-in opcodes go straight to execute_at, and we drop whatever input it consumed
+            let mut consumed = 0;
+            let outputs = self.output.len();
+            execute_at(
+                &mut self.ip,
+                &mut self.relative_base,
+                &mut self.memory,
+                &mut self.input.iter().inspect(|_| consumed += 1),
+                &mut self.output,
+            );
+            self.input.drain(..consumed);
+            (consumed, self.output.len() - outputs)
+        } else {
+            let (inputs, outputs) = (self.input.len(), self.output.len());
+            self.execute_custom(code);
+            //Handlers can also push input or take output: only what went the usual way counts
+            (
+                inputs.saturating_sub(self.input.len()),
+                self.output.len().saturating_sub(outputs),
+            )
+        };
+        //Jumps show in where the ip ends up, except the ones that land right after themselves
+        let next = ip + 1 + self.arity(opcode);
+        let jumped = match opcode {
+            5 | 6 if self.ip == next => self.jumps_to_next(ip),
+            5 | 6 => true,
+            _ if opcode_info(opcode).is_some() => false,
+            _ => self.ip != next,
+        };
+        self.steps += 1;
+        let counters = &mut self.counters;
+        counters.per_opcode[opcode as usize] += 1;
+        counters.inputs += consumed as u64;
+        counters.outputs += produced as u64;
+        if jumped {
+            counters.jumps_taken += 1;
+        }
+        if self.ip >= self.memory.len() {
+            State::Halted
+        } else {
+            State::Running
+        }
+    }
+
+    //Whether the JT/JF at ip, that just ran, took its jump (to the next instruction): it doesn't write, so decoding it again gives the same condition
+    fn jumps_to_next(&self, ip: usize) -> bool {
+        match Instruction::new(&self.memory, &mut ip.clone(), self.relative_base) {
+            Instruction::JumpIfTrue { cond, .. } => cond.actual_value(&self.memory) != 0,
+            Instruction::JumpIfFalse { cond, .. } => cond.actual_value(&self.memory) == 0,
+            _ => false,
+        }
+    }
+
+    //When the next instruction is an In with nothing to read, queue the value the input policy comes up with (if any)
+    pub(crate) fn fill_input(&mut self) -> Option<i64> {
+        if !self.input.is_empty() || self.memory.get(self.ip).map(|code| code % 100) != Some(3) {
+            return None;
+        }
+        let value = match &mut self.on_empty_input {
+            InputPolicy::Error | InputPolicy::Pause => None,
+            InputPolicy::Default(value) => Some(*value),
+            InputPolicy::Provider(provider) => provider(),
+        }?;
+        self.input.push_back(value);
+        Some(value)
+    }
+
+    //Keep stepping until the program halts or needs more input
+    pub fn run(&mut self) -> State {
+        loop {
+            match self.step() {
+                State::Running => {}
+                state => return state,
+            }
+        }
+    }
+
+    //Same as run, along with the statistics of everything the VM executed so far
+    pub fn run_with_stats(&mut self) -> (State, Stats) {
+        let state = self.run();
+        (state, self.stats())
+    }
+
+    fn arity(&self, opcode: i64) -> usize {
+        match opcode_info(opcode) {
+            Some((_, arity)) => arity,
+            None => self
+                .registry
+                .handlers
+                .get(&opcode)
+                .map_or(0, |handler| handler.arity()),
+        }
+    }
+
+    //The highest address the instruction at ip touches: its own words, and the cells its parameters point to
+    fn max_address(&self, code: i64) -> usize {
+        let opcode = code % 100;
+        let arity = self.arity(opcode);
+        let mut max_address = self.ip + arity;
+        for offset in 0..arity {
+            let value = self.memory.get(self.ip + 1 + offset).copied().unwrap_or(0);
+            let address = match ParamMode::from_instruction_code(code, offset as u32) {
+                ParamMode::Position => value,
+                ParamMode::Relative => self.relative_base + value,
+                //Built-in opcodes write to the address whatever the mode
+                ParamMode::Immediate if is_write(opcode, offset) => value,
+                ParamMode::Immediate => continue,
+            };
+            if address >= 0 {
+                max_address = max_address.max(address as usize);
+            }
+        }
+        max_address
+    }
+
+    /* The addresses the instruction at the ip is about to write to, found by decoding it without running it.
+     * Negative ones are left out, running the instruction is what crashes on them.
+     * Decoding panics like step would on invalid opcodes or modes.
+     */
+    pub fn destinations(&self) -> Vec<usize> {
+        if self.ip >= self.memory.len() {
+            return vec![];
+        }
+        let code = self.memory[self.ip];
+        let opcode = code % 100;
+        let addresses = if opcode_info(opcode).is_some() {
+            match Instruction::new(&self.memory, &mut self.ip.clone(), self.relative_base) {
+                Instruction::Add { dest, .. }
+                | Instruction::Mul { dest, .. }
+                | Instruction::LessThan { dest, .. }
+                | Instruction::Equals { dest, .. }
+                | Instruction::In { dest } => vec![dest.value],
+                _ => vec![],
+            }
+        } else {
+            match self.registry.handlers.get(&opcode) {
+                Some(handler) => (0..handler.arity())
+                    .filter(|&offset| handler.param_rule(offset) == ParamRule::Write)
+                    .map(|offset| {
+                        let value = self.memory.get(self.ip + 1 + offset).copied().unwrap_or(0);
+                        let mode = ParamMode::from_instruction_code(code, offset as u32);
+                        Parameter::new(value, mode, self.relative_base).value
+                    })
+                    .collect(),
+                None => vec![],
+            }
+        };
+        addresses
+            .into_iter()
+            .filter(|&address| address >= 0)
+            .map(|address| address as usize)
+            .collect()
+    }
+
+    fn execute_custom(&mut self, code: i64) {
+        let opcode = code % 100;
+        let handler = self.registry.handlers.get_mut(&opcode).unwrap_or_else(|| {
+            panic!(
+                "Attempting to create an invalid instruction type: \"{}\" (from instruction data: {})",
+                opcode, code
+            )
+        });
+        let memory = &self.memory;
+        let (ip, relative_base) = (self.ip, self.relative_base);
+        let args: Vec<i64> = (0..handler.arity())
+            .map(|offset| {
+                let value = memory.read(ip + 1 + offset);
+                let mode = ParamMode::from_instruction_code(code, offset as u32);
+                match (handler.param_rule(offset), mode) {
+                    (ParamRule::Write, ParamMode::Immediate) => panic!(
+                        "Parameter {} of opcode {} is written to, it can't be in immediate mode",
+                        offset, opcode
+                    ),
+                    (ParamRule::Write, mode) => {
+                        Parameter::new(value, mode, relative_base).address() as i64
+                    }
+                    (ParamRule::Read, mode) => {
+                        Parameter::new(value, mode, relative_base).actual_value(memory)
+                    }
+                }
+            })
+            .collect();
+        self.ip += 1 + args.len();
+        handler.execute(
+            &args,
+            &mut HandlerContext {
+                memory: &mut self.memory,
+                ip: &mut self.ip,
+                relative_base: &mut self.relative_base,
+                input: &mut self.input,
+                output: &mut self.output,
+            },
+        );
+    }
+}
+
+//Whether that parameter of a built-in opcode is the address it writes to
+fn is_write(opcode: i64, offset: usize) -> bool {
+    match opcode {
+        1 | 2 | 7 | 8 => offset == 2,
+        3 => offset == 0,
+        _ => false,
+    }
+}
+
+//Runs a program to the end like execute does, and also says what it took to get there
+pub fn execute_with_stats(program: &[i64], input: &[i64]) -> (Vec<i64>, Stats) {
+    let mut vm = VmBuilder::new(program).input(input).stats(true).build();
+    if vm.run() == State::WaitingForInput {
+        panic!("Input instruction cannot be executed without an input!");
+    }
+    (vm.take_output(), vm.stats())
+}
+
+/* Sets up a VM: program, starting input, and any custom opcodes it should understand.
+ * Once the input runs out, In instructions follow the InputPolicy given to on_empty_input:
+ *  Error        panic, like execute does
+ *  Pause        the default: run and step return State::WaitingForInput until push_input gives them something
+ *  Default(v)   read v, the program never waits
+ *  Provider(f)  read whatever f returns, or pause if it returns None
+ * Values that come from a default or a provider count as consumed input in the stats, and get recorded by sessions.
+ */
+pub struct VmBuilder {
+    program: Vec<i64>,
+    input: Vec<i64>,
+    registry: OpcodeRegistry,
+    trace_len: usize,
+    core_file: Option<PathBuf>,
+    on_empty_input: InputPolicy,
+    max_addresses: bool,
+}
+impl VmBuilder {
+    pub fn new(program: &[i64]) -> Self {
+        VmBuilder {
+            program: program.to_vec(),
+            input: vec![],
+            registry: OpcodeRegistry::new(),
+            trace_len: 0,
+            core_file: None,
+            on_empty_input: InputPolicy::Pause,
+            max_addresses: false,
+        }
+    }
+
+    pub fn input(mut self, input: &[i64]) -> Self {
+        self.input = input.to_vec();
+        self
+    }
+
+    pub fn registry(mut self, registry: OpcodeRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    //Keep the last trace_len executed instructions around (for core dumps, mostly)
+    pub fn trace(mut self, trace_len: usize) -> Self {
+        self.trace_len = trace_len;
+        self
+    }
+
+    //Write a core dump to that file if the program crashes
+    pub fn core_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.core_file = Some(path.into());
+        self
+    }
+
+    pub fn on_empty_input(mut self, policy: InputPolicy) -> Self {
+        self.on_empty_input = policy;
+        self
+    }
+
+    /* Everything in Stats, the highest address included. Counting the rest is cheap enough to always be on,
+     * but that one slows every instruction down.
+     */
+    pub fn stats(mut self, full: bool) -> Self {
+        self.max_addresses = full;
+        self
+    }
+
+    pub fn build(self) -> Vm {
+        Vm {
+            memory: self.program,
+            ip: 0,
+            relative_base: 0,
+            input: self.input.into_iter().collect(),
+            output: vec![],
+            registry: self.registry,
+            steps: 0,
+            counters: Counters::default(),
+            max_addresses: self.max_addresses,
+            trace: VecDeque::new(),
+            trace_len: self.trace_len,
+            core_file: self.core_file,
+            on_empty_input: self.on_empty_input,
+        }
+    }
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    //Opcode 42: dest = lhs * 2 + rhs
+    struct DoubleAdd;
+    impl OpcodeHandler for DoubleAdd {
+        fn opcode(&self) -> i64 {
+            42
+        }
+        fn arity(&self) -> usize {
+            3
+        }
+        fn param_rule(&self, index: usize) -> ParamRule {
+            if index == 2 {
+                ParamRule::Write
+            } else {
+                ParamRule::Read
+            }
+        }
+        fn execute(&mut self, args: &[i64], context: &mut HandlerContext) {
+            context.memory[args[2] as usize] = args[0] * 2 + args[1];
+        }
+    }
+
+    //Opcode 50: outputs its parameter twice
+    struct DebugPrint;
+    impl OpcodeHandler for DebugPrint {
+        fn opcode(&self) -> i64 {
+            50
+        }
+        fn arity(&self) -> usize {
+            1
+        }
+        fn execute(&mut self, args: &[i64], context: &mut HandlerContext) {
+            context.output.push(args[0]);
+            context.output.push(args[0]);
+        }
+    }
+
+    #[test]
+    fn pause_and_resume() {
+        let mut vm = Vm::new(&[3, 0, 4, 0, 3, 0, 4, 0, 99]);
+        assert_eq!(vm.run(), State::WaitingForInput);
+        assert_eq!(vm.ip(), 0);
+        vm.push_input(3);
+        assert_eq!(vm.run(), State::WaitingForInput);
+        assert_eq!(vm.take_output(), vec![3]);
+        vm.push_input(4);
+        assert_eq!(vm.run(), State::Halted);
+        assert_eq!(vm.take_output(), vec![4]);
+        assert_eq!(vm.step(), State::Halted);
+    }
+
+    #[test]
+    fn snapshot_and_restore() {
+        let mut vm = Vm::new(&[3, 0, 4, 0, 3, 0, 4, 0, 99]);
+        vm.run();
+        let snapshot = vm.snapshot();
+        vm.push_input(1);
+        vm.run();
+        assert_eq!(vm.take_output(), vec![1]);
+        //Go back and take the other branch
+        vm.restore(&snapshot);
+        assert_eq!(vm.steps(), 0);
+        vm.push_input(2);
+        vm.run();
+        assert_eq!(vm.take_output(), vec![2]);
+        assert_eq!(vm.memory()[0], 2);
+    }
+
+    #[test]
+    fn core_dump() {
+        let path = std::env::temp_dir().join(format!("intcode-core-{}", std::process::id()));
+        let mut vm = VmBuilder::new(&[4, 6, 3, 7, 1, -1, 100, 0, 0, 99])
+            .input(&[5, 6])
+            .trace(2)
+            .core_file(&path)
+            .build();
+        let crash = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| vm.run()));
+        assert!(crash.is_err());
+        let dump: CoreDump = std::fs::read_to_string(&path).unwrap().parse().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(dump.error, "Attempting to access a negative address: -1");
+        //Pointing at the ADD that failed, not past it
+        assert_eq!(dump.ip, 4);
+        assert_eq!(dump.steps, 2);
+        assert_eq!(dump.pending_input, vec![6]);
+        assert_eq!(dump.output, vec![100]);
+        assert_eq!(dump.trace.len(), 2);
+        //The instruction that crashed is the last one traced
+        assert_eq!(dump.trace[0].text, "IN [7]");
+        assert_eq!(dump.trace[1].text, "ADD [-1], [100], [5]");
+        assert_eq!(dump.trace[1].ip, 4);
+        assert_eq!(dump.memory[7], 5);
+        assert_eq!(dump, vm.core_dump(&dump.error));
+        assert!("error: x\nip: 1".parse::<CoreDump>().is_err());
+    }
+
+    #[test]
+    fn same_as_execute() {
+        let program = [3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+        let mut intcode = program;
+        let output = execute(&mut intcode, &vec![5]);
+        let mut vm = VmBuilder::new(&program).input(&[5]).build();
+        assert_eq!(vm.run(), State::Halted);
+        assert_eq!(vm.take_output(), output);
+        assert_eq!(vm.memory(), intcode);
+    }
+
+    #[test]
+    fn custom_opcodes() {
+        let mut registry = OpcodeRegistry::new();
+        registry.register(DoubleAdd);
+        registry.register(DebugPrint);
+        let mut vm = VmBuilder::new(&[1042, 9, 3, 10, 50, 10, 150, 7, 99, 20, 0])
+            .registry(registry)
+            .build();
+        assert_eq!(vm.run(), State::Halted);
+        assert_eq!(vm.memory()[10], 43);
+        assert_eq!(vm.take_output(), vec![43, 43, 7, 7]);
+    }
+
+    #[test]
+    fn destinations() {
+        let mut registry = OpcodeRegistry::new();
+        registry.register(DoubleAdd);
+        registry.register(DebugPrint);
+        let mut vm = VmBuilder::new(&[1042, 9, 3, 10, 50, 10, 99, 0, 0, 20, 0])
+            .registry(registry)
+            .build();
+        assert_eq!(vm.destinations(), [10]);
+        vm.step();
+        assert!(vm.destinations().is_empty());
+
+        //Relative mode counts from the base, negative addresses are the step's problem
+        let mut vm = Vm::new(&[109, 5, 21101, 1, 2, -2, 1101, 1, 1, -1, 99]);
+        vm.step();
+        assert_eq!(vm.destinations(), [3]);
+        vm.step();
+        assert!(vm.destinations().is_empty());
+        assert_eq!(Vm::new(&[3, 7, 99]).destinations(), [7]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Opcode 2 is built-in")]
+    fn builtin_opcodes_are_reserved() {
+        struct FakeMul;
+        impl OpcodeHandler for FakeMul {
+            fn opcode(&self) -> i64 {
+                2
+            }
+            fn arity(&self) -> usize {
+                0
+            }
+            fn execute(&mut self, _args: &[i64], _context: &mut HandlerContext) {}
+        }
+        OpcodeRegistry::new().register(FakeMul);
+    }
+
+    #[test]
+    #[should_panic(expected = "opcodes are two digits")]
+    fn opcodes_fit_in_two_digits() {
+        struct Big;
+        impl OpcodeHandler for Big {
+            fn opcode(&self) -> i64 {
+                142
+            }
+            fn arity(&self) -> usize {
+                0
+            }
+            fn execute(&mut self, _args: &[i64], _context: &mut HandlerContext) {}
+        }
+        OpcodeRegistry::new().register(Big);
+    }
+
+    #[test]
+    #[should_panic(expected = "can't be in immediate mode")]
+    fn write_parameters_are_positions() {
+        let mut registry = OpcodeRegistry::new();
+        registry.register(DoubleAdd);
+        VmBuilder::new(&[10042, 1, 1, 0, 99])
+            .registry(registry)
+            .build()
+            .run();
+    }
+
+    #[test]
+    fn stats() {
+        let (output, stats) = execute_with_stats(&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50], &[]);
+        assert!(output.is_empty());
+        assert_eq!(
+            stats.per_opcode.into_iter().collect::<Vec<_>>(),
+            vec![(1, 1), (2, 1), (99, 1)]
+        );
+        assert_eq!(stats.max_address, 11);
+
+        //JF jumps on a 0 input, past an ADD that never runs
+        let program = [3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+        let (output, stats) = execute_with_stats(&program, &[0]);
+        assert_eq!(output, execute(&mut program.to_vec(), &vec![0]));
+        assert_eq!((stats.inputs, stats.outputs, stats.jumps_taken), (1, 1, 1));
+        assert_eq!((stats.steps(), stats.max_address), (4, 15));
+        assert!(!stats.per_opcode.contains_key(&1));
+        assert_eq!(execute_with_stats(&program, &[1]).1.jumps_taken, 0);
+        assert!(stats.to_string().contains("JF: 1\n"));
+    }
+
+    //Opcode 51: feeds the VM a 1 and throws away what it output so far
+    struct Meddler;
+    impl OpcodeHandler for Meddler {
+        fn opcode(&self) -> i64 {
+            51
+        }
+        fn arity(&self) -> usize {
+            0
+        }
+        fn execute(&mut self, _args: &[i64], context: &mut HandlerContext) {
+            context.input.push_back(1);
+            context.output.clear();
+        }
+    }
+
+    #[test]
+    fn stats_edge_cases() {
+        //A jump taken to where it would have gone anyway still counts
+        let (_, stats) = execute_with_stats(&[1105, 1, 3, 1106, 1, 0, 99], &[]);
+        assert_eq!(stats.jumps_taken, 1);
+
+        let mut registry = OpcodeRegistry::new();
+        registry.register(Meddler);
+        let mut vm = VmBuilder::new(&[104, 5, 51, 3, 20, 99])
+            .registry(registry)
+            .build();
+        let (state, stats) = vm.run_with_stats();
+        assert_eq!(state, State::Halted);
+        assert_eq!((stats.inputs, stats.outputs), (1, 1));
+        assert_eq!(vm.memory()[20], 1);
+    }
+
+    #[test]
+    fn stats_across_runs() {
+        let mut registry = OpcodeRegistry::new();
+        registry.register(DebugPrint);
+        let mut vm = VmBuilder::new(&[3, 20, 50, 20, 4, 20, 99])
+            .registry(registry)
+            .stats(true)
+            .build();
+        let snapshot = vm.snapshot();
+        assert_eq!(
+            vm.run_with_stats(),
+            (State::WaitingForInput, Stats::default())
+        );
+        vm.push_input(7);
+        let (state, stats) = vm.run_with_stats();
+        assert_eq!(state, State::Halted);
+        assert_eq!((stats.inputs, stats.outputs, stats.jumps_taken), (1, 3, 0));
+        assert_eq!((stats.per_opcode[&50], stats.max_address), (1, 20));
+        assert_eq!(vm.stats(), stats);
+        vm.restore(&snapshot);
+        assert_eq!(vm.stats(), Stats::default());
+        //Without asking for it, the highest address isn't kept
+        let (_, stats) = Vm::new(&[1, 0, 0, 50, 99]).run_with_stats();
+        assert_eq!((stats.steps(), stats.max_address), (2, 0));
+    }
+
+    #[test]
+    fn input_policies() {
+        //Adds up two inputs
+        let program = [3, 12, 3, 13, 1, 12, 13, 14, 4, 14, 99, 0, 0, 0, 0];
+        let run = |policy| {
+            let mut vm = VmBuilder::new(&program)
+                .input(&[5])
+                .on_empty_input(policy)
+                .build();
+            let state = vm.run();
+            (state, vm.take_output())
+        };
+        assert_eq!(run(InputPolicy::Pause), (State::WaitingForInput, vec![]));
+        assert_eq!(run(InputPolicy::Default(-1)), (State::Halted, vec![4]));
+        let mut next = 10;
+        let provider = InputPolicy::Provider(Box::new(move || {
+            next += 1;
+            Some(next)
+        }));
+        assert_eq!(run(provider), (State::Halted, vec![16]));
+        assert_eq!(
+            run(InputPolicy::Provider(Box::new(|| None))).0,
+            State::WaitingForInput
+        );
+        let error = std::panic::catch_unwind(|| run(InputPolicy::Error)).unwrap_err();
+        assert_eq!(
+            panic_message(&*error),
+            "Input instruction cannot be executed without an input!"
+        );
+
+        //The default value is recorded like any other input
+        let vm = VmBuilder::new(&program)
+            .on_empty_input(InputPolicy::Default(-1))
+            .build();
+        let mut recorder = Recorder::new(vm);
+        assert_eq!(recorder.run(), State::Halted);
+        let (vm, session) = recorder.finish();
+        assert_eq!(vm.stats().inputs, 2);
+        assert_eq!(
+            replay(Vm::new(&program), &session).map(|vm| vm.output().to_vec()),
+            Ok(vec![-2])
+        );
+    }
+}
@@ -0,0 +1,662 @@
+use crate::{State, Vm};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+/* Expressions checked against the VM after each step, like mem[223] > 1000 or produced && outputs == 5.
+ * Numbers are i64, comparisons and ! && || give 0 or 1, and anything but 0 counts as true.
+ * Same operators and precedence as in Rust (/ and % by 0 give 0), plus mem[address] to read memory (0 outside of it).
+ * Variables:
+ *  ip, rb, steps         where the VM is now
+ *  at, opcode            address and opcode of the instruction the last step executed
+ *  consumed, in          how many inputs the last step consumed (0 or 1), and the value it read
+ *  produced, out         how many outputs the last step produced (0 or 1), and the value it wrote
+ *  inputs, outputs       how many were consumed and produced since the VM started
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct WatchExpr {
+    node: Node,
+    //Shown as it was written
+    text: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Const(i64),
+    Var(Var),
+    Mem(Box<Node>),
+    Neg(Box<Node>),
+    Not(Box<Node>),
+    Binary(&'static str, Box<Node>, Box<Node>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Var {
+    Ip,
+    Rb,
+    Steps,
+    At,
+    Opcode,
+    Consumed,
+    In,
+    Produced,
+    Out,
+    Inputs,
+    Outputs,
+}
+const VARIABLES: [(&str, Var); 11] = [
+    ("ip", Var::Ip),
+    ("rb", Var::Rb),
+    ("steps", Var::Steps),
+    ("at", Var::At),
+    ("opcode", Var::Opcode),
+    ("consumed", Var::Consumed),
+    ("in", Var::In),
+    ("produced", Var::Produced),
+    ("out", Var::Out),
+    ("inputs", Var::Inputs),
+    ("outputs", Var::Outputs),
+];
+
+//Binary operators by precedence, loosest first
+const LEVELS: [&[&str]; 5] = [
+    &["||"],
+    &["&&"],
+    &["==", "!=", "<=", ">=", "<", ">"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+//Longest first, so <= isn't read as < then =
+const SYMBOLS: [&str; 18] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<", ">", "+", "-", "*", "/", "%", "!", "(", ")", "[", "]",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Symbol(&'static str),
+}
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Number(number) => write!(f, "{}", number),
+            Token::Name(name) => write!(f, "{}", name),
+            Token::Symbol(symbol) => write!(f, "{}", symbol),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut rest = text.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let length = if c.is_ascii_digit() {
+            let length = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let number = rest[..length]
+                .parse()
+                .map_err(|_| format!("{} is too big", &rest[..length]))?;
+            tokens.push(Token::Number(number));
+            length
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let length = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Name(rest[..length].to_string()));
+            length
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| rest.starts_with(*symbol))
+                .ok_or_else(|| format!("Unexpected \"{}\"", c))?;
+            tokens.push(Token::Symbol(symbol));
+            symbol.len()
+        };
+        rest = rest[length..].trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Symbol(s)) if s == symbol => Ok(()),
+            _ => Err(format!("Expected \"{}\"", symbol)),
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Node, String> {
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(Token::Symbol(symbol)) = self.tokens.get(self.position) {
+            if !LEVELS[level].contains(symbol) {
+                break;
+            }
+            let symbol = *symbol;
+            self.position += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = Node::Binary(symbol, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Some(Token::Number(number)) => Ok(Node::Const(number)),
+            Some(Token::Symbol("-")) => Ok(Node::Neg(Box::new(self.unary()?))),
+            Some(Token::Symbol("!")) => Ok(Node::Not(Box::new(self.unary()?))),
+            Some(Token::Symbol("(")) => {
+                let node = self.binary(0)?;
+                self.expect(")")?;
+                Ok(node)
+            }
+            Some(Token::Name(name)) if name == "mem" => {
+                self.expect("[")?;
+                let address = self.binary(0)?;
+                self.expect("]")?;
+                Ok(Node::Mem(Box::new(address)))
+            }
+            Some(Token::Name(name)) => VARIABLES
+                .iter()
+                .find(|(variable, _)| *variable == name)
+                .map(|&(_, var)| Node::Var(var))
+                .ok_or_else(|| {
+                    let names: Vec<&str> = VARIABLES.iter().map(|(name, _)| *name).collect();
+                    format!(
+                        "Unknown variable \"{}\", expected mem[...] or one of {}",
+                        name,
+                        names.join(", ")
+                    )
+                }),
+            Some(Token::Symbol(symbol)) => Err(format!("Unexpected \"{}\"", symbol)),
+            None => Err("Unexpected end of expression".to_string()),
+        }
+    }
+}
+
+impl FromStr for WatchExpr {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            position: 0,
+        };
+        let node = parser
+            .binary(0)
+            .map_err(|error| format!("{} in \"{}\"", error, s.trim()))?;
+        if let Some(token) = parser.tokens.get(parser.position) {
+            return Err(format!("Unexpected \"{}\" in \"{}\"", token, s.trim()));
+        }
+        Ok(WatchExpr {
+            node,
+            text: s.trim().to_string(),
+        })
+    }
+}
+impl fmt::Display for WatchExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+//What the last step did, for the variables that are about it
+#[derive(Clone, Copy, Debug, Default)]
+struct LastStep {
+    at: usize,
+    opcode: i64,
+    consumed: i64,
+    input: i64,
+    produced: i64,
+    output: i64,
+}
+
+impl WatchExpr {
+    fn evaluate(&self, vm: &Vm, last: &LastStep) -> i64 {
+        evaluate(&self.node, vm, last)
+    }
+}
+
+fn evaluate(node: &Node, vm: &Vm, last: &LastStep) -> i64 {
+    let truth = |value: bool| value as i64;
+    match node {
+        Node::Const(value) => *value,
+        Node::Var(var) => match var {
+            Var::Ip => vm.ip() as i64,
+            Var::Rb => vm.relative_base(),
+            Var::Steps => vm.steps() as i64,
+            Var::At => last.at as i64,
+            Var::Opcode => last.opcode,
+            Var::Consumed => last.consumed,
+            Var::In => last.input,
+            Var::Produced => last.produced,
+            Var::Out => last.output,
+            Var::Inputs => vm.stats().inputs as i64,
+            Var::Outputs => vm.stats().outputs as i64,
+        },
+        Node::Mem(address) => {
+            let address = evaluate(address, vm, last);
+            usize::try_from(address)
+                .ok()
+                .and_then(|address| vm.memory().get(address))
+                .copied()
+                .unwrap_or(0)
+        }
+        Node::Neg(value) => evaluate(value, vm, last).wrapping_neg(),
+        Node::Not(value) => truth(evaluate(value, vm, last) == 0),
+        //Both sides of && and || are evaluated, nothing here has side effects anyway
+        Node::Binary(symbol, lhs, rhs) => {
+            let (lhs, rhs) = (evaluate(lhs, vm, last), evaluate(rhs, vm, last));
+            match *symbol {
+                "||" => truth(lhs != 0 || rhs != 0),
+                "&&" => truth(lhs != 0 && rhs != 0),
+                "==" => truth(lhs == rhs),
+                "!=" => truth(lhs != rhs),
+                "<=" => truth(lhs <= rhs),
+                ">=" => truth(lhs >= rhs),
+                "<" => truth(lhs < rhs),
+                ">" => truth(lhs > rhs),
+                "+" => lhs.wrapping_add(rhs),
+                "-" => lhs.wrapping_sub(rhs),
+                "*" => lhs.wrapping_mul(rhs),
+                "/" => lhs.checked_div(rhs).unwrap_or(0),
+                _ => lhs.checked_rem(rhs).unwrap_or(0),
+            }
+        }
+    }
+}
+
+//A piece of a log message: plain text, or an expression written between braces
+#[derive(Clone, Debug, PartialEq)]
+pub enum Piece {
+    Text(String),
+    Expr(WatchExpr),
+}
+
+/* What gets checked after each step, written the way the debugger takes them:
+ *  break <expr>            stop when expr is true
+ *  watch <expr>            stop when the value of expr changes
+ *  log <expr> "<message>"  print the message when expr is true, without stopping. {expr} in the message gets replaced by its value
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Watchpoint {
+    Break(WatchExpr),
+    Watch(WatchExpr),
+    Log(WatchExpr, Vec<Piece>),
+}
+impl FromStr for Watchpoint {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (kind, rest) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+        match kind {
+            "break" => Ok(Watchpoint::Break(rest.parse()?)),
+            "watch" => Ok(Watchpoint::Watch(rest.parse()?)),
+            "log" => {
+                let (condition, message) = rest
+                    .split_once('"')
+                    .and_then(|(condition, message)| Some((condition, message.strip_suffix('"')?)))
+                    .ok_or_else(|| "A log needs a message between quotes at the end".to_string())?;
+                Ok(Watchpoint::Log(
+                    condition.parse()?,
+                    message_pieces(message)?,
+                ))
+            }
+            _ => Err(format!(
+                "Unknown point \"{}\", expected break, watch or log",
+                kind
+            )),
+        }
+    }
+}
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Watchpoint::Break(condition) => write!(f, "break {}", condition),
+            Watchpoint::Watch(expr) => write!(f, "watch {}", expr),
+            Watchpoint::Log(condition, message) => {
+                write!(f, "log {} \"", condition)?;
+                for piece in message {
+                    match piece {
+                        Piece::Text(text) => write!(f, "{}", text)?,
+                        Piece::Expr(expr) => write!(f, "{{{}}}", expr)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+        }
+    }
+}
+
+fn message_pieces(mut message: &str) -> Result<Vec<Piece>, String> {
+    let mut pieces = vec![];
+    while let Some((text, rest)) = message.split_once('{') {
+        let (expr, rest) = rest
+            .split_once('}')
+            .ok_or_else(|| format!("Missing }} after \"{{{}\"", rest))?;
+        pieces.push(Piece::Text(text.to_string()));
+        pieces.push(Piece::Expr(expr.parse()?));
+        message = rest;
+    }
+    pieces.push(Piece::Text(message.to_string()));
+    Ok(pieces)
+}
+
+//Why Debugger::step or run gave control back
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stop {
+    //No point stopped it: same as what the VM's step or run returned
+    State(State),
+    Break(usize),
+    Watch { id: usize, old: i64, new: i64 },
+}
+
+//Where logpoint messages go, as soon as they fire
+pub enum LogOutput {
+    //Printed on stdout, the default
+    Print,
+    Callback(Box<dyn FnMut(&str)>),
+    //Kept until take_log, for when nothing should be printed (tests, mostly)
+    Collect(Vec<String>),
+}
+
+/* Runs a VM while checking points after each step.
+ * Points get an id when they are added, which stays the same when other points are deleted.
+ * All of them are checked after every step, in the order they were added: logs all get written,
+ * and the first break or watch that triggers is the one reported.
+ */
+pub struct Debugger {
+    vm: Vm,
+    //Watches also keep the value they had after the last step
+    points: BTreeMap<usize, (Watchpoint, i64)>,
+    next_id: usize,
+    last: LastStep,
+    log: LogOutput,
+}
+impl Debugger {
+    pub fn new(vm: Vm) -> Self {
+        Debugger {
+            last: LastStep {
+                at: vm.ip(),
+                ..LastStep::default()
+            },
+            vm,
+            points: BTreeMap::new(),
+            next_id: 1,
+            log: LogOutput::Print,
+        }
+    }
+
+    pub fn log_to(mut self, output: LogOutput) -> Self {
+        self.log = output;
+        self
+    }
+
+    pub fn vm(&self) -> &Vm {
+        &self.vm
+    }
+
+    //To push input, mostly
+    pub fn vm_mut(&mut self) -> &mut Vm {
+        &mut self.vm
+    }
+
+    pub fn add(&mut self, point: Watchpoint) -> usize {
+        let value = match &point {
+            Watchpoint::Watch(expr) => self.evaluate(expr),
+            _ => 0,
+        };
+        let id = self.next_id;
+        self.next_id += 1;
+        self.points.insert(id, (point, value));
+        id
+    }
+
+    pub fn delete(&mut self, id: usize) -> Option<Watchpoint> {
+        self.points.remove(&id).map(|(point, _)| point)
+    }
+
+    pub fn points(&self) -> impl Iterator<Item = (usize, &Watchpoint)> {
+        self.points.iter().map(|(&id, (point, _))| (id, point))
+    }
+
+    //Value of the expression right now
+    pub fn evaluate(&self, expr: &WatchExpr) -> i64 {
+        expr.evaluate(&self.vm, &self.last)
+    }
+
+    //Every message logged since the last call, when they are collected
+    pub fn take_log(&mut self) -> Vec<String> {
+        match &mut self.log {
+            LogOutput::Collect(messages) => std::mem::take(messages),
+            _ => vec![],
+        }
+    }
+
+    pub fn step(&mut self) -> Stop {
+        //Queue what the input policy gives first, so we can tell which value the step reads
+        self.vm.fill_input();
+        let at = self.vm.ip();
+        let opcode = self.vm.memory().get(at).map_or(0, |code| code % 100);
+        let steps = self.vm.steps();
+        let input = self.vm.pending_input().front().copied().unwrap_or(0);
+        let (inputs, outputs) = (self.vm.stats().inputs, self.vm.stats().outputs);
+        let state = self.vm.step();
+        if self.vm.steps() == steps {
+            return Stop::State(state);
+        }
+        let consumed = (self.vm.stats().inputs - inputs) as i64;
+        let produced = (self.vm.stats().outputs - outputs) as i64;
+        self.last = LastStep {
+            at,
+            opcode,
+            consumed,
+            input: if consumed > 0 { input } else { 0 },
+            produced,
+            output: match produced {
+                0 => 0,
+                _ => self.vm.output().last().copied().unwrap_or(0),
+            },
+        };
+
+        let mut stop = Stop::State(state);
+        let (vm, last) = (&self.vm, &self.last);
+        for (&id, (point, value)) in self.points.iter_mut() {
+            let triggered = match point {
+                Watchpoint::Break(condition) => {
+                    (condition.evaluate(vm, last) != 0).then_some(Stop::Break(id))
+                }
+                Watchpoint::Watch(expr) => {
+                    let old = *value;
+                    *value = expr.evaluate(vm, last);
+                    (*value != old).then_some(Stop::Watch {
+                        id,
+                        old,
+                        new: *value,
+                    })
+                }
+                Watchpoint::Log(condition, message) => {
+                    if condition.evaluate(vm, last) != 0 {
+                        let text: String = message
+                            .iter()
+                            .map(|piece| match piece {
+                                Piece::Text(text) => text.clone(),
+                                Piece::Expr(expr) => expr.evaluate(vm, last).to_string(),
+                            })
+                            .collect();
+                        match &mut self.log {
+                            LogOutput::Print => println!("{}", text),
+                            LogOutput::Callback(callback) => callback(&text),
+                            LogOutput::Collect(messages) => messages.push(text),
+                        }
+                    }
+                    None
+                }
+            };
+            if let (Stop::State(_), Some(triggered)) = (stop, triggered) {
+                stop = triggered;
+            }
+        }
+        stop
+    }
+
+    //Keep stepping until a point stops it, or the program halts or needs more input
+    pub fn run(&mut self) -> Stop {
+        loop {
+            match self.step() {
+                Stop::State(State::Running) => {}
+                stop => return stop,
+            }
+        }
+    }
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    //Outputs a copy of itself, mem[100] counts the outputs
+    const QUINE: [i64; 16] = [
+        109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+    ];
+    //Reads numbers and outputs them doubled, until it reads a 0
+    const DOUBLER: [i64; 16] = [
+        3, 15, 1006, 15, 14, 102, 2, 15, 15, 4, 15, 1105, 1, 0, 99, 0,
+    ];
+
+    fn value(expr: &str) -> i64 {
+        let debugger = Debugger::new(Vm::new(&QUINE));
+        debugger.evaluate(&expr.parse().unwrap())
+    }
+
+    #[test]
+    fn expressions() {
+        assert_eq!(value("1 + 2 * 3 - -4"), 11);
+        assert_eq!(value("(1 + 2) * 3 % 5"), 4);
+        assert_eq!(value("1 < 2 && 2 <= 2 || 0"), 1);
+        assert_eq!(
+            value("!(mem[0] == 109) + mem[2] + mem[-1] + mem[1000]"),
+            204
+        );
+        assert_eq!(value("7 / 0 + ip + rb + steps + outputs"), 0);
+        for invalid in [
+            "", "1 +", "mem 3", "mem[3", "(1", "1 2", "x > 3", "1 = 2", "#",
+        ] {
+            assert!(invalid.parse::<WatchExpr>().is_err(), "{}", invalid);
+        }
+        for point in [
+            "break mem[223] > 1000",
+            "watch rb",
+            "log produced \"out: {out}, at {at}\"",
+        ] {
+            assert_eq!(point.parse::<Watchpoint>().unwrap().to_string(), point);
+        }
+        assert!("log produced out".parse::<Watchpoint>().is_err());
+        assert!("log 1 \"{out\"".parse::<Watchpoint>().is_err());
+        assert!("stop 1".parse::<Watchpoint>().is_err());
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut debugger = Debugger::new(Vm::new(&QUINE)).log_to(LogOutput::Collect(vec![]));
+        let fifth = debugger.add("break produced && outputs == 5".parse().unwrap());
+        let counter = debugger.add("break mem[100] > 9".parse().unwrap());
+        let log = debugger.add("log opcode == 4 \"#{outputs}: {out}\"".parse().unwrap());
+        assert_eq!(debugger.run(), Stop::Break(fifth));
+        assert_eq!(debugger.vm().output(), &QUINE[..5]);
+        assert_eq!(
+            debugger.take_log(),
+            ["#1: 109", "#2: 1", "#3: 204", "#4: -1", "#5: 1001"]
+        );
+        assert_eq!(debugger.run(), Stop::Break(counter));
+        assert_eq!((debugger.vm().memory()[100], debugger.vm().ip()), (10, 8));
+        //Stops on every step while it holds
+        assert_eq!(debugger.step(), Stop::Break(counter));
+        assert!(debugger.delete(counter).is_some());
+        assert_eq!(debugger.run(), Stop::State(State::Halted));
+        assert_eq!(debugger.vm().output(), QUINE);
+        assert_eq!(debugger.take_log().len(), 11);
+        assert_eq!(
+            debugger.points().map(|(id, _)| id).collect::<Vec<_>>(),
+            [fifth, log]
+        );
+        assert_eq!(debugger.step(), Stop::State(State::Halted));
+    }
+
+    #[test]
+    fn log_callback() {
+        let lines = Rc::new(RefCell::new(vec![]));
+        let sink = lines.clone();
+        let mut debugger = Debugger::new(Vm::new(&QUINE)).log_to(LogOutput::Callback(Box::new(
+            move |line: &str| sink.borrow_mut().push(line.to_string()),
+        )));
+        let second = debugger.add("break produced && outputs == 2".parse().unwrap());
+        debugger.add("log produced \"{out}\"".parse().unwrap());
+        //Already there when the break stops it
+        assert_eq!(debugger.run(), Stop::Break(second));
+        assert_eq!(*lines.borrow(), ["109", "1"]);
+        assert!(debugger.take_log().is_empty());
+    }
+
+    #[test]
+    fn watches_and_input() {
+        let vm = VmBuilder::new(&DOUBLER).input(&[4, 5]).build();
+        let mut debugger = Debugger::new(vm);
+        let read = debugger.add("break consumed && at == 0 && in == 5".parse().unwrap());
+        let doubled = debugger.add("watch mem[15]".parse().unwrap());
+        assert_eq!(
+            debugger.run(),
+            Stop::Watch {
+                id: doubled,
+                old: 0,
+                new: 4
+            }
+        );
+        assert_eq!(
+            debugger.run(),
+            Stop::Watch {
+                id: doubled,
+                old: 4,
+                new: 8
+            }
+        );
+        //Both trigger on that step, the first one added wins
+        assert_eq!(debugger.run(), Stop::Break(read));
+        assert_eq!(
+            debugger.run(),
+            Stop::Watch {
+                id: doubled,
+                old: 5,
+                new: 10
+            }
+        );
+        assert_eq!(debugger.run(), Stop::State(State::WaitingForInput));
+        debugger.vm_mut().push_input(0);
+        assert_eq!(
+            debugger.run(),
+            Stop::Watch {
+                id: doubled,
+                old: 10,
+                new: 0
+            }
+        );
+        assert_eq!(debugger.run(), Stop::State(State::Halted));
+        assert_eq!(debugger.vm().output(), [8, 10]);
+    }
+}
@@ -0,0 +1,58 @@
+/* Random small programs, run through every backend (see intcode_computer::differential), and through the decompiler.
+ * Programs are mostly made of valid instructions pointing inside the program, with a bit of garbage thrown in,
+ * so that both the happy paths and the crashes get exercised.
+ */
+use intcode_computer::decompile;
+use intcode_computer::differential::compare;
+use proptest::prelude::*;
+
+const MAX_STEPS: usize = 1000;
+
+//One instruction: opcode with random modes, followed by the right number of parameters
+fn instruction(len: i64) -> impl Strategy<Value = Vec<i64>> {
+    let opcode = prop::sample::select(vec![1i64, 2, 3, 4, 5, 6, 7, 8, 9, 99]);
+    let modes = prop::collection::vec(0i64..3, 3);
+    let params = prop::collection::vec(-2..len + 2, 3);
+    (opcode, modes, params).prop_map(|(opcode, modes, params)| {
+        let arity = match opcode {
+            1 | 2 | 7 | 8 => 3,
+            5 | 6 => 2,
+            3 | 4 | 9 => 1,
+            _ => 0,
+        };
+        let mut words = vec![opcode + 100 * modes[0] + 1000 * modes[1] + 10000 * modes[2]];
+        words.extend(&params[..arity]);
+        words
+    })
+}
+
+fn program() -> impl Strategy<Value = Vec<i64>> {
+    prop::collection::vec(
+        prop_oneof![
+            8 => instruction(24),
+            //Garbage: invalid opcodes, invalid modes, data
+            1 => prop::collection::vec(-100i64..100_000, 1..3),
+        ],
+        1..8,
+    )
+    .prop_map(|chunks| chunks.concat())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(2000))]
+    #[test]
+    fn backends_agree(program in program(), input in prop::collection::vec(-10i64..10, 0..4)) {
+        //Keep the test output readable, the panics are expected and compared by message
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(|_| {}));
+        let result = compare(&program, &input, MAX_STEPS);
+        std::panic::set_hook(hook);
+        prop_assert!(result.is_ok(), "{}", result.unwrap_err());
+    }
+
+    //Whatever the program does, decompiling it gives code or an error, never a panic
+    #[test]
+    fn decompiles_anything(program in program()) {
+        let _ = decompile(&program);
+    }
+}
@@ -0,0 +1,83 @@
+/* Every case of every file in specs/ is a test of its own, named file::case.
+ * The default test harness needs its tests known when it gets compiled, so this one replaces it
+ * (harness = false in Cargo.toml): adding a spec file, or a case to one, is all it takes to add a test.
+ * Like the default harness, an argument only runs the tests whose name contains it.
+ * The spec format is described in intcode_computer::spec.
+ */
+use intcode_computer::spec::{parse, Spec};
+use std::path::Path;
+
+//Test names, and their case (or why their file couldn't be read)
+fn cases() -> Vec<(String, Result<Spec, String>)> {
+    let specs = Path::new(env!("CARGO_MANIFEST_DIR")).join("specs");
+    let mut paths: Vec<_> = std::fs::read_dir(&specs)
+        .unwrap_or_else(|error| panic!("Can't read {}: {}", specs.display(), error))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "spec"))
+        .collect();
+    paths.sort();
+
+    let mut cases = vec![];
+    for path in paths {
+        let file = path.file_stem().unwrap().to_string_lossy().to_string();
+        let parsed = std::fs::read_to_string(&path)
+            .map_err(|error| error.to_string())
+            .and_then(|text| parse(&text));
+        match parsed {
+            Ok(specs) => cases.extend(
+                specs
+                    .into_iter()
+                    .map(|spec| (format!("{}::{}", file, spec.name), Ok(spec))),
+            ),
+            //The whole file fails, as a single test
+            Err(error) => cases.push((file, Err(error))),
+        }
+    }
+    cases
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let filter = args.iter().find(|arg| !arg.starts_with('-'));
+    let mut cases = cases();
+    cases.retain(|(name, _)| filter.is_none_or(|filter| name.contains(filter.as_str())));
+    if args.iter().any(|arg| arg == "--list") {
+        for (name, _) in &cases {
+            println!("{}: test", name);
+        }
+        return;
+    }
+
+    //Crashes are expected, and check reports them along with everything else
+    std::panic::set_hook(Box::new(|_| {}));
+    println!("\nrunning {} tests", cases.len());
+    let mut failures = vec![];
+    for (name, spec) in &cases {
+        let result = match spec {
+            Ok(spec) => spec.check(),
+            Err(error) => Err(error.clone()),
+        };
+        match result {
+            Ok(()) => println!("test {} ... ok", name),
+            Err(problem) => {
+                println!("test {} ... FAILED", name);
+                failures.push(format!("{}: {}", name, problem));
+            }
+        }
+    }
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for failure in &failures {
+            println!("    {}", failure);
+        }
+    }
+    println!(
+        "\ntest result: {}. {} passed; {} failed\n",
+        if failures.is_empty() { "ok" } else { "FAILED" },
+        cases.len() - failures.len(),
+        failures.len()
+    );
+    if !failures.is_empty() {
+        std::process::exit(101);
+    }
+}
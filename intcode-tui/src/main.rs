@@ -0,0 +1,320 @@
+mod view;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::style::{Attribute, Color, Print, SetAttribute, SetForegroundColor};
+use crossterm::{cursor, execute, queue, terminal};
+use intcode_computer::*;
+use std::io::{stdout, Write};
+use std::time::Duration;
+use view::*;
+
+const USAGE: &str = "Usage: intcode-tui <program file> [input values, comma separated]";
+const KEYS: &str =
+    "space run/pause  s step  +/- speed  x hex  i input  up/down scroll  f follow ip  q quit";
+
+//Raw mode and the alternate screen, left when dropped (even by a panic)
+struct Terminal;
+impl Terminal {
+    fn enter() -> std::io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        //From here on the terminal gets restored, even if the next part fails
+        let guard = Terminal;
+        execute!(stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(guard)
+    }
+
+    fn restore() {
+        execute!(stdout(), cursor::Show, terminal::LeaveAlternateScreen).ok();
+        terminal::disable_raw_mode().ok();
+    }
+}
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        Terminal::restore();
+    }
+}
+
+//Everything about the screen that isn't the VM
+struct Screen {
+    viewer: Viewer,
+    //Steps run per frame while running
+    speed: usize,
+    //First row of the memory grid, None to keep the ip in the middle
+    scroll: Option<usize>,
+    //What's being typed after pressing i
+    typing: Option<String>,
+}
+
+/* Typed input: numbers separated by commas or spaces,
+ * or a line of text starting with " that's sent as ASCII with a newline (see Vm::push_line).
+ */
+fn parse_input(text: &str) -> Result<Vec<i64>, String> {
+    match text.strip_prefix('"') {
+        Some(line) if line.is_ascii() => {
+            Ok(line.bytes().map(|b| b as i64).chain(Some(10)).collect())
+        }
+        Some(_) => Err("Only ASCII can be sent to the computer".to_string()),
+        None => text
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                s.parse::<i64>()
+                    .map_err(|_| format!("Invalid input \"{}\"", s))
+            })
+            .collect(),
+    }
+}
+
+//Writes the line at (x, y), cut or padded to exactly width columns so that whatever was there before is gone
+fn put(out: &mut impl Write, x: u16, y: u16, width: usize, line: &Line) -> std::io::Result<()> {
+    queue!(out, cursor::MoveTo(x, y))?;
+    let mut left = width;
+    for (text, highlight) in line {
+        let text: String = text.chars().take(left).collect();
+        left -= text.chars().count();
+        match highlight {
+            Highlight::Plain => queue!(out, Print(text))?,
+            Highlight::Instruction => queue!(
+                out,
+                SetAttribute(Attribute::Reverse),
+                Print(text),
+                SetAttribute(Attribute::NoReverse)
+            )?,
+            Highlight::Written => queue!(
+                out,
+                SetForegroundColor(Color::Yellow),
+                SetAttribute(Attribute::Bold),
+                Print(text),
+                SetAttribute(Attribute::NormalIntensity),
+                SetForegroundColor(Color::Reset)
+            )?,
+            Highlight::Faded => queue!(
+                out,
+                SetForegroundColor(Color::DarkYellow),
+                Print(text),
+                SetForegroundColor(Color::Reset)
+            )?,
+            Highlight::Dim => queue!(
+                out,
+                SetForegroundColor(Color::DarkGrey),
+                Print(text),
+                SetForegroundColor(Color::Reset)
+            )?,
+        }
+    }
+    queue!(out, Print(" ".repeat(left)))
+}
+
+//Words per line in the memory grid, and how many lines it has, for a terminal that big
+fn grid_size(width: usize, height: usize) -> (usize, usize) {
+    (
+        Viewer::columns((width * 3 / 5).max(20).min(width)),
+        height.max(8) - 2,
+    )
+}
+
+//The first line of memory on screen: wherever it was scrolled to, or with the ip in the middle
+fn first_row(screen: &Screen, columns: usize, rows: usize) -> usize {
+    screen
+        .scroll
+        .unwrap_or_else(|| screen.viewer.ip_row(columns).saturating_sub(rows / 2))
+}
+
+fn title(text: &str) -> Line {
+    vec![(format!("-- {} ", text), Highlight::Dim)]
+}
+
+/*  status line
+ *  memory grid        | disassembly
+ *                     | input
+ *                     | output
+ *  keys (or what's being typed)
+ */
+fn draw(out: &mut impl Write, screen: &Screen) -> std::io::Result<()> {
+    let (width, height) = terminal::size()?;
+    let (width, height) = (width as usize, (height as usize).max(8));
+    let viewer = &screen.viewer;
+    let grid_width = (width * 3 / 5).max(20).min(width);
+    let side_x = grid_width + 1;
+    let side_width = width.saturating_sub(side_x);
+    let rows = height - 2;
+
+    put(
+        out,
+        0,
+        0,
+        width,
+        &vec![(viewer.status_line(), Highlight::Plain)],
+    )?;
+
+    let (columns, _) = grid_size(width, height);
+    let grid = viewer.memory_grid(columns, first_row(screen, columns, rows), rows);
+    for row in 0..rows {
+        let line = grid.get(row).cloned().unwrap_or_default();
+        put(out, 0, 1 + row as u16, grid_width, &line)?;
+    }
+
+    //The side panels, one after the other
+    let disassembly_rows = rows / 2;
+    let mut side = vec![title("Disassembly")];
+    let mut disassembly = viewer.disassembly(disassembly_rows - 1);
+    disassembly.resize(disassembly_rows - 1, vec![]);
+    side.extend(disassembly);
+    side.push(title("Input"));
+    side.push(viewer.input_line());
+    side.push(title("Output"));
+    side.extend(viewer.output_lines(rows.saturating_sub(side.len())));
+    side.resize(rows, vec![]);
+    for (row, line) in side.iter().enumerate() {
+        put(out, side_x as u16, 1 + row as u16, side_width, line)?;
+    }
+
+    let bottom = match &screen.typing {
+        Some(text) => vec![
+            ("input> ".to_string(), Highlight::Dim),
+            (text.clone(), Highlight::Plain),
+        ],
+        None => vec![(format!("{} (speed {})", KEYS, screen.speed), Highlight::Dim)],
+    };
+    put(out, 0, height as u16 - 1, width, &bottom)?;
+    out.flush()
+}
+
+//false when it's time to quit
+fn handle_key(screen: &mut Screen, key: KeyCode) -> bool {
+    if let Some(text) = &mut screen.typing {
+        match key {
+            KeyCode::Enter => {
+                //Anything that doesn't parse is kept, to be fixed
+                if let Ok(values) = parse_input(text) {
+                    screen.viewer.push_input(&values);
+                    screen.typing = None;
+                }
+            }
+            KeyCode::Esc => screen.typing = None,
+            KeyCode::Backspace => {
+                text.pop();
+            }
+            KeyCode::Char(c) => text.push(c),
+            _ => {}
+        }
+        return true;
+    }
+    let viewer = &mut screen.viewer;
+    match key {
+        KeyCode::Char('q') | KeyCode::Esc => return false,
+        KeyCode::Char(' ') => {
+            viewer.status = match viewer.status {
+                Status::Paused => Status::Running,
+                Status::Running => Status::Paused,
+                ref status => status.clone(),
+            }
+        }
+        KeyCode::Char('s') | KeyCode::Right => {
+            if viewer.status == Status::Running {
+                viewer.status = Status::Paused;
+            }
+            viewer.step();
+        }
+        KeyCode::Char('+') => screen.speed = (screen.speed * 2).min(1 << 16),
+        KeyCode::Char('-') => screen.speed = (screen.speed / 2).max(1),
+        KeyCode::Char('x') => viewer.hex = !viewer.hex,
+        KeyCode::Char('i') => screen.typing = Some(String::new()),
+        KeyCode::Char('f') => screen.scroll = None,
+        KeyCode::Up | KeyCode::PageUp | KeyCode::Down | KeyCode::PageDown => {
+            let (width, height) = terminal::size().unwrap_or((80, 24));
+            let (columns, rows) = grid_size(width as usize, height as usize);
+            let row = first_row(screen, columns, rows);
+            screen.scroll = Some(match key {
+                KeyCode::Up => row.saturating_sub(1),
+                KeyCode::PageUp => row.saturating_sub(10),
+                KeyCode::Down => row + 1,
+                _ => row + 10,
+            });
+        }
+        _ => {}
+    }
+    true
+}
+
+fn run(screen: &mut Screen) -> std::io::Result<()> {
+    let mut out = stdout();
+    loop {
+        draw(&mut out, screen)?;
+        let running = screen.viewer.status == Status::Running;
+        let wait = Duration::from_millis(if running { 30 } else { 250 });
+        if event::poll(wait)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Release && !handle_key(screen, key.code) {
+                    return Ok(());
+                }
+            }
+        }
+        for _ in 0..screen.speed {
+            if screen.viewer.status != Status::Running {
+                break;
+            }
+            screen.viewer.step();
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (path, input) = match &args[..] {
+        [path] => (path, vec![]),
+        [path, input] => (
+            path,
+            parse_input(input).unwrap_or_else(|error| {
+                eprintln!("{}", error);
+                std::process::exit(2);
+            }),
+        ),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    let program = load_program(path, &[]).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    });
+    let mut screen = Screen {
+        viewer: Viewer::new(&program, &input),
+        speed: 1,
+        scroll: None,
+        typing: None,
+    };
+
+    /* VM crashes are shown on screen, the default hook would print them over it.
+     * Any other panic is a bug in here: give the terminal back first, so that the message can be read.
+     */
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if !catching() {
+            Terminal::restore();
+            hook(info);
+        }
+    }));
+    let result = Terminal::enter().and_then(|_terminal| run(&mut screen));
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+    println!("{}", screen.viewer.status_line());
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    #[test]
+    fn typed_input() {
+        assert_eq!(parse_input("1, -2 3"), Ok(vec![1, -2, 3]));
+        assert_eq!(
+            parse_input("\"NOT A J"),
+            Ok(vec![78, 79, 84, 32, 65, 32, 74, 10])
+        );
+        assert!(parse_input("1,two").is_err());
+        assert!(parse_input("\"é").is_err());
+    }
+}
@@ -0,0 +1,333 @@
+use intcode_computer::*;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+//Writes older than that many steps aren't highlighted anymore
+const FADE: u64 = 16;
+//How many of the instructions that already ran are shown above the ip
+const HISTORY: usize = 6;
+//Width of a memory cell, anything longer gets replaced by #
+const CELL: usize = 6;
+
+thread_local! {
+    //Set while a step runs under catch_unwind, see catching
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+}
+
+//Whether a panic right now is one of the VM's, that will be caught and shown as a crash
+pub fn catching() -> bool {
+    CATCHING.with(Cell::get)
+}
+
+//How a piece of text should look, main.rs turns that into colors
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Highlight {
+    Plain,
+    //The words of the instruction about to run
+    Instruction,
+    //Written by the last instruction
+    Written,
+    //Written a few steps ago
+    Faded,
+    //Labels, and things that already happened
+    Dim,
+}
+
+//A line on screen, made of pieces that each look their own way
+pub type Line = Vec<(String, Highlight)>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Status {
+    Paused,
+    Running,
+    //Stopped on an In instruction: push some input to go on
+    WaitingForInput,
+    Halted,
+    //The VM panicked, with that message
+    Crashed(String),
+}
+
+/* A VM, plus what's needed to show it: which addresses were written when, and how to show numbers.
+ * Everything here builds lines of text, the terminal itself is dealt with in main.rs.
+ */
+pub struct Viewer {
+    vm: Vm,
+    pub status: Status,
+    pub hex: bool,
+    //The step each address was last written at (only writes that changed something can be seen)
+    written: HashMap<usize, u64>,
+}
+impl Viewer {
+    pub fn new(program: &[i64], input: &[i64]) -> Self {
+        Viewer {
+            vm: VmBuilder::new(program).input(input).trace(HISTORY).build(),
+            status: Status::Paused,
+            hex: false,
+            written: HashMap::new(),
+        }
+    }
+
+    pub fn can_step(&self) -> bool {
+        matches!(self.status, Status::Paused | Status::Running)
+    }
+
+    //Runs one instruction. A crash is caught and shown, instead of taking the whole screen down with it
+    pub fn step(&mut self) {
+        if !self.can_step() {
+            return;
+        }
+        //Only the instruction's destinations can change, what they hold before it runs
+        let mut before = vec![];
+        let vm = &mut self.vm;
+        CATCHING.with(|catching| catching.set(true));
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            before = vm
+                .destinations()
+                .into_iter()
+                .map(|address| (address, vm.memory().get(address).copied().unwrap_or(0)))
+                .collect();
+            vm.step()
+        }));
+        CATCHING.with(|catching| catching.set(false));
+        match result {
+            Ok(State::Running) => {}
+            Ok(State::WaitingForInput) => self.status = Status::WaitingForInput,
+            Ok(State::Halted) => self.status = Status::Halted,
+            Err(payload) => self.status = Status::Crashed(panic_message(&*payload)),
+        }
+        let steps = self.vm.steps();
+        for (address, value) in before {
+            //Memory that grew reads 0 before and after, that's not a write
+            if self.vm.memory().get(address).copied().unwrap_or(0) != value {
+                self.written.insert(address, steps);
+            }
+        }
+    }
+
+    pub fn push_input(&mut self, values: &[i64]) {
+        for &value in values {
+            self.vm.push_input(value);
+        }
+        if self.status == Status::WaitingForInput && !values.is_empty() {
+            self.status = Status::Paused;
+        }
+    }
+
+    //The addresses of the instruction at the ip (a single word if it isn't one)
+    fn instruction(&self) -> std::ops::Range<usize> {
+        let (memory, ip) = (self.vm.memory(), self.vm.ip());
+        let end = (ip + 4).min(memory.len());
+        let len = match memory
+            .get(ip..end)
+            .and_then(|words| disassemble(words).first().cloned())
+        {
+            Some(line) if !line.is_data => line.len,
+            _ => 1,
+        };
+        ip..ip + len
+    }
+
+    fn highlight(&self, address: usize) -> Highlight {
+        if self.instruction().contains(&address) {
+            return Highlight::Instruction;
+        }
+        match self.written.get(&address) {
+            Some(&step) if step == self.vm.steps() => Highlight::Written,
+            Some(&step) if self.vm.steps() - step < FADE => Highlight::Faded,
+            _ => Highlight::Plain,
+        }
+    }
+
+    fn cell(&self, value: i64) -> String {
+        let text = match (self.hex, value < 0) {
+            (false, _) => value.to_string(),
+            (true, false) => format!("{:x}", value),
+            (true, true) => format!("-{:x}", value.unsigned_abs()),
+        };
+        if text.len() > CELL {
+            "#".repeat(CELL)
+        } else {
+            format!("{:>width$}", text, width = CELL)
+        }
+    }
+
+    //How many cells fit in a grid that wide
+    pub fn columns(width: usize) -> usize {
+        (width.saturating_sub(CELL + 1) / (CELL + 1)).max(1)
+    }
+
+    //The grid row the ip is on
+    pub fn ip_row(&self, columns: usize) -> usize {
+        self.vm.ip() / columns
+    }
+
+    //rows lines of memory from first_row on, columns words per line, each line starting with its address
+    pub fn memory_grid(&self, columns: usize, first_row: usize, rows: usize) -> Vec<Line> {
+        let memory = self.vm.memory();
+        (first_row..first_row + rows)
+            .map(|row| row * columns)
+            .take_while(|&start| start < memory.len())
+            .map(|start| {
+                let mut line = vec![(format!("{:>width$}", start, width = CELL), Highlight::Dim)];
+                for (address, &value) in memory.iter().enumerate().skip(start).take(columns) {
+                    line.push((" ".to_string(), Highlight::Plain));
+                    line.push((self.cell(value), self.highlight(address)));
+                }
+                line
+            })
+            .collect()
+    }
+
+    //The last instructions that ran, then what's coming from the ip on
+    pub fn disassembly(&self, rows: usize) -> Vec<Line> {
+        let trace = self.vm.trace();
+        let history = trace.len().min(HISTORY).min(rows / 2);
+        let mut lines: Vec<Line> = trace
+            .iter()
+            .skip(trace.len() - history)
+            .map(|event| {
+                vec![(
+                    format!("  {:>6} | {}", event.ip, event.text),
+                    Highlight::Dim,
+                )]
+            })
+            .collect();
+        let (memory, ip) = (self.vm.memory(), self.vm.ip());
+        if ip < memory.len() {
+            for (index, line) in disassemble(&memory[ip..])
+                .iter()
+                .take(rows - history)
+                .enumerate()
+            {
+                let (marker, highlight) = if index == 0 {
+                    ('>', Highlight::Instruction)
+                } else {
+                    (' ', Highlight::Plain)
+                };
+                lines.push(vec![(
+                    format!("{} {:>6} | {}", marker, ip + line.address, line.text),
+                    highlight,
+                )]);
+            }
+        }
+        lines
+    }
+
+    pub fn input_line(&self) -> Line {
+        let input: Vec<String> = self
+            .vm
+            .pending_input()
+            .iter()
+            .map(|value| value.to_string())
+            .collect();
+        vec![(input.join(", "), Highlight::Plain)]
+    }
+
+    //The last rows values output, printable ones with their character next to them
+    pub fn output_lines(&self, rows: usize) -> Vec<Line> {
+        let output = self.vm.output();
+        output[output.len().saturating_sub(rows)..]
+            .iter()
+            .map(|&value| {
+                let mut line = vec![(value.to_string(), Highlight::Plain)];
+                if (32..127).contains(&value) {
+                    line.push((format!(" '{}'", value as u8 as char), Highlight::Dim));
+                }
+                line
+            })
+            .collect()
+    }
+
+    pub fn status_line(&self) -> String {
+        let status = match &self.status {
+            Status::Crashed(message) => format!("Crashed: {}", message),
+            status => format!("{:?}", status),
+        };
+        format!(
+            "{} | step {} | ip {} | relative base {} | {}",
+            status,
+            self.vm.steps(),
+            self.vm.ip(),
+            self.vm.relative_base(),
+            if self.hex { "hex" } else { "decimal" }
+        )
+    }
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use crate::view::*;
+    fn text(line: &Line) -> String {
+        line.iter().map(|(text, _)| text.as_str()).collect()
+    }
+
+    #[test]
+    fn self_modifying() {
+        //Day 5: multiplies the 33 at address 4 into a 99, then runs it
+        let mut viewer = Viewer::new(&[1002, 4, 3, 4, 33], &[]);
+        let grid = viewer.memory_grid(4, 0, 10);
+        assert_eq!(grid.len(), 2);
+        assert_eq!(text(&grid[0]), "     0   1002      4      3      4");
+        assert_eq!(grid[0][2].1, Highlight::Instruction);
+        assert_eq!(grid[1][2].1, Highlight::Plain);
+
+        viewer.step();
+        let grid = viewer.memory_grid(4, 0, 10);
+        assert_eq!(text(&grid[1]), "     4     99");
+        //The new 99 is both what just got written and the next instruction
+        assert_eq!(grid[1][2].1, Highlight::Instruction);
+        assert_eq!(viewer.written.get(&4), Some(&1));
+        assert_eq!(grid[0][2].1, Highlight::Plain);
+        assert_eq!(
+            text(&viewer.disassembly(4)[0]),
+            "       0 | MUL [4], 3, [4]"
+        );
+        assert_eq!(text(&viewer.disassembly(4)[1]), ">      4 | HALT");
+
+        viewer.step();
+        assert_eq!(viewer.status, Status::Halted);
+    }
+
+    #[test]
+    fn writes_fade() {
+        //Adds 1 to address 9 forever
+        let mut viewer = Viewer::new(&[1001, 9, 1, 9, 1105, 1, 0, 0, 0, 0], &[]);
+        viewer.step();
+        assert_eq!(viewer.highlight(9), Highlight::Written);
+        viewer.step();
+        assert_eq!(viewer.highlight(9), Highlight::Faded);
+        for _ in 0..FADE {
+            viewer.step();
+            viewer.step();
+        }
+        //Still being written every other step
+        assert_eq!(viewer.highlight(9), Highlight::Faded);
+        viewer.hex = true;
+        assert_eq!(viewer.cell(viewer.vm.memory()[9]), "    11");
+        assert_eq!(viewer.cell(-255), "   -ff");
+        assert_eq!(viewer.cell(1 << 40), "######");
+    }
+
+    #[test]
+    fn input_and_crash() {
+        //Echoes one value, then writes to a negative address
+        let mut viewer = Viewer::new(&[3, 9, 4, 9, 1, 0, 0, -1, 99], &[]);
+        viewer.step();
+        assert_eq!(viewer.status, Status::WaitingForInput);
+        viewer.push_input(&[72, 7]);
+        assert_eq!(viewer.status, Status::Paused);
+        assert_eq!(text(&viewer.input_line()), "72, 7");
+        viewer.step();
+        viewer.step();
+        assert_eq!(text(&viewer.output_lines(5)[0]), "72 'H'");
+        assert_eq!(text(&viewer.input_line()), "7");
+        viewer.step();
+        assert!(viewer
+            .status_line()
+            .starts_with("Crashed: Attempting to access a negative address"));
+        assert!(!viewer.can_step());
+        assert!(!catching());
+    }
+}